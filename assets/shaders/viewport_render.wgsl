@@ -1,13 +1,200 @@
 @group(0) @binding(0) var<uniform> stage_data: RenderStageData;
 @group(0) @binding(1) var output_texture: texture_storage_2d<rgba8unorm, read_write>;
 @group(0) @binding(2) var depth_texture: texture_storage_2d<r32float, read_write>;
+@group(0) @binding(9) var<uniform> viewport: Viewport;
 
 const RENDER_STAGE_DEPTH_PREPASS = 0u;
 const RENDER_STAGE_MAIN = 1u;
 
 struct RenderStageData {
     stage: u32,
-    output_resolution: vec2u
+    output_resolution: vec2u,
+    contree_size: u32,
+    contree_pages: u32,
+}
+
+/// The looking glass rays are cast through, see `Viewport`
+struct Viewport {
+    origin: vec3f,
+    direction: vec3f,
+    frustum: vec3f,
+    fov: f32,
+}
+
+/// The direction of the ray from the origin of the viewport through the given pixel of the looking glass.
+/// The glass is placed `fov` away from the origin, its width follows the aspect ratio of the output,
+/// so voxels are not stretched regardless of the resolution.
+fn ray_direction(pixel: vec2u) -> vec3f {
+    let resolution = vec2f(stage_data.output_resolution);
+    let aspect_ratio = resolution.x / resolution.y;
+    let glass_position = ((vec2f(pixel) + 0.5) / resolution - 0.5) * vec2f(aspect_ratio, -1.) * viewport.frustum.y;
+    let forward = normalize(viewport.direction);
+    let world_up = select(vec3f(0., 1., 0.), vec3f(0., 0., 1.), abs(forward.y) > 0.999);
+    let right = normalize(cross(forward, world_up));
+    let up = cross(right, forward);
+    return normalize(forward * viewport.fov + right * glass_position.x + up * glass_position.y);
+}
+
+// The first `BOUND_PAGES` pages of the baked contree, see `Contree::serialize_gpu` for the layout
+@group(0) @binding(3) var<storage, read> node_page_0: array<u32>;
+@group(0) @binding(10) var<storage, read> node_page_1: array<u32>;
+@group(0) @binding(11) var<storage, read> node_page_2: array<u32>;
+@group(0) @binding(12) var<storage, read> node_page_3: array<u32>;
+@group(0) @binding(4) var<storage, read> mip_page_0: array<u32>;
+@group(0) @binding(13) var<storage, read> mip_page_1: array<u32>;
+@group(0) @binding(14) var<storage, read> mip_page_2: array<u32>;
+@group(0) @binding(15) var<storage, read> mip_page_3: array<u32>;
+@group(0) @binding(5) var<storage, read> palette: array<u32>;
+#ifdef COMPACT_NODES
+@group(0) @binding(6) var<storage, read> node_offset_page_0: array<u32>;
+@group(0) @binding(16) var<storage, read> node_offset_page_1: array<u32>;
+@group(0) @binding(17) var<storage, read> node_offset_page_2: array<u32>;
+@group(0) @binding(18) var<storage, read> node_offset_page_3: array<u32>;
+#endif
+#ifdef NODE_STREAMING
+// With a node pool the nodes are slots of the pool, see `ContreeGPUCache`
+@group(0) @binding(7) var<storage, read_write> node_requests: array<atomic<u32>>;
+@group(0) @binding(8) var<storage, read_write> node_usage: array<atomic<u32>>;
+#endif
+
+const NODE_FLAG = 0x80000000u;
+const NODE_SIZE = 66u;
+const PALETTE_ENTRY_SIZE = 8u;
+const NODE_MISSING = 0xFFFFFFFFu;
+const NODE_REQUEST_LIMIT = 1024u;
+const PAGE_BITS = 18u;
+const PAGE_NODES = 1u << PAGE_BITS;
+const BOUND_PAGES = 4u;
+
+/// The page the given node is stored in, selected by the upper bits of its index
+fn node_page(node: u32) -> u32 {
+    return node >> PAGE_BITS;
+}
+
+/// The index of the given node inside its page
+fn page_node(node: u32) -> u32 {
+    return node & (PAGE_NODES - 1u);
+}
+
+/// The entry at the given index inside the node buffer of the given page
+fn node_entry(page: u32, index: u32) -> u32 {
+    switch page {
+        case 0u: { return node_page_0[index]; }
+        case 1u: { return node_page_1[index]; }
+        case 2u: { return node_page_2[index]; }
+        default: { return node_page_3[index]; }
+    }
+}
+
+/// The mip color of the given node
+fn node_mip(node: u32) -> u32 {
+    let index = page_node(node);
+    switch node_page(node) {
+        case 0u: { return mip_page_0[index]; }
+        case 1u: { return mip_page_1[index]; }
+        case 2u: { return mip_page_2[index]; }
+        default: { return mip_page_3[index]; }
+    }
+}
+
+/// True if the given node is inside one of the bound pages, so it can be traversed
+fn is_node_bound(node: u32) -> bool {
+    let page = node_page(node);
+    if page >= min(stage_data.contree_pages, BOUND_PAGES) {
+        return false;
+    }
+    var page_length: u32;
+    switch page {
+        case 0u: { page_length = arrayLength(&mip_page_0); }
+        case 1u: { page_length = arrayLength(&mip_page_1); }
+        case 2u: { page_length = arrayLength(&mip_page_2); }
+        default: { page_length = arrayLength(&mip_page_3); }
+    }
+    return page_node(node) < page_length;
+}
+
+#ifdef COMPACT_NODES
+/// The node offset of the given node, see `Contree::serialize_gpu`
+fn node_offset(node: u32) -> u32 {
+    let index = page_node(node);
+    switch node_page(node) {
+        case 0u: { return node_offset_page_0[index]; }
+        case 1u: { return node_offset_page_1[index]; }
+        case 2u: { return node_offset_page_2[index]; }
+        default: { return node_offset_page_3[index]; }
+    }
+}
+#endif
+
+#ifdef NODE_STREAMING
+/// Notes the slot of the given node as used in this frame, so it is not replaced by other nodes
+fn mark_node_used(node: u32) {
+    atomicOr(&node_usage[node / 32u], 1u << (node % 32u));
+}
+
+/// Requests the missing child of the given node at the given sectant to be loaded into the pool
+fn request_node(node: u32, sectant: u32) {
+    let request_index = atomicAdd(&node_requests[0], 1u);
+    if request_index < NODE_REQUEST_LIMIT {
+        atomicStore(&node_requests[1u + request_index], node * 64u + sectant);
+    }
+}
+#endif
+
+/// The index the entries of the given node start at inside the node buffer of its page
+fn node_start(node: u32) -> u32 {
+#ifdef COMPACT_NODES
+    return node_offset(node) & ~NODE_FLAG;
+#else
+    return page_node(node) * NODE_SIZE;
+#endif
+}
+
+/// The occupancy bits of the given node, x: sectants 0..32, y: sectants 32..64
+fn node_occupancy(node: u32) -> vec2u {
+    let page = node_page(node);
+    let start = node_start(node);
+    return vec2u(node_entry(page, start), node_entry(page, start + 1u));
+}
+
+/// True if the child at the given sectant is not empty
+fn is_occupied(occupancy: vec2u, sectant: u32) -> bool {
+    if sectant < 32u {
+        return 0u != (occupancy.x & (1u << sectant));
+    }
+    return 0u != (occupancy.y & (1u << (sectant - 32u)));
+}
+
+/// The number of bits set in the given sectant bits before the given sectant
+fn count_bits_before(bits: vec2u, sectant: u32) -> u32 {
+    if sectant < 32u {
+        return countOneBits(bits.x & ((1u << sectant) - 1u));
+    }
+    return countOneBits(bits.x) + countOneBits(bits.y & ((1u << (sectant - 32u)) - 1u));
+}
+
+/// The entry of the child of the given node at the given sectant:
+/// the index of the child node with NODE_FLAG set, otherwise the palette index of a leaf, 0 for empty children;
+/// NODE_MISSING for child nodes not loaded into the node pool
+fn child_entry(node: u32, sectant: u32) -> u32 {
+    let page = node_page(node);
+    let start = node_start(node);
+#ifdef COMPACT_NODES
+    // Only occupied children are stored, in the order of their sectants
+    let occupancy = node_occupancy(node);
+    if !is_occupied(occupancy, sectant) {
+        return 0u;
+    }
+    if 0u != (node_offset(node) & NODE_FLAG) {
+        // Runs of identical leaves are stored once, children inside a run share the entry of the child starting it
+        let run_starts = vec2u(node_entry(page, start + 2u), node_entry(page, start + 3u));
+        let run_index = count_bits_before(run_starts, sectant) - select(1u, 0u, is_occupied(run_starts, sectant));
+        return node_entry(page, start + 4u + run_index);
+    }
+    return node_entry(page, start + 2u + count_bits_before(occupancy, sectant));
+#else
+    return node_entry(page, start + 2u + sectant);
+#endif
 }
 
 /// In preprocess, a small resolution depth texture is rendered.
@@ -23,33 +210,166 @@ struct RenderStageData {
 /// No need to continue iteration if one voxel becomes too small to be covered by a pixel completely
 /// In these cases, there were no hits so far, which is valuable information
 /// even if no useful data can be collected moving forward.
+/// The main stage starts its rays from the minimum depth of the neighbouring texels,
+/// as a voxel between the rays of the prepass is hit by at least one of them.
 
-const detail = 32;
-const dist = 700.0;
-const steps = 100;
-const emptycells = 0.5;
+/// The largest distance between the rays of neighbouring depth texels, in voxels
+const PREPASS_RAY_SPACING = 0.5;
 
-//random function from https://www.shadertoy.com/view/MlsXDf
-fn rnd(v: vec4f) -> f32 { return fract(4e4*sin(dot(v,vec4f(13.46,41.74,-73.36,14.24))+17.34)); }
+/// The distance main stage rays are started before the depth provided by the prepass, in voxels
+const PREPASS_DEPTH_MARGIN = 2.;
 
-fn getvoxel(pp: vec3<i32>, size: i32) -> bool {
-    var p = pp;
-    p /= size;
-    if (p.x == 0 && p.y == 0) {
-        return false;
+/// The distance until which the rays of neighbouring depth texels are no further apart than `PREPASS_RAY_SPACING`.
+/// Rays are the furthest apart at the center of the looking glass, so it is used for every texel.
+fn prepass_max_distance() -> f32 {
+    return PREPASS_RAY_SPACING * viewport.fov * f32(stage_data.output_resolution.y) / viewport.frustum.y;
+}
+
+/// The distance the ray of the given pixel can be started from in the main stage,
+/// based on the depth texels around the pixel
+fn prepass_start_distance(pixel: vec2u) -> f32 {
+    let depth_size = vec2i(textureDimensions(depth_texture));
+    let texel = vec2i(pixel / 2u);
+    var min_depth = viewport.frustum.z;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let neighbour = clamp(texel + vec2i(x, y), vec2i(0), depth_size - 1);
+            min_depth = min(min_depth, textureLoad(depth_texture, neighbour).r);
+        }
     }
-    let ppp = vec4f(f32(p.x), f32(p.y), f32(p.z), f32(size));
+    return max(0., min_depth - PREPASS_DEPTH_MARGIN);
+}
+
+const MAX_DEPTH = 16u;
+const MAX_STEPS = 512u;
+const OOB_SECTANT = 64u;
+
+/// The result of casting a ray into the contree
+struct RayHit {
+    hit: bool,
+    distance: f32,
+    color: vec4f,
+    normal: vec3f,
+}
 
-    return rnd(ppp) > emptycells;
+/// The sectant of the child containing the given position, inside the node at the given position and size
+fn sectant_at(position: vec3f, node_min: vec3f, node_size: f32) -> u32 {
+    let index = clamp(vec3i(floor((position - node_min) * 4. / node_size)), vec3i(0), vec3i(3));
+    return u32(index.x + index.y * 4 + index.z * 16);
 }
 
-fn LP2D(i: f32) -> i32 {
-    let j = i32(round(i));
-    if (j == 0)
-    {
-        return detail;
+/// The distances along the ray where it enters and leaves the given box; x > y if the ray misses it
+fn intersect_box(origin: vec3f, inverse_direction: vec3f, box_min: vec3f, box_max: vec3f) -> vec2f {
+    let t1 = (box_min - origin) * inverse_direction;
+    let t2 = (box_max - origin) * inverse_direction;
+    let t_min = min(t1, t2);
+    let t_max = max(t1, t2);
+    return vec2f(
+        max(max(t_min.x, t_min.y), max(t_min.z, 0.)),
+        min(min(t_max.x, t_max.y), t_max.z)
+    );
+}
+
+/// Casts the given ray into the contree, stepping through the children of every node with a DDA:
+/// empty children are skipped based on the occupancy bits of their parent, occupied nodes are descended into.
+/// Only the first `BOUND_PAGES` pages of the node buffers are bound,
+/// nodes outside of them are shown with the color of their parent.
+/// The ray is only traced between the given distances; If there is no hit,
+/// the distance of the result is how far the ray is known to be empty.
+fn cast_ray(origin: vec3f, direction: vec3f, start_distance: f32, max_distance: f32) -> RayHit {
+    var result = RayHit(false, max_distance, vec4f(0.), vec3f(0.));
+    let tree_size = f32(stage_data.contree_size);
+    let safe_direction = select(direction, vec3f(1e-7), abs(direction) < vec3f(1e-7));
+    let inverse_direction = 1. / safe_direction;
+    let bounds = intersect_box(origin, inverse_direction, vec3f(0.), vec3f(tree_size));
+    if bounds.x > bounds.y || start_distance > bounds.y || bounds.x > max_distance {
+        return result;
+    }
+
+    let step_signum = vec3i(sign(safe_direction));
+    let positive = step(vec3f(0.), safe_direction);
+    let entry_distances = min(-origin * inverse_direction, (vec3f(tree_size) - origin) * inverse_direction);
+    var normal = -vec3f(step_signum) * vec3f(entry_distances == vec3f(bounds.x));
+    var distance = max(bounds.x, start_distance);
+
+    // The nodes containing the ray, their position and the sectant of the child the ray is currently in
+    var node_stack: array<u32, MAX_DEPTH>;
+    var node_min_stack: array<vec3f, MAX_DEPTH>;
+    var sectant_stack: array<u32, MAX_DEPTH>;
+    var depth = 0u;
+    var node_size = tree_size;
+    node_stack[0] = 0u;
+    node_min_stack[0] = vec3f(0.);
+    sectant_stack[0] = sectant_at(origin + direction * distance, vec3f(0.), node_size);
+#ifdef NODE_STREAMING
+    mark_node_used(0u);
+#endif
+
+    for (var i = 0u; i < MAX_STEPS; i += 1u) {
+        let node = node_stack[depth];
+        let sectant = sectant_stack[depth];
+        let child_size = node_size / 4.;
+        let child_min = node_min_stack[depth] + SECTANT_OFFSET_REGION_LUT[sectant] * node_size;
+        if is_occupied(node_occupancy(node), sectant) {
+            let entry = child_entry(node, sectant);
+            if 0u == (entry & NODE_FLAG) {
+                return RayHit(true, distance, unpack4x8unorm(palette[entry * PALETTE_ENTRY_SIZE]), normal);
+            }
+
+            let child = entry & ~NODE_FLAG;
+            if entry == NODE_MISSING || !is_node_bound(child) || depth + 1u >= MAX_DEPTH {
+#ifdef NODE_STREAMING
+                if entry == NODE_MISSING {
+                    request_node(node, sectant);
+                }
+#endif
+                return RayHit(true, distance, unpack4x8unorm(node_mip(node)), normal);
+            }
+
+            depth += 1u;
+            node_size = child_size;
+            node_stack[depth] = child;
+            node_min_stack[depth] = child_min;
+            sectant_stack[depth] = sectant_at(origin + direction * distance, child_min, child_size);
+#ifdef NODE_STREAMING
+            mark_node_used(child);
+#endif
+            continue;
+        }
+
+        // Step into the neighbouring child on the side the ray leaves the current one
+        let exit_distances = (child_min + positive * child_size - origin) * inverse_direction;
+        distance = min(exit_distances.x, min(exit_distances.y, exit_distances.z));
+        var step_vector = vec3i(0);
+        if distance == exit_distances.x {
+            step_vector.x = step_signum.x;
+        } else if distance == exit_distances.y {
+            step_vector.y = step_signum.y;
+        } else {
+            step_vector.z = step_signum.z;
+        }
+        normal = -vec3f(step_vector);
+        if distance > max_distance {
+            return result;
+        }
+
+        // Leaving a node continues in the neighbouring child of its parent
+        loop {
+            let next_sectant = SECTANT_STEP_RESULT_LUT[sectant_stack[depth]]
+                [step_vector.x + 1][step_vector.y + 1][step_vector.z + 1];
+            if next_sectant != OOB_SECTANT {
+                sectant_stack[depth] = next_sectant;
+                break;
+            }
+            if 0u == depth {
+                return result;
+            }
+            depth -= 1u;
+            node_size *= 4.;
+        }
     }
-    return min(j & -j, detail);
+    result.distance = distance;
+    return result;
 }
 
 @compute @workgroup_size(8, 8, 1)
@@ -57,41 +377,119 @@ fn update(
     @builtin(global_invocation_id) invocation_id: vec3<u32>,
     @builtin(num_workgroups) num_workgroups: vec3<u32>
 ) {
-    if stage_data.stage == RENDER_STAGE_MAIN {
-        let uv: vec2f = vec2f(invocation_id.xy) / vec2f(stage_data.output_resolution);
-        
-        let rayDir: vec3f = normalize(vec3(uv,1.0));
-        
-        var size = detail;
-        let rayOrg: vec3f = vec3f(1.,1.,1.);
-        var rayPos: vec3f = rayOrg;
-        let deltaDist: vec3f = 1.0/rayDir;
-        var hit: vec3f;
-        let rayOff: vec3f = sign(rayDir) * 0.01;
-        let positive: vec3f = step(-rayDir, vec3f(0.0));
-        
-        for (var i = 0; i < steps; i += 1)
-        {
-            if(getvoxel(vec3i(rayPos), size)) {
-                if (size <= 1) { break; }
-                size /= 2;
-                continue;
-            }
-            
-            hit = (positive - fract(rayPos / f32(size))) * f32(size) * deltaDist;
-
-            let min_hit = min(hit.x,min(hit.y,hit.z));
-            rayPos += min_hit * rayDir;
-            
-                if (min_hit == hit.x) {size = LP2D(rayPos.x); rayPos.x += rayOff.x; }
-            else if (min_hit == hit.y) {size = LP2D(rayPos.y); rayPos.y += rayOff.y; }
-            else if (min_hit == hit.z) {size = LP2D(rayPos.z); rayPos.z += rayOff.z; }
+    if stage_data.stage == RENDER_STAGE_DEPTH_PREPASS {
+        let max_distance = min(prepass_max_distance(), viewport.frustum.z);
+        let hit = cast_ray(viewport.origin, ray_direction(invocation_id.xy), 0., max_distance);
+        textureStore(depth_texture, vec2u(invocation_id.xy), vec4f(min(hit.distance, max_distance)));
+    }
 
+    if stage_data.stage == RENDER_STAGE_MAIN {
+        var fragColor = vec4f(0., 0., 0., 1.);
+        let hit = cast_ray(
+            viewport.origin,
+            ray_direction(invocation_id.xy),
+            prepass_start_distance(invocation_id.xy),
+            viewport.frustum.z
+        );
+        if hit.hit {
+            let light = normalize(vec3f(0.5, 1., 0.25));
+            let diffuse = 0.3 + 0.7 * max(dot(hit.normal, light), 0.);
+            fragColor = vec4f(hit.color.rgb * diffuse, 1.);
         }
-        
-        let val: f32 = fract(dot(floor(rayPos),vec3(15.23,754.345,3.454)));
-        var fragColor = vec4f(sin(val*vec3f(39.896,57.3225,48.25))*0.5+0.5, 1.0);
-        fragColor = sqrt(fragColor) - distance(rayOrg, rayPos)/dist;
         textureStore(output_texture, vec2u(invocation_id.xy), fragColor);
     }
-}
\ No newline at end of file
+}
+
+// Generated by src/bin/sectant_region_offset_lut.rs
+//const
+var<private> SECTANT_OFFSET_REGION_LUT: array<vec3f, 64> = array<vec3f, 64>(
+	vec3f(0.0, 0.0, 0.0),vec3f(0.25, 0.0, 0.0),vec3f(0.5, 0.0, 0.0),vec3f(0.75, 0.0, 0.0),
+	vec3f(0.0, 0.25, 0.0),vec3f(0.25, 0.25, 0.0),vec3f(0.5, 0.25, 0.0),vec3f(0.75, 0.25, 0.0),
+	vec3f(0.0, 0.5, 0.0),vec3f(0.25, 0.5, 0.0),vec3f(0.5, 0.5, 0.0),vec3f(0.75, 0.5, 0.0),
+	vec3f(0.0, 0.75, 0.0),vec3f(0.25, 0.75, 0.0),vec3f(0.5, 0.75, 0.0),vec3f(0.75, 0.75, 0.0),
+
+	vec3f(0.0, 0.0, 0.25),vec3f(0.25, 0.0, 0.25),vec3f(0.5, 0.0, 0.25),vec3f(0.75, 0.0, 0.25),
+	vec3f(0.0, 0.25, 0.25),vec3f(0.25, 0.25, 0.25),vec3f(0.5, 0.25, 0.25),vec3f(0.75, 0.25, 0.25),
+	vec3f(0.0, 0.5, 0.25),vec3f(0.25, 0.5, 0.25),vec3f(0.5, 0.5, 0.25),vec3f(0.75, 0.5, 0.25),
+	vec3f(0.0, 0.75, 0.25),vec3f(0.25, 0.75, 0.25),vec3f(0.5, 0.75, 0.25),vec3f(0.75, 0.75, 0.25),
+
+	vec3f(0.0, 0.0, 0.5),vec3f(0.25, 0.0, 0.5),vec3f(0.5, 0.0, 0.5),vec3f(0.75, 0.0, 0.5),
+	vec3f(0.0, 0.25, 0.5),vec3f(0.25, 0.25, 0.5),vec3f(0.5, 0.25, 0.5),vec3f(0.75, 0.25, 0.5),
+	vec3f(0.0, 0.5, 0.5),vec3f(0.25, 0.5, 0.5),vec3f(0.5, 0.5, 0.5),vec3f(0.75, 0.5, 0.5),
+	vec3f(0.0, 0.75, 0.5),vec3f(0.25, 0.75, 0.5),vec3f(0.5, 0.75, 0.5),vec3f(0.75, 0.75, 0.5),
+
+	vec3f(0.0, 0.0, 0.75),vec3f(0.25, 0.0, 0.75),vec3f(0.5, 0.0, 0.75),vec3f(0.75, 0.0, 0.75),
+	vec3f(0.0, 0.25, 0.75),vec3f(0.25, 0.25, 0.75),vec3f(0.5, 0.25, 0.75),vec3f(0.75, 0.25, 0.75),
+	vec3f(0.0, 0.5, 0.75),vec3f(0.25, 0.5, 0.75),vec3f(0.5, 0.5, 0.75),vec3f(0.75, 0.5, 0.75),
+	vec3f(0.0, 0.75, 0.75),vec3f(0.25, 0.75, 0.75),vec3f(0.5, 0.75, 0.75),vec3f(0.75, 0.75, 0.75),
+);
+
+// Generated by src/bin/sectant_step_result_lut.rs
+//const
+var<private> SECTANT_STEP_RESULT_LUT: array<array<array<array<u32, 3>, 3>, 3>, 64> = array<array<array<array<u32, 3>, 3>, 3>, 64>(
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,0,16),array<u32, 3>(64,4,20)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,1,17),array<u32, 3>(64,5,21))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,0,16),array<u32, 3>(64,4,20)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,1,17),array<u32, 3>(64,5,21)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,2,18),array<u32, 3>(64,6,22))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,1,17),array<u32, 3>(64,5,21)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,2,18),array<u32, 3>(64,6,22)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,3,19),array<u32, 3>(64,7,23))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,2,18),array<u32, 3>(64,6,22)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,3,19),array<u32, 3>(64,7,23)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,0,16),array<u32, 3>(64,4,20),array<u32, 3>(64,8,24)),array<array<u32, 3>, 3>(array<u32, 3>(64,1,17),array<u32, 3>(64,5,21),array<u32, 3>(64,9,25))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,0,16),array<u32, 3>(64,4,20),array<u32, 3>(64,8,24)),array<array<u32, 3>, 3>(array<u32, 3>(64,1,17),array<u32, 3>(64,5,21),array<u32, 3>(64,9,25)),array<array<u32, 3>, 3>(array<u32, 3>(64,2,18),array<u32, 3>(64,6,22),array<u32, 3>(64,10,26))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,1,17),array<u32, 3>(64,5,21),array<u32, 3>(64,9,25)),array<array<u32, 3>, 3>(array<u32, 3>(64,2,18),array<u32, 3>(64,6,22),array<u32, 3>(64,10,26)),array<array<u32, 3>, 3>(array<u32, 3>(64,3,19),array<u32, 3>(64,7,23),array<u32, 3>(64,11,27))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,2,18),array<u32, 3>(64,6,22),array<u32, 3>(64,10,26)),array<array<u32, 3>, 3>(array<u32, 3>(64,3,19),array<u32, 3>(64,7,23),array<u32, 3>(64,11,27)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,4,20),array<u32, 3>(64,8,24),array<u32, 3>(64,12,28)),array<array<u32, 3>, 3>(array<u32, 3>(64,5,21),array<u32, 3>(64,9,25),array<u32, 3>(64,13,29))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,4,20),array<u32, 3>(64,8,24),array<u32, 3>(64,12,28)),array<array<u32, 3>, 3>(array<u32, 3>(64,5,21),array<u32, 3>(64,9,25),array<u32, 3>(64,13,29)),array<array<u32, 3>, 3>(array<u32, 3>(64,6,22),array<u32, 3>(64,10,26),array<u32, 3>(64,14,30))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,5,21),array<u32, 3>(64,9,25),array<u32, 3>(64,13,29)),array<array<u32, 3>, 3>(array<u32, 3>(64,6,22),array<u32, 3>(64,10,26),array<u32, 3>(64,14,30)),array<array<u32, 3>, 3>(array<u32, 3>(64,7,23),array<u32, 3>(64,11,27),array<u32, 3>(64,15,31))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,6,22),array<u32, 3>(64,10,26),array<u32, 3>(64,14,30)),array<array<u32, 3>, 3>(array<u32, 3>(64,7,23),array<u32, 3>(64,11,27),array<u32, 3>(64,15,31)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,8,24),array<u32, 3>(64,12,28),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,9,25),array<u32, 3>(64,13,29),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,8,24),array<u32, 3>(64,12,28),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,9,25),array<u32, 3>(64,13,29),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,10,26),array<u32, 3>(64,14,30),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,9,25),array<u32, 3>(64,13,29),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,10,26),array<u32, 3>(64,14,30),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,11,27),array<u32, 3>(64,15,31),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,10,26),array<u32, 3>(64,14,30),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,11,27),array<u32, 3>(64,15,31),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(0,16,32),array<u32, 3>(4,20,36)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(1,17,33),array<u32, 3>(5,21,37))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(0,16,32),array<u32, 3>(4,20,36)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(1,17,33),array<u32, 3>(5,21,37)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(2,18,34),array<u32, 3>(6,22,38))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(1,17,33),array<u32, 3>(5,21,37)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(2,18,34),array<u32, 3>(6,22,38)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(3,19,35),array<u32, 3>(7,23,39))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(2,18,34),array<u32, 3>(6,22,38)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(3,19,35),array<u32, 3>(7,23,39)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(0,16,32),array<u32, 3>(4,20,36),array<u32, 3>(8,24,40)),array<array<u32, 3>, 3>(array<u32, 3>(1,17,33),array<u32, 3>(5,21,37),array<u32, 3>(9,25,41))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(0,16,32),array<u32, 3>(4,20,36),array<u32, 3>(8,24,40)),array<array<u32, 3>, 3>(array<u32, 3>(1,17,33),array<u32, 3>(5,21,37),array<u32, 3>(9,25,41)),array<array<u32, 3>, 3>(array<u32, 3>(2,18,34),array<u32, 3>(6,22,38),array<u32, 3>(10,26,42))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(1,17,33),array<u32, 3>(5,21,37),array<u32, 3>(9,25,41)),array<array<u32, 3>, 3>(array<u32, 3>(2,18,34),array<u32, 3>(6,22,38),array<u32, 3>(10,26,42)),array<array<u32, 3>, 3>(array<u32, 3>(3,19,35),array<u32, 3>(7,23,39),array<u32, 3>(11,27,43))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(2,18,34),array<u32, 3>(6,22,38),array<u32, 3>(10,26,42)),array<array<u32, 3>, 3>(array<u32, 3>(3,19,35),array<u32, 3>(7,23,39),array<u32, 3>(11,27,43)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(4,20,36),array<u32, 3>(8,24,40),array<u32, 3>(12,28,44)),array<array<u32, 3>, 3>(array<u32, 3>(5,21,37),array<u32, 3>(9,25,41),array<u32, 3>(13,29,45))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(4,20,36),array<u32, 3>(8,24,40),array<u32, 3>(12,28,44)),array<array<u32, 3>, 3>(array<u32, 3>(5,21,37),array<u32, 3>(9,25,41),array<u32, 3>(13,29,45)),array<array<u32, 3>, 3>(array<u32, 3>(6,22,38),array<u32, 3>(10,26,42),array<u32, 3>(14,30,46))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(5,21,37),array<u32, 3>(9,25,41),array<u32, 3>(13,29,45)),array<array<u32, 3>, 3>(array<u32, 3>(6,22,38),array<u32, 3>(10,26,42),array<u32, 3>(14,30,46)),array<array<u32, 3>, 3>(array<u32, 3>(7,23,39),array<u32, 3>(11,27,43),array<u32, 3>(15,31,47))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(6,22,38),array<u32, 3>(10,26,42),array<u32, 3>(14,30,46)),array<array<u32, 3>, 3>(array<u32, 3>(7,23,39),array<u32, 3>(11,27,43),array<u32, 3>(15,31,47)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(8,24,40),array<u32, 3>(12,28,44),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(9,25,41),array<u32, 3>(13,29,45),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(8,24,40),array<u32, 3>(12,28,44),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(9,25,41),array<u32, 3>(13,29,45),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(10,26,42),array<u32, 3>(14,30,46),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(9,25,41),array<u32, 3>(13,29,45),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(10,26,42),array<u32, 3>(14,30,46),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(11,27,43),array<u32, 3>(15,31,47),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(10,26,42),array<u32, 3>(14,30,46),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(11,27,43),array<u32, 3>(15,31,47),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(16,32,48),array<u32, 3>(20,36,52)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(17,33,49),array<u32, 3>(21,37,53))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(16,32,48),array<u32, 3>(20,36,52)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(17,33,49),array<u32, 3>(21,37,53)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(18,34,50),array<u32, 3>(22,38,54))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(17,33,49),array<u32, 3>(21,37,53)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(18,34,50),array<u32, 3>(22,38,54)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(19,35,51),array<u32, 3>(23,39,55))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(18,34,50),array<u32, 3>(22,38,54)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(19,35,51),array<u32, 3>(23,39,55)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(16,32,48),array<u32, 3>(20,36,52),array<u32, 3>(24,40,56)),array<array<u32, 3>, 3>(array<u32, 3>(17,33,49),array<u32, 3>(21,37,53),array<u32, 3>(25,41,57))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(16,32,48),array<u32, 3>(20,36,52),array<u32, 3>(24,40,56)),array<array<u32, 3>, 3>(array<u32, 3>(17,33,49),array<u32, 3>(21,37,53),array<u32, 3>(25,41,57)),array<array<u32, 3>, 3>(array<u32, 3>(18,34,50),array<u32, 3>(22,38,54),array<u32, 3>(26,42,58))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(17,33,49),array<u32, 3>(21,37,53),array<u32, 3>(25,41,57)),array<array<u32, 3>, 3>(array<u32, 3>(18,34,50),array<u32, 3>(22,38,54),array<u32, 3>(26,42,58)),array<array<u32, 3>, 3>(array<u32, 3>(19,35,51),array<u32, 3>(23,39,55),array<u32, 3>(27,43,59))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(18,34,50),array<u32, 3>(22,38,54),array<u32, 3>(26,42,58)),array<array<u32, 3>, 3>(array<u32, 3>(19,35,51),array<u32, 3>(23,39,55),array<u32, 3>(27,43,59)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(20,36,52),array<u32, 3>(24,40,56),array<u32, 3>(28,44,60)),array<array<u32, 3>, 3>(array<u32, 3>(21,37,53),array<u32, 3>(25,41,57),array<u32, 3>(29,45,61))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(20,36,52),array<u32, 3>(24,40,56),array<u32, 3>(28,44,60)),array<array<u32, 3>, 3>(array<u32, 3>(21,37,53),array<u32, 3>(25,41,57),array<u32, 3>(29,45,61)),array<array<u32, 3>, 3>(array<u32, 3>(22,38,54),array<u32, 3>(26,42,58),array<u32, 3>(30,46,62))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(21,37,53),array<u32, 3>(25,41,57),array<u32, 3>(29,45,61)),array<array<u32, 3>, 3>(array<u32, 3>(22,38,54),array<u32, 3>(26,42,58),array<u32, 3>(30,46,62)),array<array<u32, 3>, 3>(array<u32, 3>(23,39,55),array<u32, 3>(27,43,59),array<u32, 3>(31,47,63))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(22,38,54),array<u32, 3>(26,42,58),array<u32, 3>(30,46,62)),array<array<u32, 3>, 3>(array<u32, 3>(23,39,55),array<u32, 3>(27,43,59),array<u32, 3>(31,47,63)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(24,40,56),array<u32, 3>(28,44,60),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(25,41,57),array<u32, 3>(29,45,61),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(24,40,56),array<u32, 3>(28,44,60),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(25,41,57),array<u32, 3>(29,45,61),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(26,42,58),array<u32, 3>(30,46,62),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(25,41,57),array<u32, 3>(29,45,61),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(26,42,58),array<u32, 3>(30,46,62),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(27,43,59),array<u32, 3>(31,47,63),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(26,42,58),array<u32, 3>(30,46,62),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(27,43,59),array<u32, 3>(31,47,63),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(32,48,64),array<u32, 3>(36,52,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(33,49,64),array<u32, 3>(37,53,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(32,48,64),array<u32, 3>(36,52,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(33,49,64),array<u32, 3>(37,53,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(34,50,64),array<u32, 3>(38,54,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(33,49,64),array<u32, 3>(37,53,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(34,50,64),array<u32, 3>(38,54,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(35,51,64),array<u32, 3>(39,55,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(34,50,64),array<u32, 3>(38,54,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(35,51,64),array<u32, 3>(39,55,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(32,48,64),array<u32, 3>(36,52,64),array<u32, 3>(40,56,64)),array<array<u32, 3>, 3>(array<u32, 3>(33,49,64),array<u32, 3>(37,53,64),array<u32, 3>(41,57,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(32,48,64),array<u32, 3>(36,52,64),array<u32, 3>(40,56,64)),array<array<u32, 3>, 3>(array<u32, 3>(33,49,64),array<u32, 3>(37,53,64),array<u32, 3>(41,57,64)),array<array<u32, 3>, 3>(array<u32, 3>(34,50,64),array<u32, 3>(38,54,64),array<u32, 3>(42,58,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(33,49,64),array<u32, 3>(37,53,64),array<u32, 3>(41,57,64)),array<array<u32, 3>, 3>(array<u32, 3>(34,50,64),array<u32, 3>(38,54,64),array<u32, 3>(42,58,64)),array<array<u32, 3>, 3>(array<u32, 3>(35,51,64),array<u32, 3>(39,55,64),array<u32, 3>(43,59,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(34,50,64),array<u32, 3>(38,54,64),array<u32, 3>(42,58,64)),array<array<u32, 3>, 3>(array<u32, 3>(35,51,64),array<u32, 3>(39,55,64),array<u32, 3>(43,59,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(36,52,64),array<u32, 3>(40,56,64),array<u32, 3>(44,60,64)),array<array<u32, 3>, 3>(array<u32, 3>(37,53,64),array<u32, 3>(41,57,64),array<u32, 3>(45,61,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(36,52,64),array<u32, 3>(40,56,64),array<u32, 3>(44,60,64)),array<array<u32, 3>, 3>(array<u32, 3>(37,53,64),array<u32, 3>(41,57,64),array<u32, 3>(45,61,64)),array<array<u32, 3>, 3>(array<u32, 3>(38,54,64),array<u32, 3>(42,58,64),array<u32, 3>(46,62,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(37,53,64),array<u32, 3>(41,57,64),array<u32, 3>(45,61,64)),array<array<u32, 3>, 3>(array<u32, 3>(38,54,64),array<u32, 3>(42,58,64),array<u32, 3>(46,62,64)),array<array<u32, 3>, 3>(array<u32, 3>(39,55,64),array<u32, 3>(43,59,64),array<u32, 3>(47,63,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(38,54,64),array<u32, 3>(42,58,64),array<u32, 3>(46,62,64)),array<array<u32, 3>, 3>(array<u32, 3>(39,55,64),array<u32, 3>(43,59,64),array<u32, 3>(47,63,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(40,56,64),array<u32, 3>(44,60,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(41,57,64),array<u32, 3>(45,61,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(40,56,64),array<u32, 3>(44,60,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(41,57,64),array<u32, 3>(45,61,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(42,58,64),array<u32, 3>(46,62,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(41,57,64),array<u32, 3>(45,61,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(42,58,64),array<u32, 3>(46,62,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(43,59,64),array<u32, 3>(47,63,64),array<u32, 3>(64,64,64))),
+	array<array<array<u32, 3>, 3>, 3>(array<array<u32, 3>, 3>(array<u32, 3>(42,58,64),array<u32, 3>(46,62,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(43,59,64),array<u32, 3>(47,63,64),array<u32, 3>(64,64,64)),array<array<u32, 3>, 3>(array<u32, 3>(64,64,64),array<u32, 3>(64,64,64),array<u32, 3>(64,64,64)))
+
+);
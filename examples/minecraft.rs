@@ -1,20 +1,20 @@
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 use bevy::{prelude::*, window::WindowPlugin};
 
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 use iyes_perf_ui::{
     entries::diagnostics::{PerfUiEntryFPS, PerfUiEntryFPSWorst},
     ui::root::PerfUiRoot,
     PerfUiPlugin,
 };
 
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 const DISPLAY_RESOLUTION: [u32; 2] = [1024, 768];
 
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 fn main() {
     App::new()
         .insert_resource(ClearColor(Color::BLACK))
@@ -38,38 +38,47 @@ fn main() {
         .run();
 }
 
-#[cfg(feature = "bevy_wgpu")]
-fn setup(mut commands: Commands, images: ResMut<Assets<Image>>) {
-    use voxelhex::{raytracing::{bevy::types::RaymarchingViewSet, Viewport}, spatial::math::vector::V3c};
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
+fn setup(
+    mut commands: Commands,
+    images: ResMut<Assets<Image>>,
+    render_device: Res<bevy::render::renderer::RenderDevice>,
+) {
+    use voxelhex::{
+        contree::{
+            contree_gpu_serialization::GpuNodeEncoding,
+            types::{Contree, MaterialTable},
+        },
+        raytracing::{bevy::types::RaymarchingViewSet, Viewport},
+        spatial::math::vector::V3c,
+    };
 
     std::env::set_var("RUST_BACKTRACE", "1");
-    /*let tree: Contree;
-    let tree_path = "example_junk_minecraft";
-    if std::path::Path::new(tree_path).exists() {
-        tree = Contree::load(&tree_path).ok().unwrap();
-    } else {
-        println!("Loading minecraft.vox");
-        tree = match voxelhex::contree::Contree::load_vox_file("assets/models/minecraft.vox") {
-            Ok(tree_) => tree_,
-            Err(message) => panic!("Parsing model file failed with message: {message}"),
-        };
-        println!("Loaded minecraft.vox");
-        tree.save(&tree_path).ok().unwrap();
-    }*/
+    println!("Loading minecraft.vox");
+    let (tree, size) = match Contree::load_vox_file("assets/models/minecraft.vox") {
+        Ok(loaded) => loaded,
+        Err(message) => panic!("Parsing model file failed with message: {message:?}"),
+    };
+    println!("Loaded minecraft.vox");
+    let tree_size = size as f32;
+    commands.spawn(
+        tree.bake(&render_device, size, &MaterialTable::new(), GpuNodeEncoding::Full)
+            .unwrap(),
+    );
 
     let view = RaymarchingViewSet::new(
         Viewport::new(
             V3c {
-                x: 0.,
-                y: 0.,
-                z: 0.,
+                x: tree_size / 2.,
+                y: tree_size / 2.,
+                z: -tree_size,
             },
             V3c {
                 x: 0.,
                 y: 0.,
-                z: -1.,
+                z: 1.,
             },
-            V3c::new(10., 10., 200.),
+            V3c::new(10., 10., tree_size * 3.),
             6.,
         ),
         DISPLAY_RESOLUTION,
@@ -116,7 +125,7 @@ fn setup(mut commands: Commands, images: ResMut<Assets<Image>>) {
     ));
 }
 
-#[cfg(feature = "bevy_wgpu")]
+#[cfg(all(feature = "bevy_wgpu", feature = "dot_vox_support"))]
 fn direction_from_cam(cam: &PanOrbitCamera) -> Option<voxelhex::spatial::math::vector::V3cf32> {
     use voxelhex::spatial::math::vector::V3c;
 
@@ -134,8 +143,8 @@ fn direction_from_cam(cam: &PanOrbitCamera) -> Option<voxelhex::spatial::math::v
     }
 }
 
-#[cfg(not(feature = "bevy_wgpu"))]
+#[cfg(not(all(feature = "bevy_wgpu", feature = "dot_vox_support")))]
 fn main() {
-    println!("You probably forgot to enable the bevy_wgpu feature!");
+    println!("You probably forgot to enable the bevy_wgpu and dot_vox_support features!");
     //nothing to do when the feature is not enabled
 }
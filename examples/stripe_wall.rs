@@ -0,0 +1,118 @@
+#[cfg(feature = "bevy_wgpu")]
+use bevy::{prelude::*, window::WindowPlugin};
+
+#[cfg(feature = "bevy_wgpu")]
+use iyes_perf_ui::{
+    entries::diagnostics::{PerfUiEntryFPS, PerfUiEntryFPSWorst},
+    ui::root::PerfUiRoot,
+    PerfUiPlugin,
+};
+
+#[cfg(feature = "bevy_wgpu")]
+const DISPLAY_RESOLUTION: [u32; 2] = [1024, 768];
+
+#[cfg(feature = "bevy_wgpu")]
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((
+            DefaultPlugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    // uncomment for unthrottled FPS
+                    present_mode: bevy::window::PresentMode::AutoNoVsync,
+                    ..default()
+                }),
+                ..default()
+            }),
+            voxelhex::raytracing::bevy::RenderBevyPlugin,
+            bevy::diagnostic::FrameTimeDiagnosticsPlugin,
+            PerfUiPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+#[cfg(feature = "bevy_wgpu")]
+fn setup(
+    mut commands: Commands,
+    images: ResMut<Assets<Image>>,
+    render_device: Res<bevy::render::renderer::RenderDevice>,
+) {
+    use voxelhex::{
+        contree::{
+            contree_gpu_serialization::GpuNodeEncoding,
+            types::{Contree, MaterialTable},
+        },
+        raytracing::{bevy::types::RaymarchingViewSet, Viewport},
+        spatial::math::vector::V3c,
+    };
+
+    std::env::set_var("RUST_BACKTRACE", "1");
+    // A wall of colored stripes, rendered without needing any model files
+    const TREE_SIZE: u32 = 64;
+    let mut tree = Contree::new();
+    for x in 0..TREE_SIZE {
+        for y in 0..TREE_SIZE {
+            let color = [0xFF4040FF, 0x40FF40FF, 0x4040FFFF][((x + y) / 8 % 3) as usize];
+            tree.insert(TREE_SIZE, &V3c::new(x, y, 40), color).unwrap();
+        }
+    }
+    commands.spawn(
+        tree.bake(&render_device, TREE_SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+            .unwrap(),
+    );
+
+    let view = RaymarchingViewSet::new(
+        Viewport::new(
+            V3c {
+                x: 32.,
+                y: 32.,
+                z: -40.,
+            },
+            V3c {
+                x: 0.,
+                y: 0.,
+                z: 1.,
+            },
+            V3c::new(10., 10., 200.),
+            6.,
+        ),
+        DISPLAY_RESOLUTION,
+        images,
+    );
+
+    let mut display = Sprite::from_image(
+        view.view
+            .lock()
+            .unwrap()
+            .output_texture()
+            .clone(),
+    );
+    commands.insert_resource(view);
+    display.custom_size = Some(Vec2::new(1024., 768.));
+    commands.spawn(display);
+    commands.spawn(Camera2d::default());
+    commands.spawn((
+        PerfUiRoot::default(),
+        PerfUiEntryFPS {
+            label: "Frame Rate (current)".into(),
+            threshold_highlight: Some(60.0),
+            digits: 5,
+            precision: 2,
+            ..default()
+        },
+        PerfUiEntryFPSWorst {
+            label: "Frame Rate (worst)".into(),
+            threshold_highlight: Some(60.0),
+            digits: 5,
+            precision: 2,
+            ..default()
+        },
+    ));
+}
+
+#[cfg(not(feature = "bevy_wgpu"))]
+fn main() {
+    println!("You probably forgot to enable the bevy_wgpu feature!");
+    //nothing to do when the feature is not enabled
+}
@@ -27,7 +27,7 @@ fn main() {
     println!("CPU LUT: {:?}", sectant_offset);
     println!("WGSL LUT:");
     println!(
-        "//const\nvar<private> SECTANT_OFFSET_REGION_LUT: array<vec3f, {}> = array<u32, {}>(",
+        "//const\nvar<private> SECTANT_OFFSET_REGION_LUT: array<vec3f, {}> = array<vec3f, {}>(",
         BOX_NODE_CHILDREN_COUNT, BOX_NODE_CHILDREN_COUNT
     );
 
@@ -115,7 +115,7 @@ fn main() {
     println!("CPU LUT:{:?}", sectant_step_result);
     println!("WGSL LUT:");
     println!(
-        "//const\nvar<private> SECTANT_STEP_RESULT_LUT: array<array<array<array<vec3f, 3>, 3>, 3>,{}> = array<array<array<array<vec3f, 3>, 3>, 3>,{}>(",
+        "//const\nvar<private> SECTANT_STEP_RESULT_LUT: array<array<array<array<u32, 3>, 3>, 3>, {}> = array<array<array<array<u32, 3>, 3>, 3>, {}>(",
         BOX_NODE_CHILDREN_COUNT, BOX_NODE_CHILDREN_COUNT
     );
 
@@ -0,0 +1,381 @@
+use std::{collections::HashMap, ops::Range};
+
+#[cfg(feature = "bevy_wgpu")]
+use bevy::render::{
+    render_resource::{Buffer, BufferDescriptor, BufferUsages, MapMode},
+    renderer::RenderDevice,
+};
+#[cfg(feature = "bevy_wgpu")]
+use std::sync::{Arc, Mutex};
+
+use crate::contree::{
+    contree_gpu_serialization::{
+        merge_ranges, write_entry, GpuContreeData, GpuContreeRanges, GPU_NODE_FLAG, GPU_NODE_SIZE,
+        GPU_PAGE_NODES,
+    },
+    types::{ContreeError, AIR},
+};
+
+/// Child entry of a node inside the GPU node pool, whose child node is not loaded into the pool
+pub const GPU_NODE_MISSING: u32 = u32::MAX;
+
+/// Maximum number of missing nodes the GPU can request in a frame
+pub const GPU_NODE_REQUEST_LIMIT: usize = 1024;
+
+/// Number of frames the reports of the GPU can be on their way to the CPU at once;
+/// Reports are read once the GPU finished copying them, without waiting for it
+pub const GPU_FEEDBACK_FRAMES: usize = 3;
+
+/// A fixed number of node slots on the GPU, holding the part of a baked contree which is currently in view.
+/// The pool uses the layout of `GpuNodeEncoding::Full`, with node pointers being the indices of slots;
+/// Children not loaded into the pool are marked with `GPU_NODE_MISSING`.
+/// The GPU requests missing children it reaches during traversal, and notes every slot it used in a frame;
+/// Requested nodes are loaded into free slots, or into the slots used least recently.
+#[derive(Debug, Clone, Default)]
+pub struct ContreeGPUCache {
+    /// Occupancy bits and children of every slot
+    nodes: Vec<u32>,
+
+    /// The mip color of every slot
+    mips: Vec<u32>,
+
+    /// The index of the node inside the baked contree loaded into each slot
+    slot_nodes: Vec<Option<usize>>,
+
+    /// The slot of every node loaded into the pool
+    node_slots: HashMap<usize, usize>,
+
+    /// The node and sectant each slot was requested from
+    slot_parents: Vec<Option<(usize, usize)>>,
+
+    /// The frame each slot was last used in
+    last_used: Vec<u64>,
+
+    /// Slots without a node
+    free_slots: Vec<usize>,
+
+    /// The number of usage reports received
+    frame: u64,
+
+    /// The number of reports the GPU may make before it sees nodes loaded into the pool
+    feedback_latency: u64,
+
+    /// Ranges of entries changed since the last upload
+    dirty_ranges: GpuContreeRanges,
+}
+
+impl ContreeGPUCache {
+    /// Creates a pool with the given number of slots, with the root of the given baked contree loaded into slot 0.
+    /// The pool is uploaded into a single buffer, so it can hold at most `GPU_PAGE_NODES` nodes.
+    pub fn new(data: &GpuContreeData, capacity: usize) -> Result<Self, ContreeError> {
+        if !(1..=GPU_PAGE_NODES).contains(&capacity) {
+            return Err(ContreeError::LimitExceeded {
+                limit: "GPU node pool size",
+            });
+        }
+        let mut cache = Self {
+            nodes: vec![AIR; capacity * GPU_NODE_SIZE],
+            mips: vec![AIR; capacity],
+            slot_nodes: vec![None; capacity],
+            slot_parents: vec![None; capacity],
+            last_used: vec![0; capacity],
+            free_slots: (1..capacity).rev().collect(),
+            ..Default::default()
+        };
+        cache.slot_nodes[0] = Some(0);
+        cache.node_slots.insert(0, 0);
+        cache.write_slot(data, 0);
+        Ok(cache)
+    }
+
+    /// Occupancy bits and children of every slot, in the layout of `GpuNodeEncoding::Full`
+    pub fn nodes(&self) -> &[u32] {
+        &self.nodes
+    }
+
+    /// The mip color of every slot
+    pub fn mips(&self) -> &[u32] {
+        &self.mips
+    }
+
+    /// Sets the number of reports made by the GPU before changes to the pool reach it,
+    /// i.e. how many frames late the reports are processed. Nodes loaded or used within that many reports
+    /// before the last one are not replaced, as the GPU may not have had the chance to report them yet.
+    pub fn set_feedback_latency(&mut self, reports: u64) {
+        self.feedback_latency = reports;
+    }
+
+    /// The slot the node with the given index is loaded into, if any
+    pub fn slot_of(&self, node_index: usize) -> Option<usize> {
+        self.node_slots.get(&node_index).copied()
+    }
+
+    /// Updates the loaded nodes which changed inside the baked contree
+    /// * `changes` - The ranges changed in the arrays of the baked contree, as provided by `ContreeGPUHost::take_dirty_ranges`
+    pub fn update(&mut self, data: &GpuContreeData, changes: &GpuContreeRanges) {
+        let mut changed_nodes: Vec<usize> = changes
+            .nodes
+            .iter()
+            .flat_map(|range| range.start / GPU_NODE_SIZE..range.end.div_ceil(GPU_NODE_SIZE))
+            .chain(changes.mips.iter().flat_map(Range::clone))
+            .collect();
+        changed_nodes.sort_unstable();
+        changed_nodes.dedup();
+        for node_index in changed_nodes {
+            if let Some(slot) = self.slot_of(node_index) {
+                self.write_slot(data, slot);
+            }
+        }
+    }
+
+    /// Notes the slots used by the GPU in the last frame
+    /// * `usage` - A bit for every slot, set if it was used
+    pub fn register_usage(&mut self, usage: &[u32]) {
+        self.frame += 1;
+        for (word_index, word) in usage.iter().enumerate() {
+            for bit in 0..32 {
+                let slot = word_index * 32 + bit;
+                if 0 != word & (1 << bit) && slot < self.last_used.len() {
+                    self.last_used[slot] = self.frame;
+                }
+            }
+        }
+    }
+
+    /// Loads the requested nodes into the pool, replacing the nodes used least recently if there are no free slots.
+    /// Nodes used in the last frame, or within the feedback latency, are never replaced.
+    /// Returns the number of nodes loaded.
+    /// * `requests` - For every missing node: the slot of its parent multiplied by 64, plus its sectant
+    pub fn load_requested(&mut self, data: &GpuContreeData, requests: &[u32]) -> usize {
+        let mut eviction_candidates: Vec<usize> = (1..self.slot_nodes.len())
+            .filter(|slot| self.slot_nodes[*slot].is_some() && self.is_evictable(*slot))
+            .collect();
+        eviction_candidates.sort_by_key(|slot| std::cmp::Reverse(self.last_used[*slot]));
+
+        let mut loaded = 0;
+        for request in requests {
+            let parent_slot = *request as usize / 64;
+            let sectant = *request as usize % 64;
+            let Some(parent) = self.slot_nodes.get(parent_slot).copied().flatten() else {
+                continue;
+            };
+            let Some(entry) = data.nodes.get(parent * GPU_NODE_SIZE + 2 + sectant) else {
+                continue;
+            };
+            if 0 == entry & GPU_NODE_FLAG {
+                continue;
+            }
+            let node_index = (entry & !GPU_NODE_FLAG) as usize;
+            if self.node_slots.contains_key(&node_index) {
+                continue;
+            }
+            let slot = match self.free_slots.pop() {
+                Some(slot) => slot,
+                None => {
+                    // Candidates may have been taken by requests earlier in the list
+                    let Some(slot) = std::iter::from_fn(|| eviction_candidates.pop())
+                        .find(|slot| self.is_evictable(*slot))
+                    else {
+                        break;
+                    };
+                    self.evict(slot);
+                    slot
+                }
+            };
+            self.slot_nodes[slot] = Some(node_index);
+            self.node_slots.insert(node_index, slot);
+            self.slot_parents[slot] = Some((parent, sectant));
+            self.last_used[slot] = self.frame;
+            self.write_slot(data, slot);
+            write_entry(
+                &mut self.nodes,
+                &mut self.dirty_ranges.nodes,
+                parent_slot * GPU_NODE_SIZE + 2 + sectant,
+                slot as u32 | GPU_NODE_FLAG,
+            );
+            loaded += 1;
+        }
+        loaded
+    }
+
+    /// Provides the ranges of entries changed since the last call, merging overlapping and adjacent ones
+    pub fn take_dirty_ranges(&mut self) -> GpuContreeRanges {
+        let ranges = std::mem::take(&mut self.dirty_ranges);
+        GpuContreeRanges {
+            nodes: merge_ranges(ranges.nodes),
+            mips: merge_ranges(ranges.mips),
+            ..Default::default()
+        }
+    }
+
+    /// True if the node in the given slot was neither loaded nor used within the feedback latency
+    fn is_evictable(&self, slot: usize) -> bool {
+        self.last_used[slot] + self.feedback_latency < self.frame
+    }
+
+    /// Removes the node from the given slot, marking it missing in its parent if the parent is loaded.
+    /// Loaded children of the node stay in the pool until they are replaced.
+    fn evict(&mut self, slot: usize) {
+        if let Some(node_index) = self.slot_nodes[slot].take() {
+            self.node_slots.remove(&node_index);
+        }
+        if let Some((parent, sectant)) = self.slot_parents[slot].take() {
+            if let Some(parent_slot) = self.slot_of(parent) {
+                let entry_index = parent_slot * GPU_NODE_SIZE + 2 + sectant;
+                if self.nodes[entry_index] == slot as u32 | GPU_NODE_FLAG {
+                    write_entry(
+                        &mut self.nodes,
+                        &mut self.dirty_ranges.nodes,
+                        entry_index,
+                        GPU_NODE_MISSING,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Copies the node loaded into the given slot from the baked contree, pointing to the slots of loaded children
+    fn write_slot(&mut self, data: &GpuContreeData, slot: usize) {
+        let Some(node_index) = self.slot_nodes[slot] else {
+            return;
+        };
+        let start = slot * GPU_NODE_SIZE;
+        for offset in 0..GPU_NODE_SIZE {
+            let entry = data.nodes[node_index * GPU_NODE_SIZE + offset];
+            let entry = if 2 <= offset && 0 != entry & GPU_NODE_FLAG {
+                let child = (entry & !GPU_NODE_FLAG) as usize;
+                match self.slot_of(child) {
+                    Some(child_slot) => {
+                        self.slot_parents[child_slot] = Some((node_index, offset - 2));
+                        child_slot as u32 | GPU_NODE_FLAG
+                    }
+                    None => GPU_NODE_MISSING,
+                }
+            } else {
+                entry
+            };
+            write_entry(
+                &mut self.nodes,
+                &mut self.dirty_ranges.nodes,
+                start + offset,
+                entry,
+            );
+        }
+        write_entry(
+            &mut self.mips,
+            &mut self.dirty_ranges.mips,
+            slot,
+            data.mips[node_index],
+        );
+    }
+}
+
+/// The buffers the GPU reports the nodes it is missing and the slots it used in,
+/// and the copies of the reports of the last frames on their way to the CPU
+#[cfg(feature = "bevy_wgpu")]
+pub(crate) struct NodePoolBuffers {
+    /// The number of requests made in the frame, followed by at most `GPU_NODE_REQUEST_LIMIT` requests,
+    /// see `ContreeGPUCache::load_requested`
+    pub(crate) requests: Buffer,
+
+    /// A bit for every slot of the pool, set if the slot was used in the frame
+    pub(crate) usage: Buffer,
+
+    /// A ring of `GPU_FEEDBACK_FRAMES` readbacks, each of them holding the reports of a frame
+    pub(crate) readbacks: Vec<NodePoolReadback>,
+
+    /// The readback the reports of the next frame are copied into, the oldest one in the ring
+    pub(crate) next_readback: usize,
+}
+
+#[cfg(feature = "bevy_wgpu")]
+impl NodePoolBuffers {
+    pub(crate) fn new(device: &RenderDevice, capacity: usize) -> Self {
+        let create_buffer = |label, size: usize, usage| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: (size * std::mem::size_of::<u32>()) as u64,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        let feedback_usage =
+            BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+        let readback_usage = BufferUsages::MAP_READ | BufferUsages::COPY_DST;
+        Self {
+            requests: create_buffer(
+                "Node pool requests",
+                1 + GPU_NODE_REQUEST_LIMIT,
+                feedback_usage,
+            ),
+            usage: create_buffer("Node pool usage", capacity.div_ceil(32), feedback_usage),
+            readbacks: (0..GPU_FEEDBACK_FRAMES)
+                .map(|_| NodePoolReadback {
+                    requests: create_buffer(
+                        "Node pool requests readback",
+                        1 + GPU_NODE_REQUEST_LIMIT,
+                        readback_usage,
+                    ),
+                    usage: create_buffer(
+                        "Node pool usage readback",
+                        capacity.div_ceil(32),
+                        readback_usage,
+                    ),
+                    map_results: Arc::new(Mutex::new([None; 2])),
+                    pending: false,
+                })
+                .collect(),
+            next_readback: 0,
+        }
+    }
+}
+
+/// Copies of the reports of a frame, read on the CPU once the GPU finished writing them
+#[cfg(feature = "bevy_wgpu")]
+pub(crate) struct NodePoolReadback {
+    pub(crate) requests: Buffer,
+    pub(crate) usage: Buffer,
+
+    /// The result of mapping the requests and the usage buffer, set by wgpu once the copies are done
+    map_results: Arc<Mutex<[Option<bool>; 2]>>,
+
+    /// Set from the copy of the reports until they are read
+    pub(crate) pending: bool,
+}
+
+#[cfg(feature = "bevy_wgpu")]
+impl NodePoolReadback {
+    /// Requests the buffers to be mapped once the copies submitted into them are done, without waiting for them
+    pub(crate) fn map(&mut self, device: &RenderDevice) {
+        self.pending = true;
+        for (index, buffer) in [&self.requests, &self.usage].into_iter().enumerate() {
+            let map_results = self.map_results.clone();
+            device.map_buffer(&buffer.slice(..), MapMode::Read, move |result| {
+                map_results.lock().unwrap()[index] = Some(result.is_ok());
+            });
+        }
+    }
+
+    /// Provides the contents of the requests and the usage buffer if both of them are mapped,
+    /// and frees the readback for the next copy once both mappings are finished
+    pub(crate) fn take_mapped(&mut self) -> Option<(Vec<u32>, Vec<u32>)> {
+        if !self.pending {
+            return None;
+        }
+        let [Some(requests_mapped), Some(usage_mapped)] = *self.map_results.lock().unwrap() else {
+            return None;
+        };
+        *self.map_results.lock().unwrap() = [None; 2];
+        self.pending = false;
+        let read = |buffer: &Buffer, mapped: bool| {
+            mapped.then(|| {
+                let contents = bytemuck::cast_slice(&buffer.slice(..).get_mapped_range()).to_vec();
+                buffer.unmap();
+                contents
+            })
+        };
+        let requests = read(&self.requests, requests_mapped);
+        let usage = read(&self.usage, usage_mapped);
+        requests.zip(usage)
+    }
+}
@@ -1,70 +1,1025 @@
-use std::sync::Arc;
+#[cfg(feature = "bevy_wgpu")]
+use std::sync::{Arc, Mutex};
 
-use bevy::{ecs::component::Component, render::{extract_component::ExtractComponent, render_resource::{Buffer, BufferInitDescriptor, BufferUsages}, renderer::RenderDevice}};
+#[cfg(feature = "bevy_wgpu")]
+use bevy::{
+    ecs::component::Component,
+    render::{
+        extract_component::ExtractComponent,
+        render_resource::{Buffer, BufferInitDescriptor, BufferUsages},
+        renderer::RenderDevice,
+    },
+};
 
-use crate::contree::types::AIR;
+use std::{collections::HashMap, ops::Range};
+
+#[cfg(feature = "bevy_wgpu")]
+use crate::contree::contree_gpu_cache::{ContreeGPUCache, NodePoolBuffers, GPU_FEEDBACK_FRAMES};
+
+use crate::{
+    contree::{
+        detail::{is_valid_size, sectant_of, LoadBudget, CONTREE_NODE_DIMENSION},
+        types::{
+            Albedo, ContreeError, ContreeNode, LoadLimits, MaterialKind, MaterialProperties,
+            MaterialTable, VoxelData, AIR,
+        },
+    },
+    spatial::math::vector::V3c,
+};
 
 use super::types::Contree;
 
-#[derive(Component, ExtractComponent, Clone)]
-pub struct BakedContree {
-    buffer: Arc<Buffer>
+/// Set in the entries of the GPU representation which point to nodes
+pub const GPU_NODE_FLAG: u32 = 1 << 31;
+
+/// Number of nodes in a page of the node and mip arrays; every page is uploaded into its own GPU buffer,
+/// so a single buffer stays below the binding size limits while the whole structure can hold up to 2^31 nodes
+pub const GPU_PAGE_NODES: usize = 1 << 18;
+
+/// Number of pages bound to the render pipeline at once, each of them with its node, mip and node offset buffer.
+/// Baking a tree with more pages fails with `ContreeError::LimitExceeded { limit: "bound pages" }`,
+/// larger trees are meant to be streamed, see `Contree::bake_streamed`
+pub const GPU_BOUND_PAGES: usize = 4;
+
+/// Number of u32 entries in a serialized node: 2 for the occupancy bits, then one for every child
+pub const GPU_NODE_SIZE: usize = 2 + 64;
+
+/// Number of u32 entries in a serialized palette entry, see `Contree::serialize_gpu`
+pub const GPU_PALETTE_ENTRY_SIZE: usize = 8;
+
+/// The way nodes are stored in the GPU buffers, chosen at bake time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GpuNodeEncoding {
+    /// Every node stores all of its 64 children, so any child can be read directly
+    #[default]
+    Full,
+
+    /// Nodes store only their occupied children, found by counting the occupancy bits before them.
+    /// Runs of identical leaves among the occupied children are stored once, if that makes the node smaller.
+    Compact,
 }
 
-impl Contree {
-    /// Converts a contree into a flat structure ready to be sent to the GPU.
-    /// The GPU repersentation is an array of u32 with a max length of 2^31.
-    pub fn bake(&self, device: &RenderDevice) -> BakedContree {
-        fn serialize(contree: &Contree, serial_structure: &mut Vec<u32>) -> u32 {
-            let contree_pointer = serial_structure.len();
-            if let Contree::Node(node) = contree {
-                // Add contree metadata such as occupancy bits and mipmaps.
-                serial_structure.extend(bytemuck::cast_slice(bytemuck::bytes_of(&node.occupancy)));
-                let first_child_position = serial_structure.len();
-
-                const TEMP_CHILD_POINTER: u32 = 0xFFFFFFFF;
-                for child in node.children.iter() {
-                    serial_structure.push(match child {
-                        Some(Contree::Leaf(leaf_material)) => {
-                            // When the GPU reads a entry in the contree array, the first bit signifies if this is a leaf or node.
-                            // Thus the max number of voxel materials is 2^31 not 2^32.
-                            // Additionally the max length of the flattened contree structure is also 2^31.
-                            debug_assert!(leaf_material & (2^15) == 0, "Expected the first bit of contree leaf to be 0. Got {leaf_material}.");
-                            *leaf_material
-                        },
-                        Some(_) => TEMP_CHILD_POINTER,
-                        None => AIR
-                    });
+/// The arrays of a contree prepared for the GPU, each uploaded into its own storage buffers.
+/// See `Contree::serialize_gpu` for the layout.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GpuContreeData {
+    /// The encoding of the nodes uploaded to the GPU
+    pub encoding: GpuNodeEncoding,
+
+    /// Occupancy bits and children of every node; Only uploaded with `GpuNodeEncoding::Full`
+    pub nodes: Vec<u32>,
+
+    /// With `GpuNodeEncoding::Compact`: the position of every node inside its compact page, paged like the mips
+    pub node_offsets: Vec<u32>,
+
+    /// With `GpuNodeEncoding::Compact`: the compacted nodes of every page
+    pub compact_pages: Vec<Vec<u32>>,
+
+    /// The mip color of every node, indexed by node
+    pub mips: Vec<u32>,
+
+    /// The color and material properties of every distinct voxel data in the tree
+    pub palette: Vec<u32>,
+}
+
+impl GpuContreeData {
+    /// The node array split into pages of `GPU_PAGE_NODES` nodes
+    pub fn node_pages(&self) -> std::slice::Chunks<'_, u32> {
+        self.nodes.chunks(GPU_PAGE_NODES * GPU_NODE_SIZE)
+    }
+
+    /// The mip array split into pages of `GPU_PAGE_NODES` nodes
+    pub fn mip_pages(&self) -> std::slice::Chunks<'_, u32> {
+        self.mips.chunks(GPU_PAGE_NODES)
+    }
+
+    /// The node offset array split into pages of `GPU_PAGE_NODES` nodes
+    pub fn node_offset_pages(&self) -> std::slice::Chunks<'_, u32> {
+        self.node_offsets.chunks(GPU_PAGE_NODES)
+    }
+
+    /// The occupancy bits and the entries of every child of the given node, in the layout of `GpuNodeEncoding::Full`
+    fn expanded_node(&self, node_index: usize) -> Result<[u32; GPU_NODE_SIZE], ContreeError> {
+        if GpuNodeEncoding::Full == self.encoding {
+            let entries = self
+                .nodes
+                .get(node_index * GPU_NODE_SIZE..(node_index + 1) * GPU_NODE_SIZE)
+                .ok_or(ContreeError::UnexpectedEndOfData)?;
+            let mut expanded = [AIR; GPU_NODE_SIZE];
+            expanded.copy_from_slice(entries);
+            return Ok(expanded);
+        }
+
+        let node_offset = *self
+            .node_offsets
+            .get(node_index)
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+        let page = self
+            .compact_pages
+            .get(node_index / GPU_PAGE_NODES)
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+        let start = (node_offset & !GPU_NODE_FLAG) as usize;
+        let entry = |index: usize| {
+            page.get(start + index)
+                .copied()
+                .ok_or(ContreeError::UnexpectedEndOfData)
+        };
+        let occupancy = entry(0)? as u64 | ((entry(1)? as u64) << 32);
+        let run_starts = if 0 != node_offset & GPU_NODE_FLAG {
+            Some(entry(2)? as u64 | ((entry(3)? as u64) << 32))
+        } else {
+            None
+        };
+        let mut expanded = [AIR; GPU_NODE_SIZE];
+        expanded[0] = occupancy as u32;
+        expanded[1] = (occupancy >> 32) as u32;
+        for sectant in 0..64 {
+            if 0 == occupancy & (1 << sectant) {
+                continue;
+            }
+            let bits_before = |bits: u64| (bits & ((1 << sectant) - 1)).count_ones() as usize;
+            expanded[2 + sectant] = match run_starts {
+                // Children inside a run share the entry of the child starting it
+                Some(run_starts) => {
+                    let run_index = if 0 != run_starts & (1 << sectant) {
+                        bits_before(run_starts)
+                    } else {
+                        bits_before(run_starts).checked_sub(1).ok_or_else(|| {
+                            ContreeError::InvalidStructure(
+                                "Occupied child outside of any run".into(),
+                            )
+                        })?
+                    };
+                    entry(4 + run_index)?
                 }
+                None => entry(2 + bits_before(occupancy))?,
+            };
+        }
+        Ok(expanded)
+    }
+}
+
+/// Ranges of entries changed inside each array of `GpuContreeData`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GpuContreeRanges {
+    pub nodes: Vec<Range<usize>>,
+    pub node_offsets: Vec<Range<usize>>,
+    pub compact_pages: Vec<Vec<Range<usize>>>,
+    pub mips: Vec<Range<usize>>,
+    pub palette: Vec<Range<usize>>,
+}
+
+/// Leaves are stored inline in their parents, a leaf root is baked as a node of identical children
+fn leaf_root_node(data: VoxelData) -> ContreeNode {
+    ContreeNode {
+        mip: Albedo::from(data),
+        occupancy: if AIR == data { 0 } else { u64::MAX },
+        children: Box::new(std::array::from_fn(|_| Some(Contree::Leaf(data)))),
+    }
+}
+
+/// Packs the color so `unpack4x8unorm` provides its r, g, b, a components in order
+fn gpu_color(color: &Albedo) -> u32 {
+    u32::from_le_bytes([color.r, color.g, color.b, color.a])
+}
+
+fn from_gpu_color(color: u32) -> Albedo {
+    let [r, g, b, a] = color.to_le_bytes();
+    Albedo { r, g, b, a }
+}
+
+fn gpu_material_kind(kind: &MaterialKind) -> u32 {
+    match kind {
+        MaterialKind::Diffuse => 0,
+        MaterialKind::Metal => 1,
+        MaterialKind::Glass => 2,
+        MaterialKind::Emissive => 3,
+        MaterialKind::Blend => 4,
+        MaterialKind::Media => 5,
+    }
+}
+
+fn palette_entry(
+    data: VoxelData,
+    properties: &MaterialProperties,
+) -> [u32; GPU_PALETTE_ENTRY_SIZE] {
+    [
+        gpu_color(&Albedo::from(data)),
+        gpu_material_kind(&properties.kind),
+        properties.emission.to_bits(),
+        properties.transparency.to_bits(),
+        properties.roughness.to_bits(),
+        properties.metalness.to_bits(),
+        properties.refraction_index.to_bits(),
+        data,
+    ]
+}
+
+/// Notes the given range as changed, extending the last noted range if they are adjacent
+fn mark_range(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// Sorts the given ranges, merging overlapping and adjacent ones
+pub(crate) fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Sets the entry at the given index, and notes it as changed if it differs; Returns true if it was changed
+pub(crate) fn write_entry(
+    array: &mut [u32],
+    ranges: &mut Vec<Range<usize>>,
+    index: usize,
+    entry: u32,
+) -> bool {
+    if array[index] == entry {
+        return false;
+    }
+    array[index] = entry;
+    mark_range(ranges, index..index + 1);
+    true
+}
+
+/// Encodes a node given in the full layout into the compact layout: the occupancy bits of the non-empty children,
+/// then the entries of the non-empty children. If it makes the node smaller, runs of identical leaves
+/// are stored once instead: the occupancy bits are followed by the bits of the children starting a run,
+/// then the entry of every run.
+/// Returns the encoded node and whether its runs were collapsed.
+fn encode_compact_node(entries: &[u32]) -> (Vec<u32>, bool) {
+    let mut occupancy = 0u64;
+    let mut run_starts = 0u64;
+    let mut stored = Vec::new();
+    let mut runs: Vec<u32> = Vec::new();
+    for (sectant, entry) in entries[2..GPU_NODE_SIZE].iter().enumerate() {
+        if AIR == *entry {
+            continue;
+        }
+        occupancy |= 1 << sectant;
+        stored.push(*entry);
+
+        // Node pointers are unique, so only leaves continue a run
+        if 0 != entry & GPU_NODE_FLAG || Some(entry) != runs.last() {
+            run_starts |= 1 << sectant;
+            runs.push(*entry);
+        }
+    }
+    let collapsed = runs.len() + 2 < stored.len();
+    let mut compact = vec![occupancy as u32, (occupancy >> 32) as u32];
+    if collapsed {
+        compact.extend([run_starts as u32, (run_starts >> 32) as u32]);
+        compact.extend(runs);
+    } else {
+        compact.extend(stored);
+    }
+    (compact, collapsed)
+}
+
+/// CPU side copy of a baked contree, which can be updated in place after edits.
+/// Only the entries which changed since the last upload need to be written to the GPU,
+/// nodes added by edits are placed into the space left behind by removed nodes, or at the end of the buffer.
+#[derive(Debug, Clone, Default)]
+pub struct ContreeGPUHost {
+    data: GpuContreeData,
+
+    /// The edge length of the tree given at the last update
+    size: u32,
+
+    /// Material properties of the voxel data added to the palette
+    materials: MaterialTable,
+
+    /// The index of every voxel data inside the palette
+    palette_indices: HashMap<VoxelData, u32>,
+
+    /// The index of the nodes not used in the tree
+    free_nodes: Vec<usize>,
+
+    /// Positions changed in the tree since the last update
+    dirty_positions: Vec<V3c<u32>>,
+
+    /// Set if every node needs to be checked on the next update
+    all_dirty: bool,
+
+    /// Entries changed since the last upload
+    dirty_ranges: GpuContreeRanges,
+
+    /// Nodes changed since they were last compacted
+    changed_nodes: Vec<usize>,
+
+    /// The number of entries reserved for every node inside its compact page
+    compact_capacities: Vec<usize>,
+
+    /// The number of entries inside every compact page no longer used by any node
+    compact_waste: Vec<usize>,
+}
+
+impl ContreeGPUHost {
+    /// Bakes the given tree, see `Contree::serialize_gpu`
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn new(
+        tree: &Contree,
+        size: u32,
+        materials: &MaterialTable,
+        encoding: GpuNodeEncoding,
+    ) -> Result<Self, ContreeError> {
+        let mut host = Self {
+            data: GpuContreeData {
+                encoding,
+                ..Default::default()
+            },
+            materials: materials.clone(),
+            all_dirty: true,
+            ..Default::default()
+        };
+        host.palette_index(AIR)?;
+        host.allocate_node()?;
+        host.update(tree, size)?;
+        Ok(host)
+    }
+
+    /// The current arrays in the layout described at `Contree::serialize_gpu`,
+    /// except that children may be placed anywhere in the node array
+    pub fn data(&self) -> &GpuContreeData {
+        &self.data
+    }
+
+    /// The edge length of the baked tree in voxels, as given at the last update
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Marks the voxel at the given position as changed, to be processed on the next `update`
+    pub fn mark_dirty(&mut self, position: &V3c<u32>) {
+        self.dirty_positions.push(*position);
+    }
+
+    /// Marks the whole tree as changed, e.g. after it was replaced
+    pub fn mark_all_dirty(&mut self) {
+        self.all_dirty = true;
+    }
+
+    /// Sets the voxel in the tree and marks its position as changed
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn insert(
+        &mut self,
+        tree: &mut Contree,
+        size: u32,
+        position: &V3c<u32>,
+        data: VoxelData,
+    ) -> Result<(), ContreeError> {
+        tree.insert(size, position, data)?;
+        self.mark_dirty(position);
+        Ok(())
+    }
+
+    /// Sets the material properties of the given voxel data, updating its palette entry if it has one
+    pub fn set_material(&mut self, data: VoxelData, properties: MaterialProperties) {
+        self.materials.insert(data, properties);
+        if let Some(index) = self.palette_indices.get(&data) {
+            let start = *index as usize * GPU_PALETTE_ENTRY_SIZE;
+            for (offset, entry) in palette_entry(data, &properties).into_iter().enumerate() {
+                write_entry(
+                    &mut self.data.palette,
+                    &mut self.dirty_ranges.palette,
+                    start + offset,
+                    entry,
+                );
+            }
+        }
+    }
 
-                for (i, child) in node.children.iter().enumerate() {
-                    match child {
-                        Some(Contree::Leaf(_)) => {},
-                        Some(node) => {
-                            let pointer_to_child = serialize(node, serial_structure);
-                            serial_structure[first_child_position + i] = pointer_to_child;
-                        },
-                        None => {},
+    /// Brings the stored arrays in sync with the tree, by visiting only the subtrees
+    /// containing positions marked as changed. The tree needs to be the one the arrays were made from.
+    /// * `size` - The edge length of the tree, the positions are marked relative to
+    pub fn update(&mut self, tree: &Contree, size: u32) -> Result<(), ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        self.size = size;
+        let dirty_positions = std::mem::take(&mut self.dirty_positions);
+        let all_dirty = std::mem::take(&mut self.all_dirty);
+        if dirty_positions.is_empty() && !all_dirty {
+            return Ok(());
+        }
+
+        let subdivided;
+        let root = match tree {
+            Contree::Node(node) => node,
+            Contree::Leaf(data) => {
+                subdivided = leaf_root_node(*data);
+                &subdivided
+            }
+        };
+        let result = self.sync_node(root, 0, size, (!all_dirty).then_some(&dirty_positions[..]));
+        self.compact_changed_nodes();
+        if result.is_err() {
+            // Keep the changes to be retried, everything written so far is still a valid structure
+            self.dirty_positions = dirty_positions;
+            self.all_dirty = all_dirty;
+        }
+        result
+    }
+
+    /// Provides the ranges of entries changed since the last call, merging overlapping and adjacent ones
+    pub fn take_dirty_ranges(&mut self) -> GpuContreeRanges {
+        let ranges = std::mem::take(&mut self.dirty_ranges);
+        GpuContreeRanges {
+            nodes: merge_ranges(ranges.nodes),
+            node_offsets: merge_ranges(ranges.node_offsets),
+            compact_pages: ranges.compact_pages.into_iter().map(merge_ranges).collect(),
+            mips: merge_ranges(ranges.mips),
+            palette: merge_ranges(ranges.palette),
+        }
+    }
+
+    fn write_node_entry(&mut self, index: usize, entry: u32) {
+        if write_entry(
+            &mut self.data.nodes,
+            &mut self.dirty_ranges.nodes,
+            index,
+            entry,
+        ) {
+            self.changed_nodes.push(index / GPU_NODE_SIZE);
+        }
+    }
+
+    /// Updates the compact pages with the nodes changed since the last call
+    fn compact_changed_nodes(&mut self) {
+        let mut changed_nodes = std::mem::take(&mut self.changed_nodes);
+        if GpuNodeEncoding::Compact != self.data.encoding {
+            return;
+        }
+        changed_nodes.sort_unstable();
+        changed_nodes.dedup();
+        for node_index in changed_nodes {
+            self.write_compact_node(node_index);
+        }
+
+        // Pages where most of the space is left behind by moved nodes are packed again
+        for page_index in 0..self.data.compact_pages.len() {
+            if self.compact_waste[page_index] > self.data.compact_pages[page_index].len() / 2 {
+                self.repack_page(page_index);
+            }
+        }
+    }
+
+    /// Writes the given node into its compact page; in place if it fits into its reserved space,
+    /// otherwise at the end of the page
+    fn write_compact_node(&mut self, node_index: usize) {
+        let page_index = node_index / GPU_PAGE_NODES;
+        if self.data.compact_pages.len() <= page_index {
+            self.data.compact_pages.resize(page_index + 1, Vec::new());
+            self.compact_waste.resize(page_index + 1, 0);
+        }
+        if self.dirty_ranges.compact_pages.len() <= page_index {
+            self.dirty_ranges
+                .compact_pages
+                .resize(page_index + 1, Vec::new());
+        }
+        if self.data.node_offsets.len() <= node_index {
+            let start = self.data.node_offsets.len();
+            self.data.node_offsets.resize(node_index + 1, 0);
+            mark_range(&mut self.dirty_ranges.node_offsets, start..node_index + 1);
+            self.compact_capacities.resize(node_index + 1, 0);
+        }
+
+        let (encoded, collapsed) = encode_compact_node(
+            &self.data.nodes[node_index * GPU_NODE_SIZE..(node_index + 1) * GPU_NODE_SIZE],
+        );
+        let page = &mut self.data.compact_pages[page_index];
+        let page_ranges = &mut self.dirty_ranges.compact_pages[page_index];
+        let start = if encoded.len() <= self.compact_capacities[node_index] {
+            (self.data.node_offsets[node_index] & !GPU_NODE_FLAG) as usize
+        } else {
+            self.compact_waste[page_index] += self.compact_capacities[node_index];
+            self.compact_capacities[node_index] = encoded.len();
+            let start = page.len();
+            page.resize(start + encoded.len(), AIR);
+            mark_range(page_ranges, start..page.len());
+            start
+        };
+        for (offset, entry) in encoded.into_iter().enumerate() {
+            write_entry(page, page_ranges, start + offset, entry);
+        }
+        let node_offset = start as u32 | if collapsed { GPU_NODE_FLAG } else { 0 };
+        write_entry(
+            &mut self.data.node_offsets,
+            &mut self.dirty_ranges.node_offsets,
+            node_index,
+            node_offset,
+        );
+    }
+
+    /// Moves the nodes of the given compact page next to each other, dropping the space not used by any node
+    fn repack_page(&mut self, page_index: usize) {
+        let first_node = page_index * GPU_PAGE_NODES;
+        let last_node = (first_node + GPU_PAGE_NODES).min(self.data.node_offsets.len());
+        let page = std::mem::take(&mut self.data.compact_pages[page_index]);
+        let mut packed = Vec::with_capacity(page.len() - self.compact_waste[page_index]);
+        for node_index in first_node..last_node {
+            let node_offset = self.data.node_offsets[node_index];
+            let start = (node_offset & !GPU_NODE_FLAG) as usize;
+            let new_offset = packed.len() as u32 | (node_offset & GPU_NODE_FLAG);
+            packed.extend_from_slice(&page[start..start + self.compact_capacities[node_index]]);
+            write_entry(
+                &mut self.data.node_offsets,
+                &mut self.dirty_ranges.node_offsets,
+                node_index,
+                new_offset,
+            );
+        }
+        if self.dirty_ranges.compact_pages.len() <= page_index {
+            self.dirty_ranges
+                .compact_pages
+                .resize(page_index + 1, Vec::new());
+        }
+        let page_ranges = &mut self.dirty_ranges.compact_pages[page_index];
+        page_ranges.clear();
+        page_ranges.push(0..packed.len());
+        self.data.compact_pages[page_index] = packed;
+        self.compact_waste[page_index] = 0;
+    }
+
+    /// Provides the palette index of the given voxel data, adding a new entry for data not yet in the palette
+    fn palette_index(&mut self, data: VoxelData) -> Result<u32, ContreeError> {
+        if let Some(index) = self.palette_indices.get(&data) {
+            return Ok(*index);
+        }
+        let index = self.palette_indices.len() as u32;
+        // The first bit of node entries signifies if they point to a node, so there can be at most 2^31 materials
+        if 0 != index & GPU_NODE_FLAG {
+            return Err(ContreeError::LimitExceeded {
+                limit: "GPU material count",
+            });
+        }
+        let start = self.data.palette.len();
+        let properties = self.materials.get(&data).copied().unwrap_or_default();
+        self.data.palette.extend(palette_entry(data, &properties));
+        mark_range(
+            &mut self.dirty_ranges.palette,
+            start..self.data.palette.len(),
+        );
+        self.palette_indices.insert(data, index);
+        Ok(index)
+    }
+
+    /// Provides the index of an empty node, reusing freed nodes before growing the arrays
+    fn allocate_node(&mut self) -> Result<usize, ContreeError> {
+        if let Some(node_index) = self.free_nodes.pop() {
+            for index in node_index * GPU_NODE_SIZE..(node_index + 1) * GPU_NODE_SIZE {
+                self.write_node_entry(index, AIR);
+            }
+            return Ok(node_index);
+        }
+        // Node pointers are stored next to the flag bit, so there can be at most 2^31 nodes
+        let node_index = self.data.mips.len();
+        if 0 != node_index & GPU_NODE_FLAG as usize {
+            return Err(ContreeError::LimitExceeded {
+                limit: "GPU node count",
+            });
+        }
+        let start = self.data.nodes.len();
+        self.data.nodes.resize(start + GPU_NODE_SIZE, AIR);
+        mark_range(&mut self.dirty_ranges.nodes, start..self.data.nodes.len());
+        self.changed_nodes.push(node_index);
+        self.data.mips.push(0);
+        mark_range(&mut self.dirty_ranges.mips, node_index..node_index + 1);
+        Ok(node_index)
+    }
+
+    /// Marks the given node and all of its descendants as free
+    fn free_node(&mut self, node_index: usize) {
+        for index in node_index * GPU_NODE_SIZE + 2..(node_index + 1) * GPU_NODE_SIZE {
+            let entry = self.data.nodes[index];
+            if 0 != entry & GPU_NODE_FLAG {
+                self.free_node((entry & !GPU_NODE_FLAG) as usize);
+            }
+        }
+        self.free_nodes.push(node_index);
+    }
+
+    /// Updates the node stored at the given index to match the given node
+    /// * `node_size` - The edge length of the node, the dirty positions are relative to
+    /// * `dirty_positions` - Positions changed inside the node, or None if the whole node needs to be checked
+    fn sync_node(
+        &mut self,
+        node: &ContreeNode,
+        node_index: usize,
+        node_size: u32,
+        dirty_positions: Option<&[V3c<u32>]>,
+    ) -> Result<(), ContreeError> {
+        let start = node_index * GPU_NODE_SIZE;
+        self.write_node_entry(start, node.occupancy as u32);
+        self.write_node_entry(start + 1, (node.occupancy >> 32) as u32);
+        write_entry(
+            &mut self.data.mips,
+            &mut self.dirty_ranges.mips,
+            node_index,
+            gpu_color(&node.mip),
+        );
+        let child_size = (node_size / CONTREE_NODE_DIMENSION).max(1);
+        for (sectant, child) in node.children.iter().enumerate() {
+            let entry_index = start + 2 + sectant;
+            let current = self.data.nodes[entry_index];
+            let current_child =
+                (0 != current & GPU_NODE_FLAG).then_some((current & !GPU_NODE_FLAG) as usize);
+            let child_node = match child {
+                Some(Contree::Node(child_node)) => child_node,
+                _ => {
+                    let entry = match child {
+                        Some(Contree::Leaf(data)) => self.palette_index(*data)?,
+                        _ => AIR,
+                    };
+                    if let Some(child_index) = current_child {
+                        self.free_node(child_index);
                     }
+                    self.write_node_entry(entry_index, entry);
+                    continue;
+                }
+            };
+
+            match current_child {
+                Some(child_index) => {
+                    let child_dirty = dirty_positions.map(|positions| {
+                        positions
+                            .iter()
+                            .filter(|position| sectant == sectant_of(position, child_size))
+                            .map(|position| *position % child_size)
+                            .collect::<Vec<_>>()
+                    });
+                    match child_dirty {
+                        Some(positions) if positions.is_empty() => {}
+                        Some(positions) => {
+                            self.sync_node(child_node, child_index, child_size, Some(&positions))?
+                        }
+                        None => self.sync_node(child_node, child_index, child_size, None)?,
+                    }
+                }
+                None => {
+                    let child_index = self.allocate_node()?;
+                    self.sync_node(child_node, child_index, child_size, None)?;
+                    self.write_node_entry(entry_index, GPU_NODE_FLAG | child_index as u32);
                 }
-            } else {
-                panic!("Attempted to serialize contree leaf.");
             }
-            ((2^15) + contree_pointer).try_into().unwrap()
         }
+        Ok(())
+    }
+}
+
+/// Creates a storage buffer for the given array, with room to grow up to the given capacity
+#[cfg(feature = "bevy_wgpu")]
+pub(crate) fn create_contree_buffer(
+    device: &RenderDevice,
+    label: &'static str,
+    array: &[u32],
+    capacity: usize,
+) -> Buffer {
+    let mut contents = array.to_vec();
+    contents.resize(
+        (array.len() + array.len() / 4 + GPU_NODE_SIZE)
+            .min(capacity)
+            .max(array.len()),
+        AIR,
+    );
+    device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(&contents),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    })
+}
+
+/// The GPU buffers of a baked contree: a buffer for every page of the node and mip arrays, and one for the palette
+#[cfg(feature = "bevy_wgpu")]
+pub(crate) struct BakedContreeBuffers {
+    /// Pages of the node array, or the compact pages with `GpuNodeEncoding::Compact`
+    pub(crate) node_pages: Vec<Buffer>,
+
+    /// Pages of the node offset array, only used with `GpuNodeEncoding::Compact`
+    pub(crate) node_offset_pages: Vec<Buffer>,
+
+    pub(crate) mip_pages: Vec<Buffer>,
+    pub(crate) palette: Buffer,
+
+    /// The buffers the GPU reports its node usage in, only present if the nodes are streamed into a node pool
+    pub(crate) node_pool: Option<NodePoolBuffers>,
+}
+
+#[cfg(feature = "bevy_wgpu")]
+#[derive(Component, ExtractComponent, Clone)]
+pub struct BakedContree {
+    pub(crate) host: Arc<Mutex<ContreeGPUHost>>,
+    pub(crate) buffers: Arc<Mutex<BakedContreeBuffers>>,
+
+    /// The nodes on the GPU if only the part of the tree in view is uploaded, see `Contree::bake_streamed`
+    pub(crate) cache: Option<Arc<Mutex<ContreeGPUCache>>>,
+}
+
+#[cfg(feature = "bevy_wgpu")]
+impl BakedContree {
+    /// Uploads the arrays of the given host into GPU storage buffers
+    /// Fails with `ContreeError::LimitExceeded` if the arrays take more, than `GPU_BOUND_PAGES` pages
+    pub fn upload(device: &RenderDevice, mut host: ContreeGPUHost) -> Result<Self, ContreeError> {
+        check_bound_pages(&host.data)?;
+        host.take_dirty_ranges();
+        let node_pages = match host.data.encoding {
+            GpuNodeEncoding::Full => host
+                .data
+                .node_pages()
+                .map(|page| {
+                    create_contree_buffer(
+                        device,
+                        "Baked Contree nodes",
+                        page,
+                        GPU_PAGE_NODES * GPU_NODE_SIZE,
+                    )
+                })
+                .collect(),
+            GpuNodeEncoding::Compact => host
+                .data
+                .compact_pages
+                .iter()
+                .map(|page| create_contree_buffer(device, "Baked Contree nodes", page, usize::MAX))
+                .collect(),
+        };
+        let buffers = BakedContreeBuffers {
+            node_pages,
+            node_offset_pages: host
+                .data
+                .node_offset_pages()
+                .map(|page| {
+                    create_contree_buffer(
+                        device,
+                        "Baked Contree node offsets",
+                        page,
+                        GPU_PAGE_NODES,
+                    )
+                })
+                .collect(),
+            mip_pages: host
+                .data
+                .mip_pages()
+                .map(|page| {
+                    create_contree_buffer(device, "Baked Contree mips", page, GPU_PAGE_NODES)
+                })
+                .collect(),
+            palette: create_contree_buffer(
+                device,
+                "Baked Contree palette",
+                &host.data.palette,
+                usize::MAX,
+            ),
+            node_pool: None,
+        };
+        Ok(BakedContree {
+            host: Arc::new(Mutex::new(host)),
+            buffers: Arc::new(Mutex::new(buffers)),
+            cache: None,
+        })
+    }
+
+    /// Uploads a node pool of the given number of slots with only the root of the given host loaded into it,
+    /// alongside the palette of the host. Further nodes are uploaded as the GPU requests them.
+    pub fn upload_streamed(
+        device: &RenderDevice,
+        mut host: ContreeGPUHost,
+        capacity: usize,
+    ) -> Result<Self, ContreeError> {
+        host.take_dirty_ranges();
+        let mut cache = ContreeGPUCache::new(&host.data, capacity)?;
+        cache.set_feedback_latency(GPU_FEEDBACK_FRAMES as u64);
+        cache.take_dirty_ranges();
+        let buffers = BakedContreeBuffers {
+            node_pages: vec![create_contree_buffer(
+                device,
+                "Baked Contree node pool",
+                cache.nodes(),
+                cache.nodes().len(),
+            )],
+            node_offset_pages: Vec::new(),
+            mip_pages: vec![create_contree_buffer(
+                device,
+                "Baked Contree node pool mips",
+                cache.mips(),
+                cache.mips().len(),
+            )],
+            palette: create_contree_buffer(
+                device,
+                "Baked Contree palette",
+                &host.data.palette,
+                usize::MAX,
+            ),
+            node_pool: Some(NodePoolBuffers::new(device, capacity)),
+        };
+        Ok(BakedContree {
+            host: Arc::new(Mutex::new(host)),
+            buffers: Arc::new(Mutex::new(buffers)),
+            cache: Some(Arc::new(Mutex::new(cache))),
+        })
+    }
 
-        let mut serial_structure = vec![];
-        _ = serialize(self, &mut serial_structure);
-        assert!(serial_structure.len() <= 2^31);
+    /// Sets the voxel in the tree and marks its position as changed, see `ContreeGPUHost::insert`
+    pub fn insert(
+        &self,
+        tree: &mut Contree,
+        size: u32,
+        position: &V3c<u32>,
+        data: VoxelData,
+    ) -> Result<(), ContreeError> {
+        self.host.lock().unwrap().insert(tree, size, position, data)
+    }
+
+    /// Marks the voxel at the given position as changed, see `ContreeGPUHost::mark_dirty`
+    pub fn mark_dirty(&self, position: &V3c<u32>) {
+        self.host.lock().unwrap().mark_dirty(position);
+    }
 
-        let buffer = device.create_buffer_with_data(&BufferInitDescriptor{
-            label: Some("Baked Contree"),
-            contents: bytemuck::cast_slice(&serial_structure),
-            usage: BufferUsages::STORAGE,
+    /// Sets the material properties of the given voxel data, see `ContreeGPUHost::set_material`
+    pub fn set_material(&self, data: VoxelData, properties: MaterialProperties) {
+        self.host.lock().unwrap().set_material(data, properties);
+    }
+
+    /// Updates the baked arrays with the changes marked in the tree.
+    /// The changed entries are uploaded to the GPU by the render pipeline.
+    /// Unless the nodes are streamed, fails with `ContreeError::LimitExceeded` once the arrays
+    /// grow past `GPU_BOUND_PAGES` pages; nodes in further pages are drawn with the mip of their parent
+    pub fn update(&self, tree: &Contree, size: u32) -> Result<(), ContreeError> {
+        let mut host = self.host.lock().unwrap();
+        host.update(tree, size)?;
+        if self.cache.is_none() {
+            check_bound_pages(&host.data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fails if the arrays have more pages, than the render pipeline binds
+#[cfg(feature = "bevy_wgpu")]
+fn check_bound_pages(data: &GpuContreeData) -> Result<(), ContreeError> {
+    if data.mip_pages().len() > GPU_BOUND_PAGES {
+        return Err(ContreeError::LimitExceeded {
+            limit: "bound pages",
         });
+    }
+    Ok(())
+}
 
-        BakedContree {
-            buffer: Arc::new(buffer)
+impl Contree {
+    /// Converts a contree into flat arrays ready to be sent to the GPU, each of them an array of u32.
+    /// The node array holds at most 2^31 nodes:
+    /// * Every node takes `GPU_NODE_SIZE` entries: the low and high half of its occupancy bits, then its 64 children
+    /// * A child entry with `GPU_NODE_FLAG` set is the index of the child node, its entries start at index * `GPU_NODE_SIZE`;
+    ///   otherwise it is the palette index of a leaf, 0 for empty children
+    /// * The root node is at index 0, children are always placed after their parents,
+    ///   which no longer holds after updates through `ContreeGPUHost`
+    /// * The array is uploaded in pages of `GPU_PAGE_NODES` nodes, node i is at i % `GPU_PAGE_NODES`
+    ///   inside page i / `GPU_PAGE_NODES`; so the lower 18 bits of a node index address the node inside its page,
+    ///   the upper 13 bits select the page
+    ///
+    /// Node indices are kept at 31 bits: 2^31 full nodes take more than 500 GB, far beyond what a GPU can bind,
+    /// so the renderer binds the first `GPU_BOUND_PAGES` pages, and larger trees are streamed into a node pool instead.
+    ///
+    /// With `GpuNodeEncoding::Compact` the node array is kept on the CPU only, and the GPU receives
+    /// a compact page for every page of nodes, alongside the node offset array paged like the mips:
+    /// * The node offset of node i is the index its entries start at inside its compact page;
+    ///   with `GPU_NODE_FLAG` set if the node stores runs of identical leaves once
+    /// * A compact node starts with the low and high half of the bits of its non-empty children,
+    ///   followed by the entries of the non-empty children in the order of their sectants
+    /// * The entry of an occupied child is at `2 + countOneBits(occupancy & ((1 << sectant) - 1))`
+    /// * A node storing runs has the low and high half of the bits of the children starting a run after its
+    ///   occupancy bits, then the entry of every run: a run starts at every node pointer, and at every leaf
+    ///   differing from the occupied child before it. The entry of an occupied child is at
+    ///   `4 + countOneBits(run_starts & ((1 << sectant) - 1))`, minus one if the child does not start a run
+    /// * Runs are only stored if they make the node smaller, i.e. there are more than 2 occupied children
+    ///   than runs
+    ///
+    /// The mip array has an entry for every node: its color to be used when the node is too far away
+    /// to be traversed, paged the same way as the nodes. Colors are packed so that
+    /// `unpack4x8unorm` provides their r, g, b, a components.
+    ///
+    /// The palette array has `GPU_PALETTE_ENTRY_SIZE` entries for every distinct voxel data,
+    /// the first entry belongs to `AIR`:
+    /// * 0: the color of the voxel data, packed like the mips
+    /// * 1: the `MaterialKind`, in the order of its declaration starting from 0
+    /// * 2..7: emission, transparency, roughness, metalness and refraction index; f32 bits to be read with `bitcast<f32>`
+    /// * 7: the voxel data itself
+    ///
+    /// Voxel data without an entry in `materials` uses the default material properties.
+    /// Fails with `ContreeError::LimitExceeded` if the tree has more than 2^31 nodes or distinct voxel data.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn serialize_gpu(
+        &self,
+        size: u32,
+        materials: &MaterialTable,
+        encoding: GpuNodeEncoding,
+    ) -> Result<GpuContreeData, ContreeError> {
+        Ok(ContreeGPUHost::new(self, size, materials, encoding)?.data)
+    }
+
+    /// Converts a contree into flat arrays and uploads them to the GPU, see `Contree::serialize_gpu`
+    /// Fails with `ContreeError::LimitExceeded { limit: "bound pages" }` if the tree takes more, than
+    /// `GPU_BOUND_PAGES` pages of nodes, see `Contree::bake_streamed` for such trees
+    /// * `size` - The edge length of the tree, must be a power of 4
+    #[cfg(feature = "bevy_wgpu")]
+    pub fn bake(
+        &self,
+        device: &RenderDevice,
+        size: u32,
+        materials: &MaterialTable,
+        encoding: GpuNodeEncoding,
+    ) -> Result<BakedContree, ContreeError> {
+        BakedContree::upload(
+            device,
+            ContreeGPUHost::new(self, size, materials, encoding)?,
+        )
+    }
+
+    /// Uploads the palette and a pool of the given number of node slots to the GPU, holding only the nodes
+    /// reached while rendering, for trees which do not fit into GPU memory. The nodes are requested
+    /// by the GPU and loaded in the next frames, replacing the nodes used least recently when the pool is full.
+    /// See `ContreeGPUCache` for the layout of the pool.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    #[cfg(feature = "bevy_wgpu")]
+    pub fn bake_streamed(
+        &self,
+        device: &RenderDevice,
+        size: u32,
+        materials: &MaterialTable,
+        capacity: usize,
+    ) -> Result<BakedContree, ContreeError> {
+        BakedContree::upload_streamed(
+            device,
+            ContreeGPUHost::new(self, size, materials, GpuNodeEncoding::Full)?,
+            capacity,
+        )
+    }
+
+    /// Rebuilds a contree from the arrays created by `Contree::serialize_gpu` or `ContreeGPUHost`,
+    /// reading the nodes in the encoding they are uploaded with.
+    /// Empty children are decoded as absent, material properties are not part of the tree.
+    pub fn from_gpu_serialized(data: &GpuContreeData) -> Result<Self, ContreeError> {
+        fn deserialize(
+            data: &GpuContreeData,
+            node_index: usize,
+            ancestors: &mut Vec<usize>,
+            budget: &mut LoadBudget,
+        ) -> Result<Contree, ContreeError> {
+            let depth = ancestors.len() as u32;
+            budget.add_node(depth)?;
+            let entries = data.expanded_node(node_index)?;
+            let mip = data
+                .mips
+                .get(node_index)
+                .ok_or(ContreeError::UnexpectedEndOfData)?;
+            let occupancy = entries[0] as u64 | ((entries[1] as u64) << 32);
+            let mut children: [Option<Contree>; 64] = std::array::from_fn(|_| None);
+            for (child, entry) in children.iter_mut().zip(entries[2..].iter()) {
+                *child = if 0 != entry & GPU_NODE_FLAG {
+                    let child_index = (entry & !GPU_NODE_FLAG) as usize;
+                    if child_index == node_index || ancestors.contains(&child_index) {
+                        return Err(ContreeError::InvalidStructure(
+                            "Node pointer creates a cycle".into(),
+                        ));
+                    }
+                    ancestors.push(node_index);
+                    let child = deserialize(data, child_index, ancestors, budget)?;
+                    ancestors.pop();
+                    Some(child)
+                } else if AIR != *entry {
+                    let voxel_data = data
+                        .palette
+                        .get(*entry as usize * GPU_PALETTE_ENTRY_SIZE + GPU_PALETTE_ENTRY_SIZE - 1)
+                        .ok_or_else(|| {
+                            ContreeError::InvalidStructure(
+                                "Leaf refers to a missing palette entry".into(),
+                            )
+                        })?;
+                    Some(Contree::Leaf(*voxel_data))
+                } else {
+                    None
+                };
+            }
+            Ok(Contree::Node(ContreeNode {
+                mip: from_gpu_color(*mip),
+                occupancy,
+                children: Box::new(children),
+            }))
         }
+
+        deserialize(
+            data,
+            0,
+            &mut vec![],
+            &mut LoadBudget::new(&LoadLimits::default()),
+        )
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,150 @@
+use crate::contree::{
+    detail::read_bounded,
+    types::{Contree, ContreeError},
+};
+use std::io::{Read, Write};
+
+/// Compressed files start with these bytes, followed by a single byte naming the codec
+pub(crate) const COMPRESSED_MAGIC: [u8; 4] = *b"VHXC";
+
+const CODEC_NONE: u8 = 0;
+const CODEC_LZ4: u8 = 1;
+const CODEC_ZSTD: u8 = 2;
+
+/// The codec used to compress saved data
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Data is stored as is
+    #[default]
+    None,
+
+    /// Fast compression and decompression, requires the `lz4_support` feature
+    Lz4,
+
+    /// Smaller files, requires the `zstd_support` feature
+    /// * `level` - 1 to 22, higher levels compress better but slower
+    Zstd { level: i32 },
+}
+
+impl Compression {
+    fn codec(&self) -> u8 {
+        match self {
+            Compression::None => CODEC_NONE,
+            Compression::Lz4 => CODEC_LZ4,
+            Compression::Zstd { .. } => CODEC_ZSTD,
+        }
+    }
+}
+
+#[cfg(not(all(feature = "lz4_support", feature = "zstd_support")))]
+fn codec_not_enabled(feature: &str) -> ContreeError {
+    ContreeError::InvalidStructure(format!("Compression codec requires the {feature} feature").into())
+}
+
+/// Writes the payload into the given writer
+type PayloadWriter<'a> = &'a dyn Fn(&mut dyn Write) -> Result<(), ContreeError>;
+
+#[cfg(feature = "lz4_support")]
+fn compress_lz4<W: Write>(writer: W, write_payload: PayloadWriter) -> Result<(), ContreeError> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+    write_payload(&mut encoder)?;
+    encoder.finish().map_err(std::io::Error::from)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "lz4_support"))]
+fn compress_lz4<W: Write>(_writer: W, _write_payload: PayloadWriter) -> Result<(), ContreeError> {
+    Err(codec_not_enabled("lz4_support"))
+}
+
+#[cfg(feature = "lz4_support")]
+fn decompress_lz4<R: Read>(reader: R) -> Result<impl Read, ContreeError> {
+    Ok(lz4_flex::frame::FrameDecoder::new(reader))
+}
+
+#[cfg(not(feature = "lz4_support"))]
+fn decompress_lz4<R: Read>(_reader: R) -> Result<R, ContreeError> {
+    Err(codec_not_enabled("lz4_support"))
+}
+
+#[cfg(feature = "zstd_support")]
+fn compress_zstd<W: Write>(writer: W, level: i32, write_payload: PayloadWriter) -> Result<(), ContreeError> {
+    let mut encoder = zstd::stream::write::Encoder::new(writer, level)?;
+    write_payload(&mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd_support"))]
+fn compress_zstd<W: Write>(_writer: W, _level: i32, _write_payload: PayloadWriter) -> Result<(), ContreeError> {
+    Err(codec_not_enabled("zstd_support"))
+}
+
+#[cfg(feature = "zstd_support")]
+fn decompress_zstd<R: Read>(reader: R) -> Result<impl Read, ContreeError> {
+    Ok(zstd::stream::read::Decoder::new(reader)?)
+}
+
+#[cfg(not(feature = "zstd_support"))]
+fn decompress_zstd<R: Read>(_reader: R) -> Result<R, ContreeError> {
+    Err(codec_not_enabled("zstd_support"))
+}
+
+/// True if the given data starts with the header of compressed data
+pub fn is_compressed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&COMPRESSED_MAGIC)
+}
+
+/// Writes the header into the writer, followed by the payload compressed on the fly with the given codec
+/// * `write_payload` - writes the uncompressed payload into the writer it is given
+pub(crate) fn write_compressed<W: Write>(
+    mut writer: W,
+    compression: Compression,
+    write_payload: PayloadWriter,
+) -> Result<(), ContreeError> {
+    writer.write_all(&COMPRESSED_MAGIC)?;
+    writer.write_all(&[compression.codec()])?;
+    match compression {
+        Compression::None => write_payload(&mut writer)?,
+        Compression::Lz4 => compress_lz4(&mut writer, write_payload)?,
+        Compression::Zstd { level } => compress_zstd(&mut writer, level, write_payload)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads data from the given reader, decompressing it on the fly if it starts with the
+/// header of compressed data; Uncompressed data is returned as is.
+/// * `max_size` - the largest allowed size of the data after decompression
+pub(crate) fn read_decompressed<R: Read>(mut reader: R, max_size: usize) -> Result<Vec<u8>, ContreeError> {
+    let mut magic = Vec::with_capacity(COMPRESSED_MAGIC.len());
+    (&mut reader)
+        .take(COMPRESSED_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    if magic != COMPRESSED_MAGIC {
+        magic.extend(read_bounded(reader, max_size.saturating_sub(magic.len()))?);
+        return Ok(magic);
+    }
+
+    let mut codec = [0u8];
+    reader.read_exact(&mut codec).map_err(|_| ContreeError::UnexpectedEndOfData)?;
+    match codec[0] {
+        CODEC_NONE => read_bounded(reader, max_size),
+        CODEC_LZ4 => read_bounded(decompress_lz4(reader)?, max_size),
+        CODEC_ZSTD => read_bounded(decompress_zstd(reader)?, max_size),
+        _ => Err(ContreeError::InvalidStructure("Unknown compression codec".into())),
+    }
+}
+
+impl Contree {
+    /// Writes the contree in the .vhx format into the given writer, compressed with the given codec
+    pub fn write_vhx_compressed<W: Write>(&self, writer: W, compression: Compression) -> Result<(), ContreeError> {
+        write_compressed(writer, compression, &|payload| self.write_vhx(payload))
+    }
+
+    /// Saves the contree into a .vhx file compressed with the given codec;
+    /// [Contree::load_vhx] recognizes compressed files automatically
+    pub fn save_vhx_compressed(&self, path: &str, compression: Compression) -> Result<(), ContreeError> {
+        self.write_vhx_compressed(std::io::BufWriter::new(std::fs::File::create(path)?), compression)
+    }
+}
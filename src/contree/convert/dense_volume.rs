@@ -0,0 +1,311 @@
+use crate::{
+    contree::{
+        detail::{is_valid_size, read_file_within_limits, size_within_limits, LoadBudget},
+        types::{Contree, ContreeError, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+use std::io::Write;
+
+/// Describes how the axes of a dense volume file map to the axes of the library,
+/// which uses a left-handed, Y up coordinate system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisOrder {
+    /// The library axis ( 0: X, 1: Y, 2: Z ) of every file axis, from the fastest changing index to the slowest
+    pub axes: [usize; 3],
+
+    /// Whether the direction of every file axis is opposite to the library axis
+    pub mirrored: [bool; 3],
+}
+
+impl AxisOrder {
+    /// The layout of the library: X changes fastest, then Y, then Z
+    pub const LIBRARY: AxisOrder = AxisOrder {
+        axes: [0, 1, 2],
+        mirrored: [false, false, false],
+    };
+
+    /// X changes fastest, then Y, then Z in a right-handed, Y up coordinate system
+    pub const RIGHT_HANDED_Y_UP: AxisOrder = AxisOrder {
+        axes: [0, 1, 2],
+        mirrored: [false, false, true],
+    };
+
+    /// X changes fastest, then Y, then Z in a right-handed, Z up coordinate system
+    pub const RIGHT_HANDED_Z_UP: AxisOrder = AxisOrder {
+        axes: [0, 2, 1],
+        mirrored: [false, false, false],
+    };
+
+    /// The layout of .binvox files: Y changes fastest, then Z, then X; right-handed, Y up
+    pub const BINVOX: AxisOrder = AxisOrder {
+        axes: [1, 2, 0],
+        mirrored: [false, true, false],
+    };
+
+    fn validate(&self) -> Result<(), ContreeError> {
+        let mut used = [false; 3];
+        for axis in self.axes {
+            if axis > 2 || used[axis] {
+                return Err(ContreeError::InvalidStructure(
+                    "Axis order must contain every axis once".into(),
+                ));
+            }
+            used[axis] = true;
+        }
+        Ok(())
+    }
+
+    /// Converts a position inside the file into a library position
+    /// * `dimensions` - the extent of the file along each file axis
+    fn to_library(self, file_position: [u32; 3], dimensions: [u32; 3]) -> V3c<u32> {
+        let mut position = [0; 3];
+        for file_axis in 0..3 {
+            position[self.axes[file_axis]] = if self.mirrored[file_axis] {
+                dimensions[file_axis] - 1 - file_position[file_axis]
+            } else {
+                file_position[file_axis]
+            };
+        }
+        V3c::new(position[0], position[1], position[2])
+    }
+
+    /// Calls the given function with every file position of the volume in file order,
+    /// alongside the library position it maps to
+    fn for_each_position<F: FnMut(V3c<u32>) -> Result<(), ContreeError>>(
+        &self,
+        dimensions: [u32; 3],
+        fun: &mut F,
+    ) -> Result<(), ContreeError> {
+        for slowest in 0..dimensions[2] {
+            for middle in 0..dimensions[1] {
+                for fastest in 0..dimensions[0] {
+                    fun(self.to_library([fastest, middle, slowest], dimensions))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The type of a single sample inside a raw dense volume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawSampleType {
+    U8,
+    /// Little endian
+    U32,
+}
+
+impl RawSampleType {
+    fn byte_size(&self) -> usize {
+        match self {
+            RawSampleType::U8 => 1,
+            RawSampleType::U32 => 4,
+        }
+    }
+}
+
+/// Reads a single line of the binvox header, returning it and the position after it
+fn binvox_header_line(bytes: &[u8], start: usize) -> Result<(&str, usize), ContreeError> {
+    let length = bytes
+        .get(start..)
+        .and_then(|rest| rest.iter().position(|byte| b'\n' == *byte))
+        .ok_or(ContreeError::UnexpectedEndOfData)?;
+    let line = std::str::from_utf8(&bytes[start..start + length]).map_err(|_| ContreeError::InvalidHeader)?;
+    Ok((line.trim(), start + length + 1))
+}
+
+impl Contree {
+    /// Loads a .binvox file, returning the tree and its edge length in voxels, within the default `LoadLimits`
+    /// * `order` - the layout of the file, [AxisOrder::BINVOX] for files following the specification
+    /// * `data` - the data set for every filled voxel
+    pub fn load_binvox(path: &str, order: &AxisOrder, data: VoxelData) -> Result<(Self, u32), ContreeError> {
+        Self::load_binvox_with_limits(path, order, data, &LoadLimits::default())
+    }
+
+    /// Loads a .binvox file, see [Contree::load_binvox]
+    /// * `limits` - bounds the size of the file and the loaded tree
+    pub fn load_binvox_with_limits(
+        path: &str,
+        order: &AxisOrder,
+        data: VoxelData,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32), ContreeError> {
+        Self::from_binvox_bytes_with_limits(&read_file_within_limits(path, limits)?, order, data, limits)
+    }
+
+    /// Parses the contents of a .binvox file within the default `LoadLimits`, see [Contree::load_binvox]
+    pub fn from_binvox_bytes(bytes: &[u8], order: &AxisOrder, data: VoxelData) -> Result<(Self, u32), ContreeError> {
+        Self::from_binvox_bytes_with_limits(bytes, order, data, &LoadLimits::default())
+    }
+
+    /// Parses the contents of a .binvox file, see [Contree::load_binvox]
+    /// * `limits` - bounds the size of the loaded tree
+    pub fn from_binvox_bytes_with_limits(
+        bytes: &[u8],
+        order: &AxisOrder,
+        data: VoxelData,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32), ContreeError> {
+        order.validate()?;
+        let (magic, mut position) = binvox_header_line(bytes, 0)?;
+        if "#binvox 1" != magic {
+            return Err(ContreeError::InvalidHeader);
+        }
+        let mut dimensions = None;
+        loop {
+            let (line, next) = binvox_header_line(bytes, position)?;
+            position = next;
+            let mut tokens = line.split_ascii_whitespace();
+            match tokens.next() {
+                Some("data") => break,
+                Some("dim") => {
+                    let values = tokens
+                        .map(|token| token.parse::<u32>().map_err(|_| ContreeError::InvalidHeader))
+                        .collect::<Result<Vec<u32>, ContreeError>>()?;
+                    if 3 != values.len() || values.contains(&0) {
+                        return Err(ContreeError::InvalidHeader);
+                    }
+                    // "dim depth height width", with width being the fastest changing index
+                    dimensions = Some([values[2], values[1], values[0]]);
+                }
+                // Placement in the source scene has no meaning inside the tree
+                Some("translate") | Some("scale") => {}
+                _ => return Err(ContreeError::InvalidHeader),
+            }
+        }
+        let dimensions = dimensions.ok_or(ContreeError::InvalidHeader)?;
+        let size = size_within_limits(*dimensions.iter().max().unwrap(), limits)?;
+
+        // Voxels are stored as ( value, count ) pairs
+        let mut runs = bytes[position..].chunks_exact(2).map(|run| (run[0], run[1]));
+        let mut current = (0, 0);
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        order.for_each_position(dimensions, &mut |voxel| {
+            while 0 == current.1 {
+                current = runs.next().ok_or(ContreeError::UnexpectedEndOfData)?;
+            }
+            current.1 -= 1;
+            if 0 != current.0 {
+                tree.insert_within_budget(size, &voxel, data, &mut budget, 0)?;
+            }
+            Ok(())
+        })?;
+        Ok((tree, size))
+    }
+
+    /// Saves the tree into a .binvox file; every voxel other than air is stored as filled
+    /// * `size` - the edge length of the tree in voxels, must be a power of 4
+    /// * `order` - the layout of the file, [AxisOrder::BINVOX] for files following the specification
+    pub fn export_binvox(&self, path: &str, size: u32, order: &AxisOrder) -> Result<(), ContreeError> {
+        let bytes = self.to_binvox_bytes(size, order)?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Encodes the tree into the contents of a .binvox file, see [Contree::export_binvox]
+    pub fn to_binvox_bytes(&self, size: u32, order: &AxisOrder) -> Result<Vec<u8>, ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        order.validate()?;
+        let mut bytes = format!("#binvox 1\ndim {size} {size} {size}\ntranslate 0 0 0\nscale 1\ndata\n").into_bytes();
+        let mut current: (u8, u8) = (0, 0);
+        order.for_each_position([size; 3], &mut |voxel| {
+            let value = (AIR != self.get_at(size, &voxel)) as u8;
+            if current.1 > 0 && (value != current.0 || u8::MAX == current.1) {
+                bytes.extend_from_slice(&[current.0, current.1]);
+                current.1 = 0;
+            }
+            current = (value, current.1 + 1);
+            Ok(())
+        })?;
+        if current.1 > 0 {
+            bytes.extend_from_slice(&[current.0, current.1]);
+        }
+        Ok(bytes)
+    }
+
+    /// Loads a headerless dense volume, returning the tree and its edge length in voxels,
+    /// within the default `LoadLimits`
+    /// * `dimensions` - the extent of the volume along each file axis, from the fastest changing to the slowest
+    /// * `order` - how the axes of the file map to the axes of the library
+    /// * `transfer` - converts a sample into voxel data; `AIR` leaves the voxel empty
+    pub fn load_raw_volume<F: Fn(u32) -> VoxelData>(
+        path: &str,
+        dimensions: [u32; 3],
+        sample_type: RawSampleType,
+        order: &AxisOrder,
+        transfer: F,
+    ) -> Result<(Self, u32), ContreeError> {
+        Self::load_raw_volume_with_limits(path, dimensions, sample_type, order, transfer, &LoadLimits::default())
+    }
+
+    /// Loads a headerless dense volume, see [Contree::load_raw_volume]
+    /// * `limits` - bounds the size of the file and the loaded tree
+    pub fn load_raw_volume_with_limits<F: Fn(u32) -> VoxelData>(
+        path: &str,
+        dimensions: [u32; 3],
+        sample_type: RawSampleType,
+        order: &AxisOrder,
+        transfer: F,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32), ContreeError> {
+        order.validate()?;
+        let size = size_within_limits(*dimensions.iter().max().unwrap(), limits)?;
+        let bytes = read_file_within_limits(path, limits)?;
+        let sample_count = dimensions.iter().map(|extent| *extent as usize).product::<usize>();
+        if bytes.len() < sample_count.saturating_mul(sample_type.byte_size()) {
+            return Err(ContreeError::UnexpectedEndOfData);
+        }
+        let mut samples = bytes.chunks_exact(sample_type.byte_size()).map(|sample| match sample_type {
+            RawSampleType::U8 => sample[0] as u32,
+            RawSampleType::U32 => u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+        });
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        order.for_each_position(dimensions, &mut |voxel| {
+            let data = transfer(samples.next().ok_or(ContreeError::UnexpectedEndOfData)?);
+            if AIR != data {
+                tree.insert_within_budget(size, &voxel, data, &mut budget, 0)?;
+            }
+            Ok(())
+        })?;
+        Ok((tree, size))
+    }
+
+    /// Saves the whole tree into a headerless dense volume, with the edge length of the tree along every axis
+    /// * `size` - the edge length of the tree in voxels, must be a power of 4
+    /// * `order` - how the axes of the file map to the axes of the library
+    /// * `transfer` - converts voxel data into a sample; must fit into a byte for [RawSampleType::U8]
+    pub fn export_raw_volume<F: Fn(VoxelData) -> u32>(
+        &self,
+        path: &str,
+        size: u32,
+        sample_type: RawSampleType,
+        order: &AxisOrder,
+        transfer: F,
+    ) -> Result<(), ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        order.validate()?;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        order.for_each_position([size; 3], &mut |voxel| {
+            let sample = transfer(self.get_at(size, &voxel));
+            match sample_type {
+                RawSampleType::U8 => {
+                    let sample = u8::try_from(sample).map_err(|_| {
+                        ContreeError::InvalidStructure("Sample does not fit into a byte".into())
+                    })?;
+                    file.write_all(&[sample])?;
+                }
+                RawSampleType::U32 => file.write_all(&sample.to_le_bytes())?,
+            }
+            Ok(())
+        })?;
+        file.flush()?;
+        Ok(())
+    }
+}
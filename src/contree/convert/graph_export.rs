@@ -0,0 +1,212 @@
+use crate::contree::types::{Albedo, Contree, VoxelData, AIR};
+use std::fmt::Write;
+
+/// Controls what is included in the exported graphs of the tree structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphExportOptions {
+    /// Nodes below this depth are summarized in a single placeholder node; None to export everything
+    pub max_depth: Option<u32>,
+
+    /// Show the occupancy bits of nodes in their labels
+    pub occupancy_labels: bool,
+
+    /// Fill nodes with their mip color; Nodes with a fully transparent mip are not filled
+    pub mip_fill: bool,
+
+    /// Include leaves with their voxel data, filled with the color they represent
+    pub leaf_materials: bool,
+}
+
+impl Default for GraphExportOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            occupancy_labels: true,
+            mip_fill: true,
+            leaf_materials: true,
+        }
+    }
+}
+
+enum GraphVertexKind {
+    Node,
+    Leaf,
+    Truncated,
+}
+
+struct GraphVertex {
+    kind: GraphVertexKind,
+    label: String,
+    fill: Option<Albedo>,
+    /// True for nodes where every child is the same leaf, which should have been simplified into a leaf
+    collapsible: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    vertices: Vec<GraphVertex>,
+    /// ( parent, child, sectant )
+    edges: Vec<(usize, usize, usize)>,
+}
+
+fn color_code(color: &Albedo) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn count_nodes(contree: &Contree) -> usize {
+    match contree {
+        Contree::Leaf(_) => 0,
+        Contree::Node(node) => 1 + node.children.iter().flatten().map(count_nodes).sum::<usize>(),
+    }
+}
+
+fn leaf_vertex(data: VoxelData) -> GraphVertex {
+    GraphVertex {
+        kind: GraphVertexKind::Leaf,
+        label: format!("0x{data:08x}"),
+        fill: Some(Albedo::from(data)),
+        collapsible: false,
+    }
+}
+
+/// Adds the given subtree to the graph, returning the index of its root vertex
+fn build_graph(contree: &Contree, depth: u32, options: &GraphExportOptions, graph: &mut Graph) -> usize {
+    let index = graph.vertices.len();
+    let node = match contree {
+        Contree::Leaf(data) => {
+            graph.vertices.push(leaf_vertex(*data));
+            return index;
+        }
+        Contree::Node(node) => node,
+    };
+
+    if options.max_depth.is_some_and(|max_depth| depth > max_depth) {
+        graph.vertices.push(GraphVertex {
+            kind: GraphVertexKind::Truncated,
+            label: format!("... {} nodes", count_nodes(contree)),
+            fill: None,
+            collapsible: false,
+        });
+        return index;
+    }
+
+    let first_child = node.children[0].as_ref();
+    let collapsible = matches!(first_child, Some(Contree::Leaf(_)))
+        && node.children.iter().all(|child| child.as_ref() == first_child);
+    let mut label = format!("node, depth {depth}");
+    if options.occupancy_labels {
+        let _ = write!(
+            label,
+            "\noccupancy 0x{:016x}\n{} / 64 occupied",
+            node.occupancy,
+            node.occupancy.count_ones()
+        );
+    }
+    graph.vertices.push(GraphVertex {
+        kind: GraphVertexKind::Node,
+        label,
+        fill: if options.mip_fill && !node.mip.is_transparent() {
+            Some(node.mip)
+        } else {
+            None
+        },
+        collapsible,
+    });
+
+    for (sectant, child) in node.children.iter().enumerate() {
+        let child_index = match child {
+            Some(child_node @ Contree::Node(_)) => build_graph(child_node, depth + 1, options, graph),
+            Some(Contree::Leaf(data)) if options.leaf_materials && AIR != *data => {
+                graph.vertices.push(leaf_vertex(*data));
+                graph.vertices.len() - 1
+            }
+            _ => continue,
+        };
+        graph.edges.push((index, child_index, sectant));
+    }
+    index
+}
+
+impl Contree {
+    fn structure_graph(&self, options: &GraphExportOptions) -> Graph {
+        let mut graph = Graph::default();
+        build_graph(self, 0, options, &mut graph);
+        graph
+    }
+
+    /// Exports the structure of the tree in the GraphViz DOT format.
+    /// Nodes which could be simplified into a single leaf are drawn with a red border.
+    pub fn to_dot(&self, options: &GraphExportOptions) -> String {
+        let graph = self.structure_graph(options);
+        let mut dot = String::from("digraph contree {\n    node [fontname=\"monospace\"];\n");
+        for (index, vertex) in graph.vertices.iter().enumerate() {
+            let shape = match vertex.kind {
+                GraphVertexKind::Node => "box",
+                GraphVertexKind::Leaf => "ellipse",
+                GraphVertexKind::Truncated => "plaintext",
+            };
+            let mut attributes = format!(
+                "shape={shape}, label=\"{}\"",
+                vertex.label.replace('"', "\\\"").replace('\n', "\\n")
+            );
+            if let Some(fill) = vertex.fill {
+                let _ = write!(attributes, ", style=filled, fillcolor=\"{}\"", color_code(&fill));
+            }
+            if vertex.collapsible {
+                attributes.push_str(", color=red, penwidth=3");
+            }
+            let _ = writeln!(dot, "    n{index} [{attributes}];");
+        }
+        for (parent, child, sectant) in graph.edges.iter() {
+            let _ = writeln!(dot, "    n{parent} -> n{child} [label=\"{sectant}\"];");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Exports the structure of the tree in the GraphML format, with the details of
+    /// each vertex stored in data attributes: label, kind, fill color and collapsible
+    pub fn to_graphml(&self, options: &GraphExportOptions) -> String {
+        let graph = self.structure_graph(options);
+        let mut graphml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key attr.name=\"label\" attr.type=\"string\" for=\"node\" id=\"label\"/>\n",
+            "  <key attr.name=\"kind\" attr.type=\"string\" for=\"node\" id=\"kind\"/>\n",
+            "  <key attr.name=\"fill\" attr.type=\"string\" for=\"node\" id=\"fill\"/>\n",
+            "  <key attr.name=\"collapsible\" attr.type=\"boolean\" for=\"node\" id=\"collapsible\"/>\n",
+            "  <key attr.name=\"sectant\" attr.type=\"int\" for=\"edge\" id=\"sectant\"/>\n",
+            "  <graph edgedefault=\"directed\" id=\"contree\">\n",
+        ));
+        for (index, vertex) in graph.vertices.iter().enumerate() {
+            let kind = match vertex.kind {
+                GraphVertexKind::Node => "node",
+                GraphVertexKind::Leaf => "leaf",
+                GraphVertexKind::Truncated => "truncated",
+            };
+            let _ = writeln!(graphml, "    <node id=\"n{index}\">");
+            let _ = writeln!(graphml, "      <data key=\"label\">{}</data>", escape_xml(&vertex.label));
+            let _ = writeln!(graphml, "      <data key=\"kind\">{kind}</data>");
+            if let Some(fill) = vertex.fill {
+                let _ = writeln!(graphml, "      <data key=\"fill\">{}</data>", color_code(&fill));
+            }
+            let _ = writeln!(graphml, "      <data key=\"collapsible\">{}</data>", vertex.collapsible);
+            graphml.push_str("    </node>\n");
+        }
+        for (edge, (parent, child, sectant)) in graph.edges.iter().enumerate() {
+            let _ = writeln!(
+                graphml,
+                "    <edge id=\"e{edge}\" source=\"n{parent}\" target=\"n{child}\">\n      <data key=\"sectant\">{sectant}</data>\n    </edge>"
+            );
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
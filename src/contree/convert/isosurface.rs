@@ -0,0 +1,324 @@
+use crate::{
+    contree::types::{Contree, ContreeError, VoxelData, AIR},
+    spatial::math::vector::{V3c, V3cf32},
+};
+use std::collections::HashMap;
+
+/// Converts a solid voxel into a density value; Air always has a density of 0
+pub type DensityFunction<'a> = &'a dyn Fn(V3c<u32>, VoxelData) -> f32;
+
+/// A smooth triangle mesh extracted from the voxels of a contree
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IsoMesh {
+    /// Vertex positions in the coordinate system of the tree, voxel centers are at half units
+    pub positions: Vec<V3cf32>,
+
+    /// Vertex normals, pointing out of the solid
+    pub normals: Vec<V3cf32>,
+
+    /// Vertex indices of the triangles; ( b - a ) x ( c - a ) points out of the solid
+    pub triangles: Vec<[u32; 3]>,
+
+    /// The data of the closest solid voxel for each triangle
+    pub materials: Vec<VoxelData>,
+}
+
+/// The edge length of the chunks a whole tree is processed in, so only the samples
+/// of a single chunk are held in memory at once
+const ISOSURFACE_CHUNK_SIZE: u32 = 64;
+
+/// Dense density samples at voxel centers for the volume a chunk needs
+struct SampleGrid {
+    /// The position of the first sample
+    origin: [i64; 3],
+    dimensions: [i64; 3],
+    densities: Vec<f32>,
+    data: Vec<VoxelData>,
+}
+
+impl SampleGrid {
+    fn index(&self, position: [i64; 3]) -> usize {
+        let local = [0, 1, 2].map(|axis| position[axis] - self.origin[axis]);
+        (local[0] + local[1] * self.dimensions[0] + local[2] * self.dimensions[0] * self.dimensions[1]) as usize
+    }
+
+    fn density(&self, position: [i64; 3]) -> f32 {
+        self.densities[self.index(position)]
+    }
+
+    fn data(&self, position: [i64; 3]) -> VoxelData {
+        self.data[self.index(position)]
+    }
+}
+
+/// Offset of the corners of a cell, in the same order as the bits of the corner index ( x, y, z )
+fn corner_offset(corner: usize) -> [i64; 3] {
+    [(corner & 1) as i64, ((corner >> 1) & 1) as i64, ((corner >> 2) & 1) as i64]
+}
+
+fn to_vector(position: [f32; 3]) -> V3cf32 {
+    V3c::new(position[0], position[1], position[2])
+}
+
+impl IsoMesh {
+    /// Appends the vertices and triangles of the other mesh to this one
+    fn append(&mut self, other: IsoMesh) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.triangles.extend(
+            other
+                .triangles
+                .into_iter()
+                .map(|triangle| triangle.map(|index| index + offset)),
+        );
+        self.materials.extend(other.materials);
+    }
+}
+
+impl Contree {
+    /// Extracts a smooth surface from the whole tree, see [Contree::extract_isosurface_chunk].
+    /// The tree is processed in chunks, skipping the ones without solid voxels around them,
+    /// so memory use does not grow with the volume of the tree.
+    /// * `size` - the edge length of the tree in voxels, must be a power of 4
+    pub fn extract_isosurface(
+        &self,
+        size: u32,
+        density: Option<DensityFunction>,
+        iso_level: f32,
+    ) -> Result<IsoMesh, ContreeError> {
+        Self::check_bounds(size, &V3c::unit(0))?;
+        let chunk_size = ISOSURFACE_CHUNK_SIZE.min(size);
+        let mut mesh = IsoMesh::default();
+        for z in (0..size).step_by(chunk_size as usize) {
+            for y in (0..size).step_by(chunk_size as usize) {
+                for x in (0..size).step_by(chunk_size as usize) {
+                    // A chunk reads samples from 1 voxel below to 1 voxel above itself
+                    let chunk_min = V3c::new(x, y, z);
+                    let mut has_solid = false;
+                    self.visit_leaves_in_box(
+                        V3c::unit(0),
+                        size,
+                        &V3c::new(x.saturating_sub(1), y.saturating_sub(1), z.saturating_sub(1)),
+                        &(chunk_min + V3c::unit(chunk_size + 1)),
+                        &mut |_, _, data| has_solid |= AIR != data,
+                    );
+                    if has_solid {
+                        mesh.append(self.extract_isosurface_chunk(size, chunk_min, chunk_size, density, iso_level)?);
+                    }
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Extracts a smooth surface from a cubic chunk of the tree using surface nets: a vertex
+    /// is placed inside every cell of 8 neighbouring voxel centers the surface passes through,
+    /// and every voxel edge crossing the surface emits a quad between the 4 cells around it.
+    /// Vertex positions only depend on the voxels around them, so the meshes of neighbouring
+    /// chunks line up without seams. Space outside the tree is considered empty.
+    /// * `size` - the edge length of the tree in voxels, must be a power of 4
+    /// * `chunk_min` - the position of the first voxel of the chunk
+    /// * `chunk_size` - the edge length of the chunk in voxels
+    /// * `density` - converts voxels into densities; without it solid voxels have a density of 1
+    /// * `iso_level` - voxels denser than this value are inside the surface, e.g. 0.5
+    pub fn extract_isosurface_chunk(
+        &self,
+        size: u32,
+        chunk_min: V3c<u32>,
+        chunk_size: u32,
+        density: Option<DensityFunction>,
+        iso_level: f32,
+    ) -> Result<IsoMesh, ContreeError> {
+        Self::check_bounds(size, &chunk_min)?;
+        if 0 == chunk_size {
+            return Ok(IsoMesh::default());
+        }
+        let chunk_min = [chunk_min.x as i64, chunk_min.y as i64, chunk_min.z as i64];
+        let chunk_max = chunk_min.map(|coordinate| (coordinate + chunk_size as i64).min(size as i64));
+
+        // The chunk owns the edges starting inside it; Edges entering the tree from outside
+        // belong to the chunks on the lower boundary of the tree.
+        let owned_min = chunk_min.map(|coordinate| if 0 == coordinate { -1 } else { coordinate });
+
+        // Cells from owned_min - 1 to chunk_max - 1 are needed, each spanning 2 samples per axis
+        let origin = owned_min.map(|coordinate| coordinate - 1);
+        let dimensions = [0, 1, 2].map(|axis| chunk_max[axis] - origin[axis] + 1);
+        let sample_count = (dimensions[0] * dimensions[1] * dimensions[2]) as usize;
+        let mut grid = SampleGrid {
+            origin,
+            dimensions,
+            densities: Vec::with_capacity(sample_count),
+            data: Vec::with_capacity(sample_count),
+        };
+        for z in origin[2]..origin[2] + dimensions[2] {
+            for y in origin[1]..origin[1] + dimensions[1] {
+                for x in origin[0]..origin[0] + dimensions[0] {
+                    let inside_tree = [x, y, z]
+                        .iter()
+                        .all(|coordinate| (0..size as i64).contains(coordinate));
+                    let (sample_density, data) = if inside_tree {
+                        let position = V3c::new(x as u32, y as u32, z as u32);
+                        match self.get_at(size, &position) {
+                            AIR => (0., AIR),
+                            data => (density.map_or(1., |density| density(position, data)), data),
+                        }
+                    } else {
+                        (0., AIR)
+                    };
+                    grid.densities.push(sample_density);
+                    grid.data.push(data);
+                }
+            }
+        }
+
+        // Place a vertex inside every cell the surface passes through
+        let mut mesh = IsoMesh::default();
+        let mut cell_vertices: HashMap<[i64; 3], u32> = HashMap::new();
+        let mut vertex_cells: Vec<[i64; 3]> = Vec::new();
+        for z in origin[2]..chunk_max[2] {
+            for y in origin[1]..chunk_max[1] {
+                for x in origin[0]..chunk_max[0] {
+                    let cell = [x, y, z];
+                    let corner_density: [f32; 8] = std::array::from_fn(|corner| {
+                        let offset = corner_offset(corner);
+                        grid.density([0, 1, 2].map(|axis| cell[axis] + offset[axis]))
+                    });
+                    let inside_count = corner_density
+                        .iter()
+                        .filter(|corner_density| **corner_density > iso_level)
+                        .count();
+                    if 0 == inside_count || 8 == inside_count {
+                        continue;
+                    }
+
+                    // The vertex is the average of the points where the cell edges cross the surface
+                    let mut crossing_sum = [0.; 3];
+                    let mut crossing_count = 0;
+                    for corner in 0..8 {
+                        for axis in 0..3 {
+                            if 0 != corner & (1 << axis) {
+                                continue;
+                            }
+                            let other = corner | (1 << axis);
+                            let (start, end) = (corner_density[corner], corner_density[other]);
+                            if (start > iso_level) == (end > iso_level) {
+                                continue;
+                            }
+                            let t = (iso_level - start) / (end - start);
+                            let offset = corner_offset(corner);
+                            for (component, sum) in crossing_sum.iter_mut().enumerate() {
+                                *sum += offset[component] as f32 + if component == axis { t } else { 0. };
+                            }
+                            crossing_count += 1;
+                        }
+                    }
+                    let position = [0, 1, 2]
+                        .map(|axis| cell[axis] as f32 + 0.5 + crossing_sum[axis] / crossing_count as f32);
+
+                    // Density decreases outwards, so the normal is the negative gradient
+                    let mut gradient = [0.; 3];
+                    for (corner, corner_density) in corner_density.iter().enumerate() {
+                        let offset = corner_offset(corner);
+                        for axis in 0..3 {
+                            gradient[axis] += if 1 == offset[axis] {
+                                *corner_density
+                            } else {
+                                -*corner_density
+                            };
+                        }
+                    }
+                    let normal = to_vector(gradient.map(|component| -component));
+                    let normal = if 0. < normal.length() {
+                        normal.normalized()
+                    } else {
+                        V3c::new(0., 1., 0.)
+                    };
+
+                    cell_vertices.insert(cell, mesh.positions.len() as u32);
+                    vertex_cells.push(cell);
+                    mesh.positions.push(to_vector(position));
+                    mesh.normals.push(normal);
+                }
+            }
+        }
+
+        // Connect the vertices around every owned voxel edge crossing the surface
+        for z in owned_min[2]..chunk_max[2] {
+            for y in owned_min[1]..chunk_max[1] {
+                for x in owned_min[0]..chunk_max[0] {
+                    let start = [x, y, z];
+                    let start_inside = grid.density(start) > iso_level;
+                    for axis in 0..3 {
+                        let mut end = start;
+                        end[axis] += 1;
+                        if end[axis] >= origin[axis] + dimensions[axis]
+                            || start_inside == (grid.density(end) > iso_level)
+                        {
+                            continue;
+                        }
+                        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                        let quad_cells = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(du, dv)| {
+                            let mut cell = start;
+                            cell[u] -= du;
+                            cell[v] -= dv;
+                            cell
+                        });
+                        let Some(quad) = quad_cells
+                            .iter()
+                            .map(|cell| cell_vertices.get(cell).copied())
+                            .collect::<Option<Vec<u32>>>()
+                        else {
+                            continue;
+                        };
+                        // The cells go counter-clockwise around the edge direction,
+                        // which points outwards when the edge leaves the solid
+                        let quad_triangles = if start_inside {
+                            [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]]
+                        } else {
+                            [[quad[0], quad[2], quad[1]], [quad[0], quad[3], quad[2]]]
+                        };
+                        for triangle in quad_triangles {
+                            mesh.materials
+                                .push(Self::closest_material(&grid, &vertex_cells, &mesh.positions, triangle));
+                            mesh.triangles.push(triangle);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// The data of the solid voxel closest to the center of the triangle,
+    /// searched among the voxels around its vertices
+    fn closest_material(
+        grid: &SampleGrid,
+        vertex_cells: &[[i64; 3]],
+        positions: &[V3cf32],
+        triangle: [u32; 3],
+    ) -> VoxelData {
+        let center = (positions[triangle[0] as usize]
+            + positions[triangle[1] as usize]
+            + positions[triangle[2] as usize])
+            / 3.;
+        let mut closest = (f32::MAX, AIR);
+        for vertex in triangle {
+            let cell = vertex_cells[vertex as usize];
+            for corner in 0..8 {
+                let offset = corner_offset(corner);
+                let sample = [0, 1, 2].map(|axis| cell[axis] + offset[axis]);
+                let data = grid.data(sample);
+                if AIR == data {
+                    continue;
+                }
+                let voxel_center = to_vector(sample.map(|coordinate| coordinate as f32 + 0.5));
+                let distance = (voxel_center - center).length();
+                if distance < closest.0 {
+                    closest = (distance, data);
+                }
+            }
+        }
+        closest.1
+    }
+}
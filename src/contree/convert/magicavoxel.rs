@@ -0,0 +1,412 @@
+use crate::{
+    contree::{
+        detail::{is_valid_size, read_file_within_limits, size_within_limits, LoadBudget},
+        types::{
+            Albedo, Contree, ContreeError, LoadLimits, MaterialKind, MaterialProperties, MaterialTable,
+        },
+    },
+    spatial::math::vector::V3c,
+};
+use dot_vox::{
+    Color, Dict, DotVoxData, Frame, Layer, Material, Model, SceneNode, ShapeModel, Size, Voxel,
+};
+use nalgebra::{Matrix3, Vector3};
+use std::collections::HashMap;
+
+/// The maximum edge length of a single model inside a .vox file
+const VOX_MODEL_MAX_SIZE: u32 = 256;
+
+/// The number of colors usable by voxels; Palette index 0 is reserved for empty voxels
+const VOX_PALETTE_SIZE: usize = 255;
+
+/// Converts a position in the library coordinate system ( Left handed, Y up )
+/// into the coordinate system of MagicaVoxel ( Right handed, Z up )
+fn to_vox_coordinates(position: V3c<u32>) -> V3c<u32> {
+    V3c::new(position.x, position.z, position.y)
+}
+
+/// Converts a position in the coordinate system of MagicaVoxel into the library coordinate system
+fn from_vox_coordinates(position: V3c<u32>) -> V3c<u32> {
+    V3c::new(position.x, position.z, position.y)
+}
+
+/// The largest distance a transform node can move its children along an axis,
+/// so accumulated positions can not overflow
+const VOX_MAX_TRANSLATION: i32 = 1 << 24;
+
+/// Parses the translation of a transform frame, stored as "x y z"
+fn parse_translation(attributes: &Dict) -> Vector3<i32> {
+    let mut translation = Vector3::zeros();
+    if let Some(value) = attributes.get("_t") {
+        for (axis, component) in value.split_whitespace().take(3).enumerate() {
+            translation[axis] = component
+                .parse::<i32>()
+                .unwrap_or(0)
+                .clamp(-VOX_MAX_TRANSLATION, VOX_MAX_TRANSLATION);
+        }
+    }
+    translation
+}
+
+/// Parses the rotation of a transform frame, stored as a single byte:
+/// * bit 0-1: index of the non-zero entry in the first row
+/// * bit 2-3: index of the non-zero entry in the second row
+/// * bit 4-6: sign of the entries in the first, second and third row
+fn parse_rotation(attributes: &Dict) -> Matrix3<i32> {
+    let Some(packed) = attributes.get("_r").and_then(|value| value.parse::<u8>().ok()) else {
+        return Matrix3::identity();
+    };
+    let first_index = (packed & 0b11) as usize;
+    let second_index = ((packed >> 2) & 0b11) as usize;
+    if first_index > 2 || second_index > 2 || first_index == second_index {
+        return Matrix3::identity();
+    }
+    let third_index = 3 - first_index - second_index;
+    let mut rotation = Matrix3::zeros();
+    for (row, column) in [first_index, second_index, third_index].into_iter().enumerate() {
+        rotation[(row, column)] = if 0 == (packed >> (4 + row)) & 1 { 1 } else { -1 };
+    }
+    rotation
+}
+
+/// Calls the given function for every model instance in the scene graph,
+/// with its accumulated translation and rotation
+fn iterate_vox_tree<F: FnMut(&Model, &Vector3<i32>, &Matrix3<i32>)>(vox_tree: &DotVoxData, mut fun: F) {
+    fn iterate_scene_node<F: FnMut(&Model, &Vector3<i32>, &Matrix3<i32>)>(
+        vox_tree: &DotVoxData,
+        node_index: usize,
+        depth: usize,
+        translation: Vector3<i32>,
+        rotation: Matrix3<i32>,
+        fun: &mut F,
+    ) {
+        // A valid scene graph can not be deeper than its node count; Deeper paths are cycles
+        if depth > vox_tree.scenes.len() {
+            return;
+        }
+        match vox_tree.scenes.get(node_index) {
+            Some(SceneNode::Transform { frames, child, .. }) => {
+                let (local_translation, local_rotation) = match frames.first() {
+                    Some(frame) => (
+                        parse_translation(&frame.attributes),
+                        parse_rotation(&frame.attributes),
+                    ),
+                    None => (Vector3::zeros(), Matrix3::identity()),
+                };
+                iterate_scene_node(
+                    vox_tree,
+                    *child as usize,
+                    depth + 1,
+                    (translation + rotation * local_translation)
+                        .map(|component| component.clamp(-VOX_MAX_TRANSLATION, VOX_MAX_TRANSLATION)),
+                    rotation * local_rotation,
+                    fun,
+                );
+            }
+            Some(SceneNode::Group { children, .. }) => {
+                for child in children {
+                    iterate_scene_node(vox_tree, *child as usize, depth + 1, translation, rotation, fun);
+                }
+            }
+            Some(SceneNode::Shape { models, .. }) => {
+                for shape_model in models {
+                    if let Some(model) = vox_tree.models.get(shape_model.model_id as usize) {
+                        fun(model, &translation, &rotation);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    if vox_tree.scenes.is_empty() {
+        for model in vox_tree.models.iter() {
+            fun(model, &Vector3::zeros(), &Matrix3::identity());
+        }
+    } else {
+        iterate_scene_node(vox_tree, 0, 0, Vector3::zeros(), Matrix3::identity(), &mut fun);
+    }
+}
+
+/// Parses the given property of the material as a number
+fn material_property(material: &Material, key: &str) -> Option<f32> {
+    material.properties.get(key).and_then(|value| value.parse().ok())
+}
+
+impl From<&Material> for MaterialProperties {
+    fn from(material: &Material) -> Self {
+        let defaults = MaterialProperties::default();
+        let kind = match material.properties.get("_type").map(|value| value.as_str()) {
+            Some("_metal") => MaterialKind::Metal,
+            Some("_glass") => MaterialKind::Glass,
+            Some("_emit") => MaterialKind::Emissive,
+            Some("_blend") => MaterialKind::Blend,
+            Some("_media") => MaterialKind::Media,
+            _ => MaterialKind::Diffuse,
+        };
+        let emission = match kind {
+            // Emission strength is scaled by the power of the light source
+            MaterialKind::Emissive | MaterialKind::Blend => {
+                material_property(material, "_emit").unwrap_or(0.)
+                    * (1. + material_property(material, "_flux").unwrap_or(0.))
+            }
+            _ => 0.,
+        };
+        let transparency = match kind {
+            MaterialKind::Glass | MaterialKind::Blend | MaterialKind::Media => {
+                material_property(material, "_trans")
+                    .or_else(|| material_property(material, "_alpha"))
+                    .unwrap_or(0.)
+            }
+            _ => 0.,
+        };
+        MaterialProperties {
+            kind,
+            emission,
+            transparency,
+            roughness: material_property(material, "_rough").unwrap_or(defaults.roughness),
+            metalness: material_property(material, "_metal").unwrap_or(defaults.metalness),
+            // MagicaVoxel stores the refraction index offset by 1
+            refraction_index: material_property(material, "_ior")
+                .map(|ior| 1. + ior)
+                .unwrap_or(defaults.refraction_index),
+        }
+    }
+}
+
+impl Contree {
+    /// Loads a MagicaVoxel .vox file into a contree, discarding material information,
+    /// within the default `LoadLimits`; returning the tree and its edge length in voxels
+    pub fn load_vox_file(path: &str) -> Result<(Self, u32), ContreeError> {
+        Self::load_vox_file_with_limits(path, &LoadLimits::default())
+    }
+
+    /// Loads a MagicaVoxel .vox file into a contree, discarding material information;
+    /// returning the tree and its edge length in voxels
+    /// * `limits` - bounds the size of the file and the loaded tree
+    pub fn load_vox_file_with_limits(
+        path: &str,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32), ContreeError> {
+        let (tree, size, _) = Self::load_vox_file_with_materials_and_limits(path, limits)?;
+        Ok((tree, size))
+    }
+
+    /// Loads a MagicaVoxel .vox file into a contree, alongside the properties of the
+    /// materials ( MATL chunks ) used by the stored voxels, within the default `LoadLimits`
+    pub fn load_vox_file_with_materials(
+        path: &str,
+    ) -> Result<(Self, u32, MaterialTable), ContreeError> {
+        Self::load_vox_file_with_materials_and_limits(path, &LoadLimits::default())
+    }
+
+    /// Loads a MagicaVoxel .vox file into a contree, alongside the properties of the
+    /// materials ( MATL chunks ) used by the stored voxels; returning the tree, its edge length
+    /// in voxels and the material table.
+    /// Voxels are stored with their palette color as `VoxelData`; When multiple palette
+    /// entries share the same color, the material of the first one is used for all of them.
+    /// * `limits` - bounds the size of the file and the loaded tree
+    pub fn load_vox_file_with_materials_and_limits(
+        path: &str,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32, MaterialTable), ContreeError> {
+        let vox_tree = dot_vox::load_bytes(&read_file_within_limits(path, limits)?)
+            .map_err(|message| ContreeError::InvalidStructure(message.into()))?;
+
+        let mut voxels: Vec<(Vector3<i32>, u8)> = Vec::new();
+        iterate_vox_tree(&vox_tree, |model, translation, rotation| {
+            let half_size = Vector3::new(
+                model.size.x as i32 / 2,
+                model.size.y as i32 / 2,
+                model.size.z as i32 / 2,
+            );
+            for voxel in model.voxels.iter() {
+                let local = Vector3::new(voxel.x as i32, voxel.y as i32, voxel.z as i32) - half_size;
+                voxels.push((translation + rotation * local, voxel.i));
+            }
+        });
+
+        if voxels.is_empty() {
+            return Ok((Contree::new(), 1, MaterialTable::new()));
+        }
+
+        let mut min_position = voxels[0].0;
+        let mut max_position = voxels[0].0;
+        for (position, _) in voxels.iter() {
+            min_position = min_position.inf(position);
+            max_position = max_position.sup(position);
+        }
+        let extent = (0..3)
+            .map(|axis| max_position[axis] as i64 - min_position[axis] as i64 + 1)
+            .max()
+            .unwrap();
+        let extent = u32::try_from(extent).map_err(|_| ContreeError::LimitExceeded { limit: "depth" })?;
+        let size = size_within_limits(extent, limits)?;
+
+        let materials: HashMap<u32, &Material> = vox_tree
+            .materials
+            .iter()
+            .map(|material| (material.id, material))
+            .collect();
+        let mut material_table = MaterialTable::new();
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        for (position, color_index) in voxels {
+            let Some(color) = vox_tree.palette.get(color_index as usize) else {
+                return Err(ContreeError::InvalidStructure(
+                    "Voxel references a color outside of the palette".into(),
+                ));
+            };
+            let data = u32::from(
+                Albedo::default()
+                    .with_red(color.r)
+                    .with_green(color.g)
+                    .with_blue(color.b)
+                    .with_alpha(color.a),
+            );
+            // Palette indices are stored from 1 in the file, as index 0 means an empty voxel;
+            // dot_vox reduces voxel color indices by one, but MATL ids keep the index of the file
+            if let Some(material) = materials.get(&(color_index as u32 + 1)) {
+                material_table
+                    .entry(data)
+                    .or_insert_with(|| MaterialProperties::from(*material));
+            }
+            let position = position - min_position;
+            let position = from_vox_coordinates(V3c::new(
+                position.x as u32,
+                position.y as u32,
+                position.z as u32,
+            ));
+            tree.insert_within_budget(size, &position, data, &mut budget, 0)?;
+        }
+        Ok((tree, size, material_table))
+    }
+
+    /// Saves the contree into a MagicaVoxel .vox file
+    /// The palette is built from the colors of the stored voxels, so at most 255 distinct colors are supported,
+    /// trees with more colors fail with `ContreeError::LimitExceeded`.
+    /// Trees larger, than 256^3 are split into multiple models, each placed by its own transform node.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn export_vox(&self, path: &str, size: u32) -> Result<(), ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        let mut palette: Vec<Albedo> = Vec::new();
+        let mut palette_indices: HashMap<Albedo, u8> = HashMap::new();
+        let mut models: HashMap<V3c<u32>, Vec<Voxel>> = HashMap::new();
+        let mut palette_overflow = false;
+
+        self.visit_voxels(size, &mut |position, data| {
+            let color = Albedo::from(data);
+            let color_index = match palette_indices.get(&color) {
+                Some(index) => *index,
+                None => {
+                    if palette.len() >= VOX_PALETTE_SIZE {
+                        palette_overflow = true;
+                        return;
+                    }
+                    palette.push(color);
+                    palette_indices.insert(color, (palette.len() - 1) as u8);
+                    (palette.len() - 1) as u8
+                }
+            };
+            let position = to_vox_coordinates(position);
+            let model_position = V3c::new(
+                position.x / VOX_MODEL_MAX_SIZE,
+                position.y / VOX_MODEL_MAX_SIZE,
+                position.z / VOX_MODEL_MAX_SIZE,
+            );
+            models.entry(model_position).or_default().push(Voxel {
+                x: (position.x % VOX_MODEL_MAX_SIZE) as u8,
+                y: (position.y % VOX_MODEL_MAX_SIZE) as u8,
+                z: (position.z % VOX_MODEL_MAX_SIZE) as u8,
+                i: color_index,
+            });
+        });
+
+        if palette_overflow {
+            // More, than VOX_PALETTE_SIZE colors can not be stored in a .vox palette
+            return Err(ContreeError::LimitExceeded { limit: "palette" });
+        }
+
+        // Models are sorted by position so the output is deterministic
+        let mut models: Vec<(V3c<u32>, Vec<Voxel>)> = models.into_iter().collect();
+        models.sort_by_key(|(position, _)| (position.z, position.y, position.x));
+
+        // Scene graph: root transform -> group -> ( transform -> shape ) for every model
+        let model_size = size.min(VOX_MODEL_MAX_SIZE);
+        let mut scenes = vec![
+            SceneNode::Transform {
+                attributes: Dict::new(),
+                frames: vec![Frame {
+                    attributes: Dict::new(),
+                }],
+                child: 1,
+                layer_id: u32::MAX,
+            },
+            SceneNode::Group {
+                attributes: Dict::new(),
+                children: (0..models.len() as u32).map(|i| 2 + i * 2).collect(),
+            },
+        ];
+        for (model_index, (model_position, _)) in models.iter().enumerate() {
+            // Translations in .vox files point to the center of the model
+            let translation = *model_position * VOX_MODEL_MAX_SIZE + V3c::unit(model_size / 2);
+            scenes.push(SceneNode::Transform {
+                attributes: Dict::new(),
+                frames: vec![Frame {
+                    attributes: Dict::from([(
+                        "_t".to_string(),
+                        format!("{} {} {}", translation.x, translation.y, translation.z),
+                    )]),
+                }],
+                child: 3 + model_index as u32 * 2,
+                layer_id: 0,
+            });
+            scenes.push(SceneNode::Shape {
+                attributes: Dict::new(),
+                models: vec![ShapeModel {
+                    model_id: model_index as u32,
+                    attributes: Dict::new(),
+                }],
+            });
+        }
+
+        let mut vox_palette: Vec<Color> = palette
+            .iter()
+            .map(|color| Color {
+                r: color.r,
+                g: color.g,
+                b: color.b,
+                a: color.a,
+            })
+            .collect();
+        vox_palette.resize(VOX_PALETTE_SIZE + 1, Color { r: 0, g: 0, b: 0, a: 255 });
+
+        let vox_data = DotVoxData {
+            version: 150,
+            index_map: (0..=255).collect(),
+            models: models
+                .into_iter()
+                .map(|(_, voxels)| Model {
+                    size: Size {
+                        x: model_size,
+                        y: model_size,
+                        z: model_size,
+                    },
+                    voxels,
+                })
+                .collect(),
+            palette: vox_palette,
+            materials: Vec::new(),
+            scenes,
+            layers: vec![Layer {
+                attributes: Dict::new(),
+            }],
+        };
+
+        let mut file = std::fs::File::create(path)?;
+        vox_data.write_vox(&mut file)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{
+    contree::{
+        detail::{sectant_of, LoadBudget, CONTREE_NODE_DIMENSION},
+        types::{Albedo, Contree, ContreeError, ContreeNode, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Every mapped contree file starts with these bytes
+const MAPPED_MAGIC: [u8; 4] = *b"VHXM";
+
+/// The version of the mapped contree format written by this library
+pub const MAPPED_VERSION: u32 = 1;
+
+/// Size of the file header in bytes
+const MAPPED_HEADER_SIZE: u64 = 24;
+
+/// Byte position of the root entry inside the header
+const MAPPED_ROOT_ENTRY_OFFSET: u64 = 8;
+
+/// Byte position of the root flags inside the header
+const MAPPED_ROOT_FLAGS_OFFSET: u64 = 16;
+
+/// Set in the root flags if the root entry is a node offset
+const MAPPED_ROOT_IS_NODE: u64 = 0x01;
+
+/// Size of a node record before its entries: occupancy, node mask and mip
+const MAPPED_NODE_HEADER_SIZE: u64 = 24;
+
+/// A child of a node inside a mapped contree file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappedChild {
+    /// The child is not present, or it is empty
+    Empty,
+
+    /// The child is a leaf with the given data
+    Leaf(VoxelData),
+
+    /// The child is a node stored at the given byte offset of the file
+    Node(u64),
+}
+
+/// Read-only, random access view of a contree stored in the mapped format.
+/// The underlying bytes can come from a memory mapped file, so only the nodes
+/// which are actually visited are read from disk.
+///
+/// Layout of the file, all numbers are little endian:
+/// * header: [ magic: "VHXM" ][ version: u32 ][ root entry: u64 ][ root flags: u64 ]
+/// * node records anywhere after the header, each of them is:
+///   [ occupancy: u64 ][ node mask: u64 ][ mip: r, g, b, a bytes ][ reserved: 4 bytes ]
+///   [ entry: u64 for every bit set in occupancy ]
+///
+/// Occupancy has a bit set for every present, not empty child; the node mask marks the ones which are nodes.
+/// Node entries are byte offsets of the child record inside the file, leaf entries store the `VoxelData`.
+/// The entry of a child is found by counting the occupied sectants before it.
+pub struct MappedContree<'a> {
+    bytes: &'a [u8],
+    root: MappedChild,
+}
+
+fn read_u64_at(bytes: &[u8], offset: u64) -> Result<u64, ContreeError> {
+    let start = usize::try_from(offset).map_err(|_| ContreeError::UnexpectedEndOfData)?;
+    bytes
+        .get(start..start.saturating_add(8))
+        .filter(|slice| slice.len() == 8)
+        .map(|slice| u64::from_le_bytes(slice.try_into().unwrap()))
+        .ok_or(ContreeError::UnexpectedEndOfData)
+}
+
+/// Position of the entry for the given sectant inside a node record
+fn entry_offset(node_offset: u64, occupancy: u64, sectant: usize) -> u64 {
+    let preceding = (occupancy & ((1u64 << sectant) - 1)).count_ones() as u64;
+    node_offset.saturating_add(MAPPED_NODE_HEADER_SIZE + preceding * 8)
+}
+
+fn encode_mip(mip: &Albedo) -> u64 {
+    u32::from_le_bytes([mip.r, mip.g, mip.b, mip.a]) as u64
+}
+
+fn decode_mip(value: u64) -> Albedo {
+    let [r, g, b, a] = (value as u32).to_le_bytes();
+    Albedo { r, g, b, a }
+}
+
+fn decode_entry(entry: u64, is_node: bool) -> MappedChild {
+    if is_node {
+        MappedChild::Node(entry)
+    } else {
+        MappedChild::Leaf(entry as VoxelData)
+    }
+}
+
+impl<'a> MappedContree<'a> {
+    /// Creates a view over the given bytes, validating only the header
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ContreeError> {
+        if bytes.len() < MAPPED_HEADER_SIZE as usize {
+            return Err(ContreeError::UnexpectedEndOfData);
+        }
+        if bytes[0..4] != MAPPED_MAGIC {
+            return Err(ContreeError::InvalidHeader);
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version > MAPPED_VERSION {
+            return Err(ContreeError::UnsupportedVersion {
+                found: version,
+                supported: MAPPED_VERSION,
+            });
+        }
+        let root_entry = read_u64_at(bytes, MAPPED_ROOT_ENTRY_OFFSET)?;
+        let root_flags = read_u64_at(bytes, MAPPED_ROOT_FLAGS_OFFSET)?;
+        Ok(Self {
+            bytes,
+            root: decode_entry(root_entry, 0 != root_flags & MAPPED_ROOT_IS_NODE),
+        })
+    }
+
+    /// The root of the stored tree
+    pub fn root(&self) -> MappedChild {
+        self.root
+    }
+
+    /// Reads the given child of the node stored at the given offset
+    pub fn child(&self, node_offset: u64, sectant: usize) -> Result<MappedChild, ContreeError> {
+        if sectant >= 64 {
+            return Err(ContreeError::InvalidStructure("Sectant out of bounds".into()));
+        }
+        let occupancy = read_u64_at(self.bytes, node_offset)?;
+        let node_mask = read_u64_at(self.bytes, node_offset.saturating_add(8))?;
+        let bit = 1u64 << sectant;
+        if 0 == occupancy & bit {
+            return Ok(MappedChild::Empty);
+        }
+        let entry = read_u64_at(self.bytes, entry_offset(node_offset, occupancy, sectant))?;
+        Ok(decode_entry(entry, 0 != node_mask & bit))
+    }
+
+    /// Provides the voxel at the given position inside a tree covering size^3 voxels,
+    /// reading only the nodes on the path to it
+    pub fn get(&self, size: u32, position: &V3c<u32>) -> Result<VoxelData, ContreeError> {
+        Contree::check_bounds(size, position)?;
+        let mut current = self.root;
+        let mut size = size;
+        let mut position = *position;
+        loop {
+            match current {
+                MappedChild::Empty => return Ok(AIR),
+                MappedChild::Leaf(data) => return Ok(data),
+                MappedChild::Node(offset) => {
+                    if size < CONTREE_NODE_DIMENSION {
+                        return Err(ContreeError::InvalidStructure(
+                            "Tree is deeper, than its size allows".into(),
+                        ));
+                    }
+                    let child_size = size / CONTREE_NODE_DIMENSION;
+                    current = self.child(offset, sectant_of(&position, child_size))?;
+                    position = position % child_size;
+                    size = child_size;
+                }
+            }
+        }
+    }
+
+    /// Deserializes the subtree at the given child entry into a contree, within the default `LoadLimits`
+    pub fn load_subtree(&self, child: MappedChild) -> Result<Contree, ContreeError> {
+        self.load_subtree_with_limits(child, &LoadLimits::default())
+    }
+
+    /// Deserializes the subtree at the given child entry into a contree, failing if it exceeds the given limits
+    pub fn load_subtree_with_limits(&self, child: MappedChild, limits: &LoadLimits) -> Result<Contree, ContreeError> {
+        self.read_subtree(child, &mut LoadBudget::new(limits), 0)
+    }
+
+    fn read_subtree(&self, child: MappedChild, budget: &mut LoadBudget, depth: u32) -> Result<Contree, ContreeError> {
+        match child {
+            MappedChild::Empty => Ok(Contree::Leaf(AIR)),
+            MappedChild::Leaf(data) => Ok(Contree::Leaf(data)),
+            MappedChild::Node(offset) => {
+                // Limiting the depth also stops node offsets pointing back to their ancestors
+                budget.add_node(depth)?;
+                let occupancy = read_u64_at(self.bytes, offset)?;
+                let mip = decode_mip(read_u64_at(self.bytes, offset.saturating_add(16))?);
+                let mut children: [Option<Contree>; 64] = std::array::from_fn(|_| None);
+                for (sectant, child) in children.iter_mut().enumerate() {
+                    if 0 != occupancy & (1u64 << sectant) {
+                        *child = Some(self.read_subtree(self.child(offset, sectant)?, budget, depth + 1)?);
+                    }
+                }
+                Ok(Contree::Node(ContreeNode {
+                    mip,
+                    occupancy,
+                    children: Box::new(children),
+                }))
+            }
+        }
+    }
+
+    /// Deserializes the whole stored tree, within the default `LoadLimits`
+    pub fn load(&self) -> Result<Contree, ContreeError> {
+        self.load_subtree(self.root)
+    }
+
+    /// Deserializes the whole stored tree, failing if it exceeds the given limits
+    pub fn load_with_limits(&self, limits: &LoadLimits) -> Result<Contree, ContreeError> {
+        self.load_subtree_with_limits(self.root, limits)
+    }
+}
+
+//####################################################################################
+//  Writing
+//####################################################################################
+
+/// Appends the node to the given bytes, which start at `base_offset` inside the file.
+/// Returns the file offset of the written node record.
+fn write_node(bytes: &mut Vec<u8>, base_offset: u64, node: &ContreeNode) -> u64 {
+    let record_start = bytes.len();
+    let mut occupancy = 0u64;
+    let mut node_mask = 0u64;
+    for (sectant, child) in node.children.iter().enumerate() {
+        match child {
+            Some(Contree::Leaf(data)) if *data != AIR => occupancy |= 1 << sectant,
+            Some(Contree::Node(_)) => {
+                occupancy |= 1 << sectant;
+                node_mask |= 1 << sectant;
+            }
+            _ => {}
+        }
+    }
+    bytes.extend_from_slice(&occupancy.to_le_bytes());
+    bytes.extend_from_slice(&node_mask.to_le_bytes());
+    bytes.extend_from_slice(&encode_mip(&node.mip).to_le_bytes());
+    let first_entry = bytes.len();
+    bytes.resize(first_entry + occupancy.count_ones() as usize * 8, 0);
+
+    let mut entry_position = first_entry;
+    for child in node.children.iter() {
+        let entry = match child {
+            Some(Contree::Leaf(data)) if *data != AIR => *data as u64,
+            Some(Contree::Node(child_node)) => write_node(bytes, base_offset, child_node),
+            _ => continue,
+        };
+        bytes[entry_position..entry_position + 8].copy_from_slice(&entry.to_le_bytes());
+        entry_position += 8;
+    }
+    base_offset + record_start as u64
+}
+
+/// Encodes the given subtree to be placed at `base_offset` inside the file,
+/// returning the bytes and the entry pointing to its root
+fn encode_subtree(subtree: &Contree, base_offset: u64) -> (Vec<u8>, MappedChild) {
+    let mut bytes = Vec::new();
+    let entry = match subtree {
+        Contree::Leaf(AIR) => MappedChild::Empty,
+        Contree::Leaf(data) => MappedChild::Leaf(*data),
+        Contree::Node(node) => MappedChild::Node(write_node(&mut bytes, base_offset, node)),
+    };
+    (bytes, entry)
+}
+
+fn read_u64_from<F: Read + Seek>(file: &mut F, offset: u64) -> Result<u64, ContreeError> {
+    let mut buffer = [0u8; 8];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer).map_err(|error| match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => ContreeError::UnexpectedEndOfData,
+        _ => ContreeError::Io(error),
+    })?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn write_u64_to<F: Write + Seek>(file: &mut F, offset: u64, value: u64) -> Result<(), ContreeError> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+/// A node record read from a mapped contree file
+struct NodeRecord {
+    occupancy: u64,
+    node_mask: u64,
+    mip: u64,
+    entries: Vec<u64>,
+}
+
+/// Reads the record of the node at the given offset
+fn read_record<F: Read + Seek>(file: &mut F, offset: u64) -> Result<NodeRecord, ContreeError> {
+    let occupancy = read_u64_from(file, offset)?;
+    let node_mask = read_u64_from(file, offset.saturating_add(8))?;
+    let mip = read_u64_from(file, offset.saturating_add(16))?;
+    let mut entries = Vec::with_capacity(occupancy.count_ones() as usize);
+    for index in 0..occupancy.count_ones() as u64 {
+        entries.push(read_u64_from(
+            file,
+            offset.saturating_add(MAPPED_NODE_HEADER_SIZE + index * 8),
+        )?);
+    }
+    Ok(NodeRecord {
+        occupancy,
+        node_mask,
+        mip,
+        entries,
+    })
+}
+
+/// Points the given parent slot to the new child entry. Overwrites the slot in place when the
+/// parent already has an entry of the same kind for it, so a single write makes the change visible.
+/// Otherwise a modified copy of the parent is appended to the file and its own parent is repointed
+/// recursively, up to the header.
+/// * `path` - The offsets of the nodes from the root to the parent, with the sectant taken at each of them
+fn repoint<F: Read + Write + Seek>(
+    file: &mut F,
+    path: &[(u64, usize)],
+    child: MappedChild,
+) -> Result<(), ContreeError> {
+    let Some(((parent_offset, sectant), ancestors)) = path.split_last() else {
+        let (entry, flags) = match child {
+            MappedChild::Empty => (AIR as u64, 0),
+            MappedChild::Leaf(data) => (data as u64, 0),
+            MappedChild::Node(offset) => (offset, MAPPED_ROOT_IS_NODE),
+        };
+        // The flags are only written when they change, and after the entry they describe
+        write_u64_to(file, MAPPED_ROOT_ENTRY_OFFSET, entry)?;
+        if flags != read_u64_from(file, MAPPED_ROOT_FLAGS_OFFSET)? {
+            write_u64_to(file, MAPPED_ROOT_FLAGS_OFFSET, flags)?;
+        }
+        return Ok(());
+    };
+
+    let NodeRecord {
+        occupancy,
+        node_mask,
+        mip,
+        mut entries,
+    } = read_record(file, *parent_offset)?;
+    let bit = 1u64 << sectant;
+    let index = (occupancy & (bit - 1)).count_ones() as usize;
+    let is_present = 0 != occupancy & bit;
+    let (new_occupancy, new_node_mask) = match child {
+        MappedChild::Empty => (occupancy & !bit, node_mask & !bit),
+        MappedChild::Leaf(_) => (occupancy | bit, node_mask & !bit),
+        MappedChild::Node(_) => (occupancy | bit, node_mask | bit),
+    };
+    let entry = match child {
+        MappedChild::Empty => None,
+        MappedChild::Leaf(data) => Some(data as u64),
+        MappedChild::Node(offset) => Some(offset),
+    };
+
+    if let (true, Some(entry), true) = (is_present, entry, node_mask == new_node_mask) {
+        // The slot exists already with the same kind of child, only its entry needs to be updated
+        return write_u64_to(file, entry_offset(*parent_offset, occupancy, *sectant), entry);
+    }
+
+    match (is_present, entry) {
+        (true, None) => {
+            entries.remove(index);
+        }
+        (true, Some(entry)) => entries[index] = entry,
+        (false, Some(entry)) => entries.insert(index, entry),
+        (false, None) => {}
+    }
+    let new_parent_offset = file.seek(SeekFrom::End(0))?;
+    let mut record = Vec::with_capacity(MAPPED_NODE_HEADER_SIZE as usize + entries.len() * 8);
+    record.extend_from_slice(&new_occupancy.to_le_bytes());
+    record.extend_from_slice(&new_node_mask.to_le_bytes());
+    record.extend_from_slice(&mip.to_le_bytes());
+    for entry in entries {
+        record.extend_from_slice(&entry.to_le_bytes());
+    }
+    file.write_all(&record)?;
+    repoint(file, ancestors, MappedChild::Node(new_parent_offset))
+}
+
+/// Replaces the subtree reached by following the given sectants from the root of a mapped contree file.
+/// The new subtree is appended to the end of the file and the parent nodes are repointed to it;
+/// Records which are no longer referenced are left in place.
+pub fn replace_subtree<F: Read + Write + Seek>(
+    file: &mut F,
+    sectants: &[usize],
+    subtree: &Contree,
+) -> Result<(), ContreeError> {
+    if sectants.iter().any(|sectant| *sectant >= 64) {
+        return Err(ContreeError::InvalidStructure("Sectant out of bounds".into()));
+    }
+
+    // Collect the nodes along the path; The path may not go through leaves
+    let root_entry = read_u64_from(file, MAPPED_ROOT_ENTRY_OFFSET)?;
+    let root_flags = read_u64_from(file, MAPPED_ROOT_FLAGS_OFFSET)?;
+    let mut current = decode_entry(root_entry, 0 != root_flags & MAPPED_ROOT_IS_NODE);
+    let mut path = Vec::with_capacity(sectants.len());
+    for sectant in sectants {
+        let MappedChild::Node(offset) = current else {
+            return Err(ContreeError::InvalidStructure(
+                "Subtree path leads through a leaf".into(),
+            ));
+        };
+        let NodeRecord {
+            occupancy,
+            node_mask,
+            ..
+        } = read_record(file, offset)?;
+        let bit = 1u64 << sectant;
+        current = if 0 == occupancy & bit {
+            MappedChild::Empty
+        } else {
+            decode_entry(
+                read_u64_from(file, entry_offset(offset, occupancy, *sectant))?,
+                0 != node_mask & bit,
+            )
+        };
+        path.push((offset, *sectant));
+    }
+
+    let base_offset = file.seek(SeekFrom::End(0))?;
+    let (bytes, entry) = encode_subtree(subtree, base_offset);
+    file.write_all(&bytes)?;
+    repoint(file, &path, entry)?;
+    file.flush()?;
+    Ok(())
+}
+
+impl Contree {
+    /// Encodes the contree into the mapped format, see `MappedContree` for the layout
+    pub fn to_mapped_bytes(&self) -> Vec<u8> {
+        let (body, root) = encode_subtree(self, MAPPED_HEADER_SIZE);
+        let (root_entry, root_flags) = match root {
+            MappedChild::Empty => (AIR as u64, 0),
+            MappedChild::Leaf(data) => (data as u64, 0),
+            MappedChild::Node(offset) => (offset, MAPPED_ROOT_IS_NODE),
+        };
+        let mut bytes = Vec::with_capacity(MAPPED_HEADER_SIZE as usize + body.len());
+        bytes.extend_from_slice(&MAPPED_MAGIC);
+        bytes.extend_from_slice(&MAPPED_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&root_entry.to_le_bytes());
+        bytes.extend_from_slice(&root_flags.to_le_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    /// Saves the contree into a file in the mapped format
+    pub fn save_mapped(&self, path: &str) -> Result<(), ContreeError> {
+        std::fs::write(path, self.to_mapped_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,453 @@
+use crate::{
+    contree::{
+        detail::{is_valid_size, read_file_within_limits},
+        types::{Albedo, Contree, ContreeError, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::{V3c, V3cf32},
+};
+use std::collections::HashMap;
+
+/// A single triangle of a mesh, with optional per-vertex colors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub vertices: [V3cf32; 3],
+    pub colors: Option<[Albedo; 3]>,
+}
+
+/// Unstructured collection of triangles, as loaded from mesh files
+#[derive(Debug, Clone, Default)]
+pub struct TriangleSoup {
+    pub triangles: Vec<Triangle>,
+}
+
+/// Decides which voxels are set for a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelizationMode {
+    /// Only voxels intersecting any triangle of the mesh are set
+    Surface,
+
+    /// Voxels inside the mesh are set too; The mesh is expected to be closed
+    Solid,
+}
+
+/// Decides the data of the voxels set for a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoxelColoring {
+    /// Every voxel gets the same data
+    Constant(VoxelData),
+
+    /// Voxels get the color interpolated from the vertex colors of the closest triangle
+    /// intersecting them; Triangles without vertex colors use the given data instead
+    VertexColors { fallback: VoxelData },
+}
+
+fn invalid_mesh(message: &str) -> ContreeError {
+    ContreeError::InvalidStructure(message.to_string().into())
+}
+
+/// Converts a color channel given either in 0..=1 or in 0..=255 into a byte
+fn color_channel(value: f32) -> u8 {
+    if value <= 1. {
+        (value * 255.).round().clamp(0., 255.) as u8
+    } else {
+        value.round().clamp(0., 255.) as u8
+    }
+}
+
+fn parse_f32(value: Option<&str>) -> Result<f32, ContreeError> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| invalid_mesh("Expected a number"))
+}
+
+/// Rejects vertices with a coordinate which is NaN or infinite
+/// * `index` - the index of the vertex inside the file
+fn finite_vertex(vertex: V3cf32, index: usize) -> Result<V3cf32, ContreeError> {
+    if vertex.x.is_finite() && vertex.y.is_finite() && vertex.z.is_finite() {
+        Ok(vertex)
+    } else {
+        Err(ContreeError::NonFiniteCoordinate { index })
+    }
+}
+
+impl TriangleSoup {
+    /// Parses a Wavefront OBJ file. Polygons are triangulated as a fan;
+    /// Vertex colors are read when given after the position ( "v x y z r g b" )
+    pub fn from_obj(source: &str) -> Result<Self, ContreeError> {
+        let mut positions = Vec::new();
+        let mut colors = Vec::new();
+        let mut triangles = Vec::new();
+        for line in source.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let values: Vec<&str> = tokens.collect();
+                    let position = V3c::new(
+                        parse_f32(values.first().copied())?,
+                        parse_f32(values.get(1).copied())?,
+                        parse_f32(values.get(2).copied())?,
+                    );
+                    positions.push(finite_vertex(position, positions.len())?);
+                    colors.push(if values.len() >= 6 {
+                        Some(
+                            Albedo::default()
+                                .with_red(color_channel(parse_f32(values.get(3).copied())?))
+                                .with_green(color_channel(parse_f32(values.get(4).copied())?))
+                                .with_blue(color_channel(parse_f32(values.get(5).copied())?))
+                                .with_alpha(255),
+                        )
+                    } else {
+                        None
+                    });
+                }
+                Some("f") => {
+                    // Face entries are "v", "v/vt", "v//vn" or "v/vt/vn", indices are 1 based or negative
+                    let indices = tokens
+                        .map(|token| {
+                            let index: i64 = token
+                                .split('/')
+                                .next()
+                                .and_then(|index| index.parse().ok())
+                                .ok_or_else(|| invalid_mesh("Invalid face index"))?;
+                            let index = if index < 0 {
+                                positions.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+                            usize::try_from(index)
+                                .ok()
+                                .filter(|index| *index < positions.len())
+                                .ok_or_else(|| invalid_mesh("Face index out of bounds"))
+                        })
+                        .collect::<Result<Vec<usize>, ContreeError>>()?;
+                    for i in 1..indices.len().saturating_sub(1) {
+                        let corners = [indices[0], indices[i], indices[i + 1]];
+                        let vertex_colors = corners.map(|index| colors[index]);
+                        triangles.push(Triangle {
+                            vertices: corners.map(|index| positions[index]),
+                            colors: if vertex_colors.iter().all(|color| color.is_some()) {
+                                Some(vertex_colors.map(|color| color.unwrap()))
+                            } else {
+                                None
+                            },
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { triangles })
+    }
+
+    /// Parses an STL file, either binary or ASCII
+    pub fn from_stl(bytes: &[u8]) -> Result<Self, ContreeError> {
+        // Binary files may also start with "solid", so the size is checked first
+        if bytes.len() >= 84 {
+            let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+            if Some(bytes.len()) == count.checked_mul(50).and_then(|size| size.checked_add(84)) {
+                return Self::from_binary_stl(&bytes[84..], count);
+            }
+        }
+        if bytes.trim_ascii_start().starts_with(b"solid") {
+            Self::from_ascii_stl(&String::from_utf8_lossy(bytes))
+        } else {
+            Err(invalid_mesh("Data is neither a binary nor an ASCII STL file"))
+        }
+    }
+
+    fn from_binary_stl(bytes: &[u8], count: usize) -> Result<Self, ContreeError> {
+        let read_vertex = |data: &[u8], index: usize| {
+            let component = |i: usize| f32::from_le_bytes(data[i * 4..i * 4 + 4].try_into().unwrap());
+            finite_vertex(V3c::new(component(0), component(1), component(2)), index)
+        };
+        let triangles = bytes
+            .chunks_exact(50)
+            .take(count)
+            .enumerate()
+            .map(|(facet_index, facet)| {
+                Ok(Triangle {
+                    // The first 12 bytes are the normal, the last 2 the attribute byte count
+                    vertices: [
+                        read_vertex(&facet[12..24], facet_index * 3)?,
+                        read_vertex(&facet[24..36], facet_index * 3 + 1)?,
+                        read_vertex(&facet[36..48], facet_index * 3 + 2)?,
+                    ],
+                    colors: None,
+                })
+            })
+            .collect::<Result<Vec<Triangle>, ContreeError>>()?;
+        Ok(Self { triangles })
+    }
+
+    fn from_ascii_stl(source: &str) -> Result<Self, ContreeError> {
+        let mut triangles = Vec::new();
+        let mut vertices = Vec::with_capacity(3);
+        for line in source.lines() {
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("vertex") => {
+                    let vertex = V3c::new(
+                        parse_f32(tokens.next())?,
+                        parse_f32(tokens.next())?,
+                        parse_f32(tokens.next())?,
+                    );
+                    vertices.push(finite_vertex(vertex, triangles.len() * 3 + vertices.len())?);
+                }
+                Some("endfacet") => {
+                    if 3 != vertices.len() {
+                        return Err(invalid_mesh("Facet does not have exactly 3 vertices"));
+                    }
+                    triangles.push(Triangle {
+                        vertices: [vertices[0], vertices[1], vertices[2]],
+                        colors: None,
+                    });
+                    vertices.clear();
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { triangles })
+    }
+
+    /// Loads an .obj or .stl file, based on its extension, within the default `LoadLimits`
+    pub fn load(path: &str) -> Result<Self, ContreeError> {
+        Self::load_with_limits(path, &LoadLimits::default())
+    }
+
+    /// Loads an .obj or .stl file, based on its extension
+    /// * `limits` - bounds the size of the file
+    pub fn load_with_limits(path: &str, limits: &LoadLimits) -> Result<Self, ContreeError> {
+        let bytes = read_file_within_limits(path, limits)?;
+        if path.to_lowercase().ends_with(".obj") {
+            Self::from_obj(&String::from_utf8_lossy(&bytes))
+        } else {
+            Self::from_stl(&bytes)
+        }
+    }
+
+    /// Minimum and maximum corner of the bounding box of all triangles
+    fn bounds(&self) -> Option<(V3cf32, V3cf32)> {
+        let mut vertices = self.triangles.iter().flat_map(|triangle| triangle.vertices.iter());
+        let first = *vertices.next()?;
+        Some(vertices.fold((first, first), |(min, max), vertex| {
+            (
+                V3c::new(min.x.min(vertex.x), min.y.min(vertex.y), min.z.min(vertex.z)),
+                V3c::new(max.x.max(vertex.x), max.y.max(vertex.y), max.z.max(vertex.z)),
+            )
+        }))
+    }
+}
+
+//####################################################################################
+//  Voxelization
+//####################################################################################
+
+/// Separating axis test between a triangle and an axis aligned box ( Akenine-Möller )
+fn triangle_box_overlap(center: V3cf32, half_size: f32, vertices: &[V3cf32; 3]) -> bool {
+    let v = vertices.map(|vertex| vertex - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    let axes = [V3c::new(1., 0., 0.), V3c::new(0., 1., 0.), V3c::new(0., 0., 1.)];
+
+    // 9 axes from the cross products of the box and triangle edges
+    for edge in edges.iter() {
+        for axis in axes.iter() {
+            let test_axis = axis.cross(*edge);
+            let projections = v.map(|vertex| vertex.dot(&test_axis));
+            let radius = half_size * (test_axis.x.abs() + test_axis.y.abs() + test_axis.z.abs());
+            let min = projections[0].min(projections[1]).min(projections[2]);
+            let max = projections[0].max(projections[1]).max(projections[2]);
+            if min > radius || max < -radius {
+                return false;
+            }
+        }
+    }
+
+    // 3 axes of the box
+    for axis in 0..3 {
+        let component = |vertex: &V3cf32| [vertex.x, vertex.y, vertex.z][axis];
+        let min = component(&v[0]).min(component(&v[1])).min(component(&v[2]));
+        let max = component(&v[0]).max(component(&v[1])).max(component(&v[2]));
+        if min > half_size || max < -half_size {
+            return false;
+        }
+    }
+
+    // The normal of the triangle
+    let normal = edges[0].cross(edges[1]);
+    let distance = normal.dot(&v[0]);
+    let radius = half_size * (normal.x.abs() + normal.y.abs() + normal.z.abs());
+    distance.abs() <= radius
+}
+
+/// Barycentric coordinates of the point projected onto the plane of the triangle, clamped inside it
+fn barycentric(point: V3cf32, vertices: &[V3cf32; 3]) -> [f32; 3] {
+    let edge0 = vertices[1] - vertices[0];
+    let edge1 = vertices[2] - vertices[0];
+    let offset = point - vertices[0];
+    let d00 = edge0.dot(&edge0);
+    let d01 = edge0.dot(&edge1);
+    let d11 = edge1.dot(&edge1);
+    let d20 = offset.dot(&edge0);
+    let d21 = offset.dot(&edge1);
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() < f32::EPSILON {
+        return [1. / 3.; 3];
+    }
+    let v = ((d11 * d20 - d01 * d21) / denominator).clamp(0., 1.);
+    let w = ((d00 * d21 - d01 * d20) / denominator).clamp(0., 1. - v);
+    [1. - v - w, v, w]
+}
+
+fn triangle_data(triangle: &Triangle, point: V3cf32, coloring: VoxelColoring) -> VoxelData {
+    match (coloring, triangle.colors) {
+        (VoxelColoring::Constant(data), _) => data,
+        (VoxelColoring::VertexColors { fallback }, None) => fallback,
+        (VoxelColoring::VertexColors { .. }, Some(colors)) => {
+            let weights = barycentric(point, &triangle.vertices);
+            let channel = |select: fn(&Albedo) -> u8| {
+                (0..3)
+                    .map(|i| select(&colors[i]) as f32 * weights[i])
+                    .sum::<f32>()
+                    .round()
+                    .clamp(0., 255.) as u8
+            };
+            Albedo::default()
+                .with_red(channel(|color| color.r))
+                .with_green(channel(|color| color.g))
+                .with_blue(channel(|color| color.b))
+                .with_alpha(channel(|color| color.a))
+                .into()
+        }
+    }
+}
+
+/// Z coordinate where the line parallel to the Z axis at (x, y) crosses the triangle, if it does
+fn column_intersection(x: f32, y: f32, vertices: &[V3cf32; 3]) -> Option<f32> {
+    let [a, b, c] = vertices;
+    let edge = |p: &V3cf32, q: &V3cf32| (q.x - p.x) * (y - p.y) - (q.y - p.y) * (x - p.x);
+    let (w0, w1, w2) = (edge(b, c), edge(c, a), edge(a, b));
+    let area = w0 + w1 + w2;
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+    let inside = |w: f32| if area > 0. { w > 0. } else { w < 0. };
+    if !(inside(w0) && inside(w1) && inside(w2)) {
+        return None;
+    }
+    Some((w0 * a.z + w1 * b.z + w2 * c.z) / area)
+}
+
+impl Contree {
+    /// Converts a triangle mesh into a contree of the given size. The mesh is scaled uniformly
+    /// so its bounding box fits the tree, and its Z axis is mirrored to convert from the usual
+    /// right handed coordinate system of mesh files into the library's left handed one.
+    /// * `size` - the edge length of the tree in voxels, must be a power of 4
+    pub fn voxelize(
+        mesh: &TriangleSoup,
+        size: u32,
+        mode: VoxelizationMode,
+        coloring: VoxelColoring,
+    ) -> Result<Self, ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        let mut tree = Contree::new();
+        let Some((min, max)) = mesh.bounds() else {
+            return Ok(tree);
+        };
+        let extent = (max.x - min.x).max(max.y - min.y).max(max.z - min.z);
+        let scale = if extent > 0. { size as f32 / extent } else { 1. };
+        let to_voxel_space = |vertex: &V3cf32| {
+            let scaled = (*vertex - min) * scale;
+            V3c::new(scaled.x, scaled.y, size as f32 - scaled.z)
+        };
+
+        // Squared distance of every colored voxel to the triangle it got its data from;
+        // Only needed when the data depends on the triangle
+        let mut closest: HashMap<(u32, u32, u32), f32> = HashMap::new();
+
+        // Intersections of voxel columns along Z with the surface, used to fill solids
+        let mut columns: HashMap<(u32, u32), Vec<(f32, VoxelData)>> = HashMap::new();
+        let last_voxel = size as f32 - 1.;
+        for triangle in mesh.triangles.iter() {
+            let vertices = triangle.vertices.map(|vertex| to_voxel_space(&vertex));
+            let tri_min = vertices.iter().fold(V3c::unit(f32::MAX), |acc, v| {
+                V3c::new(acc.x.min(v.x), acc.y.min(v.y), acc.z.min(v.z))
+            });
+            let tri_max = vertices.iter().fold(V3c::unit(f32::MIN), |acc, v| {
+                V3c::new(acc.x.max(v.x), acc.y.max(v.y), acc.z.max(v.z))
+            });
+            let first: V3c<u32> = V3c::new(
+                tri_min.x.floor().clamp(0., last_voxel),
+                tri_min.y.floor().clamp(0., last_voxel),
+                tri_min.z.floor().clamp(0., last_voxel),
+            )
+            .into();
+            let last: V3c<u32> = V3c::new(
+                tri_max.x.floor().clamp(0., last_voxel),
+                tri_max.y.floor().clamp(0., last_voxel),
+                tri_max.z.floor().clamp(0., last_voxel),
+            )
+            .into();
+
+            for x in first.x..=last.x {
+                for y in first.y..=last.y {
+                    for z in first.z..=last.z {
+                        let center = V3c::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+                        if !triangle_box_overlap(center, 0.5, &vertices) {
+                            continue;
+                        }
+                        let data = triangle_data(triangle, center, coloring);
+                        if AIR == data {
+                            continue;
+                        }
+                        if let VoxelColoring::VertexColors { .. } = coloring {
+                            let weights = barycentric(center, &vertices);
+                            let surface_point = vertices[0] * weights[0]
+                                + vertices[1] * weights[1]
+                                + vertices[2] * weights[2];
+                            let offset = center - surface_point;
+                            let distance = offset.dot(&offset);
+                            match closest.get(&(x, y, z)) {
+                                Some(closest_distance) if *closest_distance <= distance => continue,
+                                _ => {
+                                    closest.insert((x, y, z), distance);
+                                }
+                            }
+                        }
+                        tree.insert_at(size, &V3c::new(x, y, z), data);
+                    }
+                    if VoxelizationMode::Solid == mode {
+                        // Columns are slightly offset from voxel centers, so they never cross
+                        // triangle edges exactly, which would count a crossing twice
+                        let (column_x, column_y) = (x as f32 + 0.50013, y as f32 + 0.50007);
+                        if let Some(z) = column_intersection(column_x, column_y, &vertices) {
+                            let data = triangle_data(triangle, V3c::new(column_x, column_y, z), coloring);
+                            columns.entry((x, y)).or_default().push((z, data));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Voxels between pairs of surface crossings along Z are inside the mesh
+        for ((x, y), mut crossings) in columns {
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for pair in crossings.chunks_exact(2) {
+                let (entry, data) = pair[0];
+                let exit = pair[1].0;
+                if AIR == data {
+                    continue;
+                }
+                let first_z = (entry - 0.5).ceil().clamp(0., last_voxel) as u32;
+                let last_z = (exit - 0.5).floor().clamp(0., last_voxel) as u32;
+                for z in first_z..=last_z {
+                    if AIR == tree.get_at(size, &V3c::new(x, y, z)) {
+                        tree.insert_at(size, &V3c::new(x, y, z), data);
+                    }
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
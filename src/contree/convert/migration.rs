@@ -0,0 +1,181 @@
+use crate::contree::{
+    convert::{
+        compression::{is_compressed, read_decompressed},
+        vhx::{VHX_MAGIC, VHX_VERSION},
+    },
+    detail::LoadBudget,
+    types::{Contree, ContreeError, LoadLimits},
+};
+use std::{borrow::Cow, collections::HashMap, io::Read};
+
+/// Upgrades .vhx data from the version it is registered for to a newer version
+pub type MigrationStep = Box<dyn Fn(&[u8]) -> Result<Vec<u8>, ContreeError>>;
+
+/// Tells if the given data is in an encoding other than .vhx the decoder understands
+pub type EncodingDetector = Box<dyn Fn(&[u8]) -> bool>;
+
+/// Decodes data written in an encoding other than .vhx
+pub type LegacyDecoder = Box<dyn Fn(&[u8]) -> Result<Contree, ContreeError>>;
+
+/// Loads contrees saved by any earlier version of the library: .vhx data written with an
+/// older version of the format is upgraded one version at a time through the registered
+/// migration steps, while data in other encodings ( e.g. dumps written through serde )
+/// is handed to the first registered legacy decoder recognizing it. Trees saved as JSON through
+/// serde are recognized with the `serialization` feature, trees saved as bencode through bendy
+/// additionally need the `bytecode` feature.
+/// Compressed data is recognized by its header and decompressed before either of these.
+pub struct VersionedLoader {
+    migrations: HashMap<u32, MigrationStep>,
+    legacy_encodings: Vec<(EncodingDetector, LegacyDecoder)>,
+    limits: LoadLimits,
+}
+
+impl Default for VersionedLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VersionedLoader {
+    /// Creates a loader with the migration steps between the versions of the .vhx format,
+    /// and the decoders of the legacy encodings enabled by the features of the crate;
+    /// The current version is the first one, so there are no migration steps yet
+    pub fn new() -> Self {
+        let loader = Self {
+            migrations: HashMap::new(),
+            legacy_encodings: Vec::new(),
+            limits: LoadLimits::default(),
+        };
+        #[cfg(feature = "serialization")]
+        let loader = loader.with_legacy_encoding(is_serde_json, decode_serde_json);
+        #[cfg(all(feature = "serialization", feature = "bytecode"))]
+        let loader = loader.with_legacy_encoding(is_bencode, decode_bencode);
+        loader
+    }
+
+    /// Sets the limits enforced while decoding .vhx data
+    pub fn with_limits(mut self, limits: LoadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Registers a migration step upgrading .vhx data from the given version;
+    /// Replaces the step previously registered for the same version
+    pub fn with_migration<F>(mut self, from_version: u32, step: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Vec<u8>, ContreeError> + 'static,
+    {
+        self.migrations.insert(from_version, Box::new(step));
+        self
+    }
+
+    /// Registers a decoder for an encoding other than .vhx;
+    /// Decoders are tried in the order of registration, the built-in ones first.
+    /// Decoded trees are checked against the limits of the loader.
+    pub fn with_legacy_encoding<D, F>(mut self, detect: D, decode: F) -> Self
+    where
+        D: Fn(&[u8]) -> bool + 'static,
+        F: Fn(&[u8]) -> Result<Contree, ContreeError> + 'static,
+    {
+        self.legacy_encodings.push((Box::new(detect), Box::new(decode)));
+        self
+    }
+
+    /// Provides the version of the given .vhx data, or None if it is not .vhx data
+    pub fn vhx_version(bytes: &[u8]) -> Option<u32> {
+        if bytes.len() < 8 || bytes[0..4] != VHX_MAGIC {
+            return None;
+        }
+        Some(u32::from_le_bytes(bytes[4..8].try_into().unwrap()))
+    }
+
+    /// Upgrades the given .vhx data to the current version of the format
+    pub fn migrate<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, [u8]>, ContreeError> {
+        let mut bytes = Cow::Borrowed(bytes);
+        let mut version = Self::vhx_version(&bytes).ok_or(ContreeError::InvalidHeader)?;
+        while version < VHX_VERSION {
+            let step = self
+                .migrations
+                .get(&version)
+                .ok_or(ContreeError::UnsupportedVersion {
+                    found: version,
+                    supported: VHX_VERSION,
+                })?;
+            let migrated = step(&bytes)?;
+            let migrated_version = Self::vhx_version(&migrated).ok_or(ContreeError::InvalidHeader)?;
+            if migrated_version <= version {
+                return Err(ContreeError::InvalidStructure(
+                    format!("Migration step from version {version} did not upgrade the data").into(),
+                ));
+            }
+            version = migrated_version;
+            bytes = Cow::Owned(migrated);
+        }
+        if version > VHX_VERSION {
+            return Err(ContreeError::UnsupportedVersion {
+                found: version,
+                supported: VHX_VERSION,
+            });
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a contree from data written by any supported version or encoding
+    pub fn load(&self, bytes: &[u8]) -> Result<Contree, ContreeError> {
+        if is_compressed(bytes) {
+            return self.load_uncompressed(&read_decompressed(bytes, self.limits.max_memory)?);
+        }
+        self.load_uncompressed(bytes)
+    }
+
+    /// Decodes a contree from the given reader, decompressing the data while it is read
+    pub fn load_from<R: Read>(&self, reader: R) -> Result<Contree, ContreeError> {
+        self.load_uncompressed(&read_decompressed(reader, self.limits.max_memory)?)
+    }
+
+    fn load_uncompressed(&self, bytes: &[u8]) -> Result<Contree, ContreeError> {
+        if Self::vhx_version(bytes).is_none() {
+            if let Some((_, decode)) = self.legacy_encodings.iter().find(|(detect, _)| detect(bytes)) {
+                let tree = decode(bytes)?;
+                LoadBudget::new(&self.limits).add_tree(&tree, 0)?;
+                return Ok(tree);
+            }
+        }
+        Contree::decode_current_vhx(&self.migrate(bytes)?, &self.limits)
+    }
+
+    /// Loads a contree from a file written by any supported version or encoding
+    pub fn load_file(&self, path: &str) -> Result<Contree, ContreeError> {
+        self.load_from(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+//####################################################################################
+//  Legacy encodings
+//####################################################################################
+
+/// Trees saved through serde as JSON start with the variant of the root: {"Leaf": .. } or {"Node": .. }
+#[cfg(feature = "serialization")]
+fn is_serde_json(bytes: &[u8]) -> bool {
+    let Some(content) = bytes.trim_ascii_start().strip_prefix(b"{") else {
+        return false;
+    };
+    let content = content.trim_ascii_start();
+    content.starts_with(b"\"Leaf\"") || content.starts_with(b"\"Node\"")
+}
+
+#[cfg(feature = "serialization")]
+fn decode_serde_json(bytes: &[u8]) -> Result<Contree, ContreeError> {
+    serde_json::from_slice(bytes).map_err(|error| ContreeError::InvalidStructure(error.into()))
+}
+
+/// Trees saved through serde as bencode are a dictionary with the variant of the root as its only key
+#[cfg(all(feature = "serialization", feature = "bytecode"))]
+fn is_bencode(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"d4:Leaf") || bytes.starts_with(b"d4:Node")
+}
+
+#[cfg(all(feature = "serialization", feature = "bytecode"))]
+fn decode_bencode(bytes: &[u8]) -> Result<Contree, ContreeError> {
+    bendy::serde::from_bytes(bytes).map_err(|error| ContreeError::InvalidStructure(error.to_string().into()))
+}
@@ -0,0 +1,398 @@
+use crate::{
+    contree::{
+        convert::minecraft::{
+            from_minecraft_coordinates,
+            nbt::{parse_nbt, NbtTag},
+            BlockMapping,
+        },
+        detail::{
+            is_valid_size, read_bounded, read_file_within_limits, size_within_limits, LoadBudget,
+        },
+        types::{Contree, ContreeError, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+/// Edge length of a chunk section in blocks
+const SECTION_SIZE: i32 = 16;
+
+/// Number of blocks inside a chunk section
+const SECTION_VOLUME: usize = 4096;
+
+/// Number of chunks along one side of a region
+const REGION_CHUNKS: i32 = 32;
+
+/// Size of a sector inside region files in bytes
+const REGION_SECTOR_SIZE: usize = 4096;
+
+/// The first data version ( snapshot 20w17a, before 1.16 ) where block state entries do not span across longs;
+/// Before it, entries are packed tightly, so an entry may continue in the next long
+const ALIGNED_BLOCK_STATES_DATA_VERSION: i64 = 2529;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+
+/// A 16^3 section of a Minecraft world
+#[derive(Debug, Clone)]
+pub struct AnvilSection {
+    /// Position of the section in section coordinates ( block position / 16 ), Y can be negative
+    pub position: V3c<i32>,
+
+    /// The blocks of the section, in the library coordinate system
+    pub tree: Contree,
+}
+
+/// Builds a block state string ( e.g. "minecraft:oak_log[axis=y]" ) from a palette entry,
+/// with properties sorted by name
+fn block_state_name(entry: &NbtTag) -> Result<String, ContreeError> {
+    let name = entry
+        .get("Name")
+        .and_then(|name| name.as_str())
+        .ok_or_else(|| ContreeError::InvalidStructure("Block state has no name".into()))?;
+    let Some(properties) = entry
+        .get("Properties")
+        .and_then(|properties| properties.as_compound())
+    else {
+        return Ok(name.to_string());
+    };
+    let mut properties: Vec<(&String, &str)> = properties
+        .iter()
+        .filter_map(|(key, value)| value.as_str().map(|value| (key, value)))
+        .collect();
+    properties.sort();
+    let properties: Vec<String> = properties
+        .into_iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect();
+    Ok(format!("{name}[{}]", properties.join(",")))
+}
+
+/// Unpacks the block state indices of a section, every entry uses at least 4 bits
+/// * `aligned` - true if entries do not span across longs, see `ALIGNED_BLOCK_STATES_DATA_VERSION`
+fn decode_block_states(
+    palette: &[VoxelData],
+    data: Option<&[i64]>,
+    aligned: bool,
+) -> Result<Vec<VoxelData>, ContreeError> {
+    if palette.len() <= 1 {
+        return Ok(vec![
+            palette.first().copied().unwrap_or(AIR);
+            SECTION_VOLUME
+        ]);
+    }
+    let data =
+        data.ok_or_else(|| ContreeError::InvalidStructure("Section has no block data".into()))?;
+    let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(4) as usize;
+    let entries_per_long = 64 / bits;
+    let mask = (1u64 << bits) - 1;
+    let long_at = |index: usize| -> Result<u64, ContreeError> {
+        data.get(index)
+            .map(|long| *long as u64)
+            .ok_or(ContreeError::UnexpectedEndOfData)
+    };
+    (0..SECTION_VOLUME)
+        .map(|index| {
+            let palette_index = if aligned {
+                (long_at(index / entries_per_long)? >> ((index % entries_per_long) * bits)) & mask
+            } else {
+                let first_bit = index * bits;
+                let (long_index, offset) = (first_bit / 64, first_bit % 64);
+                let mut value = long_at(long_index)? >> offset;
+                if offset + bits > 64 {
+                    value |= long_at(long_index + 1)? << (64 - offset);
+                }
+                value & mask
+            };
+            palette.get(palette_index as usize).copied().ok_or_else(|| {
+                ContreeError::InvalidStructure("Block references a missing palette entry".into())
+            })
+        })
+        .collect()
+}
+
+/// Calls the given function with every section of the chunk, alongside its section position
+/// and its blocks in YZX order
+fn for_each_chunk_section<F: FnMut(V3c<i32>, &[VoxelData]) -> Result<(), ContreeError>>(
+    chunk: &NbtTag,
+    mapping: &BlockMapping,
+    fun: &mut F,
+) -> Result<(), ContreeError> {
+    // Before 1.18 chunk data is inside the "Level" compound, with capitalized names
+    let level = chunk.get("Level").unwrap_or(chunk);
+
+    // Chunks without a data version are either older than 1.9, or newer than 1.18 if they have no "Level"
+    let aligned = match chunk
+        .get("DataVersion")
+        .and_then(|version| version.as_i64())
+    {
+        Some(data_version) => data_version >= ALIGNED_BLOCK_STATES_DATA_VERSION,
+        None => chunk.get("Level").is_none(),
+    };
+    let chunk_coordinate = |name: &str| -> Result<i32, ContreeError> {
+        let value = level
+            .get(name)
+            .and_then(|value| value.as_i64())
+            .ok_or_else(|| ContreeError::InvalidStructure(format!("Chunk has no {name}").into()))?;
+        i32::try_from(value).map_err(|_| {
+            ContreeError::InvalidStructure(format!("Chunk {name} does not fit into 32 bits").into())
+        })
+    };
+    let (chunk_x, chunk_z) = (chunk_coordinate("xPos")?, chunk_coordinate("zPos")?);
+    let Some(sections) = level
+        .get("sections")
+        .or_else(|| level.get("Sections"))
+        .and_then(|sections| sections.as_list())
+    else {
+        return Ok(());
+    };
+
+    for section in sections {
+        let Some(section_y) = section.get("Y").and_then(|y| y.as_i64()) else {
+            continue;
+        };
+        let block_states = section.get("block_states");
+        let Some(palette) = block_states
+            .and_then(|states| states.get("palette"))
+            .or_else(|| section.get("Palette"))
+            .and_then(|palette| palette.as_list())
+        else {
+            continue;
+        };
+        let palette = palette
+            .iter()
+            .map(|entry| Ok(mapping.get(&block_state_name(entry)?)))
+            .collect::<Result<Vec<VoxelData>, ContreeError>>()?;
+        let data = block_states
+            .and_then(|states| states.get("data"))
+            .or_else(|| section.get("BlockStates"))
+            .and_then(|data| data.as_long_array());
+        let blocks = decode_block_states(&palette, data, aligned)?;
+        let section_y = i32::try_from(section_y).map_err(|_| {
+            ContreeError::InvalidStructure("Section Y does not fit into 32 bits".into())
+        })?;
+        fun(V3c::new(chunk_x, section_y, chunk_z), &blocks)?;
+    }
+    Ok(())
+}
+
+/// Calls the given function with the parsed NBT data of every chunk stored in a region file
+/// * `limits` - bounds the size of every decompressed chunk and its parsed data
+fn for_each_region_chunk<F: FnMut(&NbtTag) -> Result<(), ContreeError>>(
+    region: &[u8],
+    limits: &LoadLimits,
+    fun: &mut F,
+) -> Result<(), ContreeError> {
+    if region.len() < REGION_SECTOR_SIZE {
+        return Err(ContreeError::UnexpectedEndOfData);
+    }
+    for location in region[..REGION_SECTOR_SIZE].chunks_exact(4) {
+        let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        if 0 == sector {
+            continue; // Chunk not generated
+        }
+        let start = sector * REGION_SECTOR_SIZE;
+        let header = region
+            .get(start..start + 5)
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+        let length = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+        let compression = header[4];
+        let payload = region
+            .get(start + 5..(start + 4).saturating_add(length))
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+
+        let decompressed = match compression {
+            COMPRESSION_GZIP => {
+                read_bounded(flate2::read::GzDecoder::new(payload), limits.max_memory)?
+            }
+            COMPRESSION_ZLIB => {
+                read_bounded(flate2::read::ZlibDecoder::new(payload), limits.max_memory)?
+            }
+            COMPRESSION_NONE => read_bounded(payload, limits.max_memory)?,
+            _ => {
+                // Chunks stored in external files or with unsupported compression are skipped
+                continue;
+            }
+        };
+        let (_, chunk) = parse_nbt(&decompressed, limits)?;
+        fun(&chunk)?;
+    }
+    Ok(())
+}
+
+/// Applies the checked operation to every component of the given positions,
+/// failing instead of overflowing for positions too far away from the origin
+fn checked_components(
+    a: V3c<i32>,
+    b: V3c<i32>,
+    operation: fn(i32, i32) -> Option<i32>,
+) -> Result<V3c<i32>, ContreeError> {
+    match (
+        operation(a.x, b.x),
+        operation(a.y, b.y),
+        operation(a.z, b.z),
+    ) {
+        (Some(x), Some(y), Some(z)) => Ok(V3c::new(x, y, z)),
+        _ => Err(ContreeError::InvalidStructure(
+            "Block position does not fit into 32 bits".into(),
+        )),
+    }
+}
+
+/// Floor division of a block coordinate into the coordinate of its containing cell
+fn cell_of(coordinate: i32, cell_size: i32) -> i32 {
+    coordinate.div_euclid(cell_size)
+}
+
+impl Contree {
+    /// Loads every section of a Minecraft region ( .mca ) file as a separate 16^3 contree,
+    /// within the default `LoadLimits`
+    /// * `mapping` - converts the block states of the world into voxel data
+    pub fn load_anvil_sections(
+        path: &str,
+        mapping: &BlockMapping,
+    ) -> Result<Vec<AnvilSection>, ContreeError> {
+        Self::load_anvil_sections_with_limits(path, mapping, &LoadLimits::default())
+    }
+
+    /// Loads every section of a Minecraft region ( .mca ) file as a separate 16^3 contree
+    /// * `mapping` - converts the block states of the world into voxel data
+    /// * `limits` - bounds the size of the file, every decompressed chunk and the loaded sections together
+    pub fn load_anvil_sections_with_limits(
+        path: &str,
+        mapping: &BlockMapping,
+        limits: &LoadLimits,
+    ) -> Result<Vec<AnvilSection>, ContreeError> {
+        let region = read_file_within_limits(path, limits)?;
+        let mut budget = LoadBudget::new(limits);
+        let mut sections = Vec::new();
+        for_each_region_chunk(&region, limits, &mut |chunk| {
+            for_each_chunk_section(chunk, mapping, &mut |position, blocks| {
+                let mut tree = Contree::new();
+                for (index, data) in blocks.iter().enumerate() {
+                    if AIR == *data {
+                        continue;
+                    }
+                    let index = index as u32;
+                    let position = V3c::new(index % 16, index / 256, (index / 16) % 16);
+                    tree.insert_within_budget(
+                        SECTION_SIZE as u32,
+                        &from_minecraft_coordinates(position, SECTION_SIZE as u32),
+                        *data,
+                        &mut budget,
+                        0,
+                    )?;
+                }
+                sections.push(AnvilSection { position, tree });
+                Ok(())
+            })
+        })?;
+        Ok(sections)
+    }
+
+    /// Loads the blocks of a Minecraft world inside the given volume into a single contree,
+    /// within the default `LoadLimits`
+    /// * `region_directory` - the "region" folder of the world, containing r.<x>.<z>.mca files
+    /// * `min_position` - the block position of the bottom corner of the volume, Y can be negative
+    /// * `size` - the edge length of the volume and the contree, must be a power of 4
+    /// * `mapping` - converts the block states of the world into voxel data
+    pub fn load_anvil_region(
+        region_directory: &str,
+        min_position: V3c<i32>,
+        size: u32,
+        mapping: &BlockMapping,
+    ) -> Result<Self, ContreeError> {
+        Self::load_anvil_region_with_limits(
+            region_directory,
+            min_position,
+            size,
+            mapping,
+            &LoadLimits::default(),
+        )
+    }
+
+    /// Loads the blocks of a Minecraft world inside the given volume into a single contree
+    /// * `region_directory` - the "region" folder of the world, containing r.<x>.<z>.mca files
+    /// * `min_position` - the block position of the bottom corner of the volume, Y can be negative
+    /// * `size` - the edge length of the volume and the contree, must be a power of 4
+    /// * `mapping` - converts the block states of the world into voxel data
+    /// * `limits` - bounds the size of the files, every decompressed chunk and the loaded tree
+    pub fn load_anvil_region_with_limits(
+        region_directory: &str,
+        min_position: V3c<i32>,
+        size: u32,
+        mapping: &BlockMapping,
+        limits: &LoadLimits,
+    ) -> Result<Self, ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        size_within_limits(size, limits)?;
+        let max_position =
+            checked_components(min_position, V3c::unit(size as i32 - 1), i32::checked_add)?;
+        let region_size = SECTION_SIZE * REGION_CHUNKS;
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        for region_x in cell_of(min_position.x, region_size)..=cell_of(max_position.x, region_size)
+        {
+            for region_z in
+                cell_of(min_position.z, region_size)..=cell_of(max_position.z, region_size)
+            {
+                let path = std::path::Path::new(region_directory)
+                    .join(format!("r.{region_x}.{region_z}.mca"));
+                if !path.exists() {
+                    continue;
+                }
+                let region = read_file_within_limits(path, limits)?;
+                for_each_region_chunk(&region, limits, &mut |chunk| {
+                    for_each_chunk_section(chunk, mapping, &mut |section, blocks| {
+                        let section_min =
+                            checked_components(section, V3c::unit(SECTION_SIZE), i32::checked_mul)?;
+                        let section_max = checked_components(
+                            section_min,
+                            V3c::unit(SECTION_SIZE - 1),
+                            i32::checked_add,
+                        )?;
+                        if section_max.x < min_position.x
+                            || section_max.y < min_position.y
+                            || section_max.z < min_position.z
+                            || section_min.x > max_position.x
+                            || section_min.y > max_position.y
+                            || section_min.z > max_position.z
+                        {
+                            return Ok(());
+                        }
+                        for (index, data) in blocks.iter().enumerate() {
+                            if AIR == *data {
+                                continue;
+                            }
+                            let index = index as i32;
+                            let block =
+                                section_min + V3c::new(index % 16, index / 256, (index / 16) % 16);
+                            let relative =
+                                checked_components(block, min_position, i32::checked_sub)?;
+                            if relative.x < 0
+                                || relative.y < 0
+                                || relative.z < 0
+                                || relative.x >= size as i32
+                                || relative.y >= size as i32
+                                || relative.z >= size as i32
+                            {
+                                continue;
+                            }
+                            tree.insert_within_budget(
+                                size,
+                                &from_minecraft_coordinates(relative.into(), size),
+                                *data,
+                                &mut budget,
+                                0,
+                            )?;
+                        }
+                        Ok(())
+                    })
+                })?;
+            }
+        }
+        Ok(tree)
+    }
+}
@@ -0,0 +1,86 @@
+pub mod anvil;
+pub(crate) mod nbt;
+pub mod schematic;
+
+use crate::{
+    contree::{
+        detail::read_bounded,
+        types::{Albedo, ContreeError, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+use std::collections::HashMap;
+
+/// Blocks which are imported as empty space, unless they are explicitly mapped
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// User supplied table converting Minecraft block states into voxel data
+#[derive(Debug, Clone, Default)]
+pub struct BlockMapping {
+    /// Voxel data for block states; keys are either a full block state
+    /// ( e.g. "minecraft:oak_log[axis=y]" ) or just a block name ( e.g. "minecraft:oak_log" )
+    pub(crate) blocks: HashMap<String, VoxelData>,
+
+    /// Voxel data used for blocks not present in the mapping
+    pub(crate) default: VoxelData,
+}
+
+impl BlockMapping {
+    /// Creates an empty mapping where every block is converted to the given data
+    pub fn new(default: VoxelData) -> Self {
+        Self {
+            blocks: HashMap::new(),
+            default,
+        }
+    }
+
+    /// Maps the given block name or block state to the given voxel data
+    pub fn with_block(mut self, block: &str, data: VoxelData) -> Self {
+        self.blocks.insert(block.to_string(), data);
+        self
+    }
+
+    /// Maps the given block name or block state to the given color
+    pub fn with_albedo(self, block: &str, albedo: Albedo) -> Self {
+        self.with_block(block, albedo.into())
+    }
+
+    /// Sets the voxel data used for blocks not present in the mapping
+    pub fn with_default(mut self, default: VoxelData) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Provides the voxel data for the given block state, trying the full state first,
+    /// then the block name without its properties, finally falling back to the default
+    pub fn get(&self, block_state: &str) -> VoxelData {
+        if let Some(data) = self.blocks.get(block_state) {
+            return *data;
+        }
+        let name = block_state.split('[').next().unwrap_or(block_state);
+        if let Some(data) = self.blocks.get(name) {
+            return *data;
+        }
+        if AIR_BLOCKS.contains(&name) {
+            AIR
+        } else {
+            self.default
+        }
+    }
+}
+
+/// Decompresses gzip data, or returns the data as is if it is not compressed
+/// * `limits` - the data after decompression can not be larger, than the memory it allows
+pub(crate) fn decompress_gzip(bytes: &[u8], limits: &LoadLimits) -> Result<Vec<u8>, ContreeError> {
+    if bytes.starts_with(&[0x1F, 0x8B]) {
+        read_bounded(flate2::read::GzDecoder::new(bytes), limits.max_memory)
+    } else {
+        read_bounded(bytes, limits.max_memory)
+    }
+}
+
+/// Converts a position in Minecraft's right handed coordinate system into the library's
+/// left handed one, by mirroring the Z axis inside a volume of the given length
+pub(crate) fn from_minecraft_coordinates(position: V3c<u32>, length: u32) -> V3c<u32> {
+    V3c::new(position.x, position.y, length - 1 - position.z)
+}
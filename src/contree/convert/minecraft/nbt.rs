@@ -0,0 +1,189 @@
+use crate::contree::{
+    convert::vhx::ByteReader,
+    detail::LoadBudget,
+    types::{ContreeError, LoadLimits},
+};
+use std::collections::HashMap;
+
+/// Compounds and lists can not be nested deeper than this, as in Minecraft itself
+const NBT_MAX_DEPTH: usize = 512;
+
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// A single value of the Named Binary Tag format used by Minecraft
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum NbtTag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<NbtTag>),
+    Compound(HashMap<String, NbtTag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl NbtTag {
+    /// Provides the child of a compound with the given name
+    pub(crate) fn get(&self, name: &str) -> Option<&NbtTag> {
+        match self {
+            NbtTag::Compound(children) => children.get(name),
+            _ => None,
+        }
+    }
+
+    /// Provides any integer tag as an i64
+    pub(crate) fn as_i64(&self) -> Option<i64> {
+        match self {
+            NbtTag::Byte(value) => Some(*value as i64),
+            NbtTag::Short(value) => Some(*value as i64),
+            NbtTag::Int(value) => Some(*value as i64),
+            NbtTag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtTag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[NbtTag]> {
+        match self {
+            NbtTag::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_compound(&self) -> Option<&HashMap<String, NbtTag>> {
+        match self {
+            NbtTag::Compound(children) => Some(children),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_byte_array(&self) -> Option<&[u8]> {
+        match self {
+            NbtTag::ByteArray(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            NbtTag::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+fn read_array<'a>(
+    reader: &mut ByteReader<'a>,
+    element_size: usize,
+    budget: &mut LoadBudget,
+) -> Result<&'a [u8], ContreeError> {
+    let length = i32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap());
+    let length = usize::try_from(length)
+        .map_err(|_| ContreeError::InvalidStructure("Negative NBT array length".into()))?;
+    let bytes = reader.read_bytes(length.saturating_mul(element_size))?;
+    budget.allocate(bytes.len())?;
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut ByteReader, budget: &mut LoadBudget) -> Result<String, ContreeError> {
+    let length = u16::from_be_bytes(reader.read_bytes(2)?.try_into().unwrap());
+    budget.allocate(length as usize)?;
+    Ok(String::from_utf8_lossy(reader.read_bytes(length as usize)?).into_owned())
+}
+
+fn read_payload(
+    reader: &mut ByteReader,
+    tag_type: u8,
+    depth: usize,
+    budget: &mut LoadBudget,
+) -> Result<NbtTag, ContreeError> {
+    if depth > NBT_MAX_DEPTH {
+        return Err(ContreeError::LimitExceeded { limit: "depth" });
+    }
+    budget.allocate(std::mem::size_of::<NbtTag>())?;
+    Ok(match tag_type {
+        TAG_BYTE => NbtTag::Byte(reader.read_u8()? as i8),
+        TAG_SHORT => NbtTag::Short(i16::from_be_bytes(reader.read_bytes(2)?.try_into().unwrap())),
+        TAG_INT => NbtTag::Int(i32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap())),
+        TAG_LONG => NbtTag::Long(i64::from_be_bytes(reader.read_bytes(8)?.try_into().unwrap())),
+        TAG_FLOAT => NbtTag::Float(f32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap())),
+        TAG_DOUBLE => NbtTag::Double(f64::from_be_bytes(reader.read_bytes(8)?.try_into().unwrap())),
+        TAG_BYTE_ARRAY => NbtTag::ByteArray(read_array(reader, 1, budget)?.to_vec()),
+        TAG_STRING => NbtTag::String(read_string(reader, budget)?),
+        TAG_LIST => {
+            let element_type = reader.read_u8()?;
+            let length = i32::from_be_bytes(reader.read_bytes(4)?.try_into().unwrap()).max(0);
+            let mut values = Vec::new();
+            for _ in 0..length {
+                values.push(read_payload(reader, element_type, depth + 1, budget)?);
+            }
+            NbtTag::List(values)
+        }
+        TAG_COMPOUND => {
+            let mut children = HashMap::new();
+            loop {
+                let child_type = reader.read_u8()?;
+                if TAG_END == child_type {
+                    break;
+                }
+                let name = read_string(reader, budget)?;
+                children.insert(name, read_payload(reader, child_type, depth + 1, budget)?);
+            }
+            NbtTag::Compound(children)
+        }
+        TAG_INT_ARRAY => NbtTag::IntArray(
+            read_array(reader, 4, budget)?
+                .chunks_exact(4)
+                .map(|value| i32::from_be_bytes(value.try_into().unwrap()))
+                .collect(),
+        ),
+        TAG_LONG_ARRAY => NbtTag::LongArray(
+            read_array(reader, 8, budget)?
+                .chunks_exact(8)
+                .map(|value| i64::from_be_bytes(value.try_into().unwrap()))
+                .collect(),
+        ),
+        _ => {
+            return Err(ContreeError::InvalidStructure(
+                format!("Unknown NBT tag type {tag_type}").into(),
+            ))
+        }
+    })
+}
+
+/// Parses an uncompressed NBT document, returning the name and the value of its root compound
+/// * `limits` - bounds the memory used by the parsed tags
+pub(crate) fn parse_nbt(bytes: &[u8], limits: &LoadLimits) -> Result<(String, NbtTag), ContreeError> {
+    let mut budget = LoadBudget::new(limits);
+    let mut reader = ByteReader::new(bytes);
+    if TAG_COMPOUND != reader.read_u8()? {
+        return Err(ContreeError::InvalidStructure(
+            "NBT document does not start with a compound".into(),
+        ));
+    }
+    let name = read_string(&mut reader, &mut budget)?;
+    Ok((name, read_payload(&mut reader, TAG_COMPOUND, 0, &mut budget)?))
+}
@@ -0,0 +1,107 @@
+use crate::{
+    contree::{
+        convert::{
+            minecraft::{decompress_gzip, from_minecraft_coordinates, nbt::parse_nbt, BlockMapping},
+            vhx::ByteReader,
+        },
+        detail::{read_file_within_limits, size_within_limits, LoadBudget},
+        types::{Contree, ContreeError, LoadLimits, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+use std::collections::HashMap;
+
+impl Contree {
+    /// Loads a Sponge schematic ( .schem, version 1 to 3 ) file into a contree, within the default `LoadLimits`
+    /// * `mapping` - converts the block states of the schematic into voxel data
+    pub fn load_schematic(path: &str, mapping: &BlockMapping) -> Result<Self, ContreeError> {
+        Self::load_schematic_with_limits(path, mapping, &LoadLimits::default())
+    }
+
+    /// Loads a Sponge schematic ( .schem, version 1 to 3 ) file into a contree
+    /// * `mapping` - converts the block states of the schematic into voxel data
+    /// * `limits` - bounds the size of the file, the decompressed data and the loaded tree
+    pub fn load_schematic_with_limits(
+        path: &str,
+        mapping: &BlockMapping,
+        limits: &LoadLimits,
+    ) -> Result<Self, ContreeError> {
+        Self::from_schematic_bytes_with_limits(&read_file_within_limits(path, limits)?, mapping, limits)
+    }
+
+    /// Parses the contents of a Sponge schematic, compressed or not, into a contree,
+    /// within the default `LoadLimits`
+    /// * `mapping` - converts the block states of the schematic into voxel data
+    pub fn from_schematic_bytes(bytes: &[u8], mapping: &BlockMapping) -> Result<Self, ContreeError> {
+        Self::from_schematic_bytes_with_limits(bytes, mapping, &LoadLimits::default())
+    }
+
+    /// Parses the contents of a Sponge schematic, compressed or not, into a contree
+    /// * `mapping` - converts the block states of the schematic into voxel data
+    /// * `limits` - bounds the size of the decompressed data and the loaded tree
+    pub fn from_schematic_bytes_with_limits(
+        bytes: &[u8],
+        mapping: &BlockMapping,
+        limits: &LoadLimits,
+    ) -> Result<Self, ContreeError> {
+        let (_, root) = parse_nbt(&decompress_gzip(bytes, limits)?, limits)?;
+
+        // Version 3 wraps everything into a "Schematic" compound, and moves blocks into "Blocks"
+        let schematic = root.get("Schematic").unwrap_or(&root);
+        let blocks = schematic.get("Blocks").unwrap_or(schematic);
+        let dimension = |name: &str| -> Result<u32, ContreeError> {
+            schematic
+                .get(name)
+                .and_then(|value| value.as_i64())
+                .map(|value| value as u16 as u32)
+                .ok_or_else(|| ContreeError::InvalidStructure(format!("Schematic has no {name}").into()))
+        };
+        let (width, height, length) = (dimension("Width")?, dimension("Height")?, dimension("Length")?);
+
+        let palette = blocks
+            .get("Palette")
+            .and_then(|palette| palette.as_compound())
+            .ok_or_else(|| ContreeError::InvalidStructure("Schematic has no block palette".into()))?;
+        let mut palette_data = HashMap::with_capacity(palette.len());
+        for (block_state, index) in palette.iter() {
+            let index = index
+                .as_i64()
+                .and_then(|index| u32::try_from(index).ok())
+                .ok_or_else(|| ContreeError::InvalidStructure("Invalid block palette index".into()))?;
+            palette_data.insert(index, mapping.get(block_state));
+        }
+
+        let block_data = blocks
+            .get("BlockData")
+            .or_else(|| blocks.get("Data"))
+            .and_then(|data| data.as_byte_array())
+            .ok_or_else(|| ContreeError::InvalidStructure("Schematic has no block data".into()))?;
+
+        let size = size_within_limits(width.max(height).max(length), limits)?;
+
+        // Blocks are stored as varint palette indices in YZX order
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        let mut reader = ByteReader::new(block_data);
+        for y in 0..height {
+            for z in 0..length {
+                for x in 0..width {
+                    let index = reader.read_varint()?;
+                    let data = *palette_data.get(&index).ok_or_else(|| {
+                        ContreeError::InvalidStructure("Block references a missing palette entry".into())
+                    })?;
+                    if AIR != data {
+                        tree.insert_within_budget(
+                            size,
+                            &from_minecraft_coordinates(V3c::new(x, y, z), length),
+                            data,
+                            &mut budget,
+                            0,
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
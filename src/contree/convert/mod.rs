@@ -0,0 +1,15 @@
+pub mod compression;
+pub mod dense_volume;
+pub mod graph_export;
+pub mod isosurface;
+#[cfg(feature = "dot_vox_support")]
+pub mod magicavoxel;
+pub mod mapped;
+pub mod mesh;
+pub mod migration;
+pub mod point_cloud;
+pub mod surface_mesh;
+#[cfg(feature = "minecraft_support")]
+pub mod minecraft;
+pub mod vhx;
+pub mod volume_slices;
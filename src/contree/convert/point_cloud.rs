@@ -0,0 +1,435 @@
+use crate::{
+    contree::{
+        convert::vhx::ByteReader,
+        detail::{read_file_within_limits, size_within_limits, LoadBudget},
+        types::{Albedo, Contree, ContreeError, LoadLimits},
+    },
+    spatial::math::vector::{V3c, V3cf32},
+};
+use std::collections::HashMap;
+
+/// A single sample of a point cloud
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloudPoint {
+    pub position: V3cf32,
+    pub color: Option<Albedo>,
+}
+
+/// Unstructured collection of points, as loaded from scan files
+#[derive(Debug, Clone, Default)]
+pub struct PointCloud {
+    pub points: Vec<CloudPoint>,
+}
+
+/// The result of converting a point cloud into voxels
+#[derive(Debug, Clone)]
+pub struct PointCloudVoxels {
+    /// The voxels, storing the average color of their points
+    pub tree: Contree,
+
+    /// The edge length of the tree in voxels
+    pub size: u32,
+
+    /// The number of points which landed in each voxel, including dropped voxels
+    pub point_counts: HashMap<V3c<u32>, u32>,
+}
+
+fn invalid_cloud(message: &str) -> ContreeError {
+    ContreeError::InvalidStructure(message.to_string().into())
+}
+
+/// Scalar types of PLY properties
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<Self, ContreeError> {
+        Ok(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return Err(invalid_cloud("Unknown PLY property type")),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, PlyType::F32 | PlyType::F64)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum PlyProperty {
+    Scalar { name: String, value_type: PlyType },
+    List { count_type: PlyType, value_type: PlyType },
+}
+
+#[derive(Debug, Clone)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Source of PLY values, either whitespace separated text or packed binary
+enum PlyValues<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary(ByteReader<'a>, bool),
+}
+
+impl PlyValues<'_> {
+    fn next(&mut self, value_type: PlyType) -> Result<f64, ContreeError> {
+        match self {
+            PlyValues::Ascii(tokens) => tokens
+                .next()
+                .and_then(|token| token.parse().ok())
+                .ok_or(ContreeError::UnexpectedEndOfData),
+            PlyValues::Binary(reader, little_endian) => {
+                let bytes = reader.read_bytes(value_type.size())?;
+                macro_rules! read {
+                    ($type:ty) => {{
+                        let bytes = bytes.try_into().unwrap();
+                        if *little_endian {
+                            <$type>::from_le_bytes(bytes) as f64
+                        } else {
+                            <$type>::from_be_bytes(bytes) as f64
+                        }
+                    }};
+                }
+                Ok(match value_type {
+                    PlyType::I8 => bytes[0] as i8 as f64,
+                    PlyType::U8 => bytes[0] as f64,
+                    PlyType::I16 => read!(i16),
+                    PlyType::U16 => read!(u16),
+                    PlyType::I32 => read!(i32),
+                    PlyType::U32 => read!(u32),
+                    PlyType::F32 => read!(f32),
+                    PlyType::F64 => read!(f64),
+                })
+            }
+        }
+    }
+}
+
+/// Converts a color channel into a byte; floating point channels are expected in 0..=1
+fn color_channel(value: f64, value_type: PlyType) -> u8 {
+    if value_type.is_float() {
+        (value * 255.).round().clamp(0., 255.) as u8
+    } else {
+        value.round().clamp(0., 255.) as u8
+    }
+}
+
+impl PointCloud {
+    /// Parses a text file with a point on every line: "x y z" or "x y z r g b",
+    /// separated by whitespace or commas. Colors are bytes, or floats in 0..=1 if no
+    /// color channel in the whole file is above 1.
+    /// Empty lines and lines starting with '#' or '//' are skipped.
+    pub fn from_xyz(source: &str) -> Result<Self, ContreeError> {
+        let mut rows = Vec::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let values = line
+                .split(|c: char| c.is_whitespace() || ',' == c)
+                .filter(|token| !token.is_empty())
+                .map(|token| token.parse::<f32>().map_err(|_| invalid_cloud("Expected a number")))
+                .collect::<Result<Vec<f32>, ContreeError>>()?;
+            if values.len() < 3 {
+                return Err(invalid_cloud("Point has less than 3 coordinates"));
+            }
+            rows.push(values);
+        }
+
+        // The scale of colors is decided for the whole file, as a dark point in a file
+        // with byte colors would otherwise be read as a bright float color
+        let is_float = rows
+            .iter()
+            .filter(|values| values.len() >= 6)
+            .all(|values| values[3..6].iter().all(|value| *value <= 1.));
+        let value_type = if is_float { PlyType::F32 } else { PlyType::U8 };
+        let points = rows
+            .into_iter()
+            .map(|values| CloudPoint {
+                position: V3c::new(values[0], values[1], values[2]),
+                color: if values.len() >= 6 {
+                    Some(
+                        Albedo::default()
+                            .with_red(color_channel(values[3] as f64, value_type))
+                            .with_green(color_channel(values[4] as f64, value_type))
+                            .with_blue(color_channel(values[5] as f64, value_type))
+                            .with_alpha(255),
+                    )
+                } else {
+                    None
+                },
+            })
+            .collect();
+        Ok(Self { points })
+    }
+
+    /// Parses a PLY file in ASCII or binary format, reading the positions and colors of the
+    /// "vertex" element; Other elements are skipped.
+    pub fn from_ply(bytes: &[u8]) -> Result<Self, ContreeError> {
+        const HEADER_END: &[u8] = b"end_header";
+        let header_end = bytes
+            .windows(HEADER_END.len())
+            .position(|window| window == HEADER_END)
+            .ok_or_else(|| invalid_cloud("PLY header is not terminated"))?;
+        let body_start = bytes[header_end..]
+            .iter()
+            .position(|byte| b'\n' == *byte)
+            .map(|newline| header_end + newline + 1)
+            .unwrap_or(bytes.len());
+
+        let header = String::from_utf8_lossy(&bytes[..header_end]);
+        let mut lines = header.lines();
+        if Some("ply") != lines.next().map(|line| line.trim()) {
+            return Err(ContreeError::InvalidHeader);
+        }
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = Vec::new();
+        for line in lines {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.as_slice() {
+                ["format", "ascii", ..] => format = Some(PlyFormat::Ascii),
+                ["format", "binary_little_endian", ..] => format = Some(PlyFormat::BinaryLittleEndian),
+                ["format", "binary_big_endian", ..] => format = Some(PlyFormat::BinaryBigEndian),
+                ["element", name, count] => elements.push(PlyElement {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| invalid_cloud("Invalid PLY element count"))?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count_type, value_type, _] => elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_cloud("PLY property outside of element"))?
+                    .properties
+                    .push(PlyProperty::List {
+                        count_type: PlyType::parse(count_type)?,
+                        value_type: PlyType::parse(value_type)?,
+                    }),
+                ["property", value_type, name] => elements
+                    .last_mut()
+                    .ok_or_else(|| invalid_cloud("PLY property outside of element"))?
+                    .properties
+                    .push(PlyProperty::Scalar {
+                        name: name.to_string(),
+                        value_type: PlyType::parse(value_type)?,
+                    }),
+                _ => {}
+            }
+        }
+
+        let body = &bytes[body_start.min(bytes.len())..];
+        let ascii_body;
+        let mut values = match format.ok_or_else(|| invalid_cloud("PLY format is missing"))? {
+            PlyFormat::Ascii => {
+                ascii_body = String::from_utf8_lossy(body);
+                PlyValues::Ascii(ascii_body.split_ascii_whitespace())
+            }
+            PlyFormat::BinaryLittleEndian => PlyValues::Binary(ByteReader::new(body), true),
+            PlyFormat::BinaryBigEndian => PlyValues::Binary(ByteReader::new(body), false),
+        };
+
+        let mut points = Vec::new();
+        for element in elements.iter() {
+            // Every instance of an element takes at least a byte, unless it has no properties,
+            // which would make any count valid without reading anything
+            if 0 < element.count && element.properties.is_empty() {
+                return Err(invalid_cloud("PLY element has no properties"));
+            }
+            if element.count > body.len() {
+                return Err(ContreeError::UnexpectedEndOfData);
+            }
+            let is_vertex = "vertex" == element.name;
+            for _ in 0..element.count {
+                let mut position = V3c::unit(0.);
+                let mut color = Albedo::default().with_alpha(255);
+                let mut has_color = false;
+                for property in element.properties.iter() {
+                    match property {
+                        PlyProperty::Scalar { name, value_type } => {
+                            let value = values.next(*value_type)?;
+                            match name.as_str() {
+                                "x" => position.x = value as f32,
+                                "y" => position.y = value as f32,
+                                "z" => position.z = value as f32,
+                                "red" | "r" => {
+                                    color.r = color_channel(value, *value_type);
+                                    has_color = true;
+                                }
+                                "green" | "g" => color.g = color_channel(value, *value_type),
+                                "blue" | "b" => color.b = color_channel(value, *value_type),
+                                "alpha" | "a" => color.a = color_channel(value, *value_type),
+                                _ => {}
+                            }
+                        }
+                        PlyProperty::List { count_type, value_type } => {
+                            let count = values.next(*count_type)?.max(0.) as usize;
+                            for _ in 0..count {
+                                values.next(*value_type)?;
+                            }
+                        }
+                    }
+                }
+                if is_vertex {
+                    points.push(CloudPoint {
+                        position,
+                        color: if has_color { Some(color) } else { None },
+                    });
+                }
+            }
+            if is_vertex {
+                break;
+            }
+        }
+        Ok(Self { points })
+    }
+
+    /// Loads a .ply file, or a text file with a point on every line for any other extension,
+    /// within the default `LoadLimits`
+    pub fn load(path: &str) -> Result<Self, ContreeError> {
+        Self::load_with_limits(path, &LoadLimits::default())
+    }
+
+    /// Loads a .ply file, or a text file with a point on every line for any other extension
+    /// * `limits` - bounds the size of the file
+    pub fn load_with_limits(path: &str, limits: &LoadLimits) -> Result<Self, ContreeError> {
+        let bytes = read_file_within_limits(path, limits)?;
+        if path.to_lowercase().ends_with(".ply") {
+            Self::from_ply(&bytes)
+        } else {
+            Self::from_xyz(&String::from_utf8_lossy(&bytes))
+        }
+    }
+}
+
+impl Contree {
+    /// Bins the points of the cloud into voxels of the given size within the default `LoadLimits`,
+    /// see [Contree::from_point_cloud_with_limits]
+    pub fn from_point_cloud(
+        cloud: &PointCloud,
+        voxel_size: f32,
+        min_points: u32,
+        default_color: Albedo,
+    ) -> Result<PointCloudVoxels, ContreeError> {
+        Self::from_point_cloud_with_limits(cloud, voxel_size, min_points, default_color, &LoadLimits::default())
+    }
+
+    /// Bins the points of the cloud into voxels of the given size, storing the average color
+    /// of the points inside each voxel. The Z axis is mirrored to convert from the usual right
+    /// handed coordinate system of scans into the library's left handed one.
+    /// * `voxel_size` - The edge length of a voxel, in the units of the point cloud
+    /// * `min_points` - Voxels with fewer points than this are left empty
+    /// * `default_color` - Used for points without color information
+    /// * `limits` - bounds the depth and the size of the resulting tree
+    ///
+    /// Fails with `ContreeError::NonFiniteCoordinate` for points at NaN or infinite positions,
+    /// and with `ContreeError::LimitExceeded` if the resulting tree would exceed the limits.
+    pub fn from_point_cloud_with_limits(
+        cloud: &PointCloud,
+        voxel_size: f32,
+        min_points: u32,
+        default_color: Albedo,
+        limits: &LoadLimits,
+    ) -> Result<PointCloudVoxels, ContreeError> {
+        if !voxel_size.is_finite() || voxel_size <= 0. {
+            return Err(invalid_cloud("Voxel size must be positive"));
+        }
+        let Some(first) = cloud.points.first() else {
+            return Ok(PointCloudVoxels {
+                tree: Contree::new(),
+                size: 1,
+                point_counts: HashMap::new(),
+            });
+        };
+        if let Some(index) = cloud.points.iter().position(|point| {
+            let p = point.position;
+            !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+        }) {
+            return Err(ContreeError::NonFiniteCoordinate { index });
+        }
+        let (min, max) = cloud.points.iter().fold((first.position, first.position), |(min, max), point| {
+            let p = point.position;
+            (
+                V3c::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                V3c::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        });
+        let cell_count = ((max - min) / voxel_size).floor();
+        // The cell count may still be infinite for tiny voxel sizes; The conversion saturates then
+        let extent = (cell_count.x.max(cell_count.y).max(cell_count.z) as u64)
+            .checked_add(1)
+            .and_then(|extent| u32::try_from(extent).ok())
+            .ok_or(ContreeError::LimitExceeded { limit: "depth" })?;
+        let size = size_within_limits(extent, limits)?;
+
+        // Color channel sums and point count for every voxel
+        let mut bins: HashMap<V3c<u32>, ([u64; 4], u32)> = HashMap::new();
+        for point in cloud.points.iter() {
+            let cell = ((point.position - min) / voxel_size).floor();
+            let cell = V3c::new(cell.x as u32, cell.y as u32, cell_count.z as u32 - cell.z as u32);
+            let color = point.color.unwrap_or(default_color);
+            let bin = bins.entry(cell).or_insert(([0; 4], 0));
+            bin.0[0] += color.r as u64;
+            bin.0[1] += color.g as u64;
+            bin.0[2] += color.b as u64;
+            bin.0[3] += color.a as u64;
+            bin.1 += 1;
+        }
+
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        let mut point_counts = HashMap::with_capacity(bins.len());
+        for (cell, (sums, count)) in bins {
+            point_counts.insert(cell, count);
+            if count < min_points.max(1) {
+                continue;
+            }
+            // Alpha is kept above zero, so no averaged color could be mistaken for empty space
+            let average = |sum: u64| (sum / count as u64) as u8;
+            let color = Albedo::default()
+                .with_red(average(sums[0]))
+                .with_green(average(sums[1]))
+                .with_blue(average(sums[2]))
+                .with_alpha(average(sums[3]).max(1));
+            tree.insert_within_budget(size, &cell, color.into(), &mut budget, 0)?;
+        }
+        Ok(PointCloudVoxels {
+            tree,
+            size,
+            point_counts,
+        })
+    }
+}
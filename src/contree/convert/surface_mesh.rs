@@ -0,0 +1,393 @@
+use crate::{
+    contree::{
+        detail::is_valid_size,
+        types::{Albedo, Contree, ContreeError, VoxelData, AIR},
+    },
+    spatial::math::vector::{V3c, V3cf32},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
+
+/// A rectangular face on the boundary between solid voxels and air
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceQuad {
+    /// Corners in counter-clockwise order when looking at the front of the face
+    pub corners: [V3cf32; 4],
+
+    /// Unit vector pointing out of the solid
+    pub normal: V3cf32,
+
+    /// The data of the voxel the face belongs to
+    pub data: VoxelData,
+}
+
+/// Faces on one plane facing the same direction, with the same data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FaceGroup {
+    axis: usize,
+    positive: bool,
+    plane: u32,
+    data: VoxelData,
+}
+
+fn component(vector: &V3c<u32>, axis: usize) -> u32 {
+    [vector.x, vector.y, vector.z][axis]
+}
+
+/// The given vector with its coordinate on the given axis replaced
+fn with_component(vector: &V3c<u32>, axis: usize, value: u32) -> V3c<u32> {
+    let mut components = [vector.x, vector.y, vector.z];
+    components[axis] = value;
+    V3c::new(components[0], components[1], components[2])
+}
+
+/// Builds a vector from the coordinate on the given axis and the two coordinates on the following axes
+fn from_plane(axis: usize, plane: f32, u: f32, v: f32) -> V3cf32 {
+    match axis {
+        0 => V3c::new(plane, u, v),
+        1 => V3c::new(v, plane, u),
+        _ => V3c::new(u, v, plane),
+    }
+}
+
+/// Merges the given cells of a plane into rectangles: extends each rectangle along u as far
+/// as possible first, then along v while every cell of the next row is available.
+/// Returns rectangles as ( u, v, width, height )
+fn greedy_rectangles(cells: &HashSet<(u32, u32)>) -> Vec<(u32, u32, u32, u32)> {
+    let mut ordered: Vec<(u32, u32)> = cells.iter().copied().collect();
+    ordered.sort_by_key(|(u, v)| (*v, *u));
+    let mut remaining = cells.clone();
+    let mut rectangles = Vec::new();
+    for (u, v) in ordered {
+        if !remaining.contains(&(u, v)) {
+            continue;
+        }
+        let mut width = 1;
+        while remaining.contains(&(u + width, v)) {
+            width += 1;
+        }
+        let mut height = 1;
+        while (u..u + width).all(|cell_u| remaining.contains(&(cell_u, v + height))) {
+            height += 1;
+        }
+        for cell_v in v..v + height {
+            for cell_u in u..u + width {
+                remaining.remove(&(cell_u, cell_v));
+            }
+        }
+        rectangles.push((u, v, width, height));
+    }
+    rectangles
+}
+
+impl Contree {
+    /// Collects the faces between solid voxels and air ( or the boundary of the tree ).
+    /// Coordinates are converted into a right handed, Y up system by mirroring the Z axis.
+    /// Faces are searched on the boundaries of leaves only, so the interior of uniform
+    /// regions is never visited.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    /// * `merge` - if true, coplanar faces with the same data are merged into larger rectangles
+    pub fn surface_quads(&self, size: u32, merge: bool) -> Result<Vec<SurfaceQuad>, ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        let directions: [(usize, bool); 6] = [
+            (0, true),
+            (0, false),
+            (1, true),
+            (1, false),
+            (2, true),
+            (2, false),
+        ];
+
+        let mut groups: HashMap<FaceGroup, HashSet<(u32, u32)>> = HashMap::new();
+        self.visit_leaves(V3c::unit(0), size, &mut |position, leaf_size, data| {
+            if AIR == data {
+                return;
+            }
+            for (axis, positive) in directions {
+                let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+                let start = component(&position, axis);
+                let plane = if positive { start + leaf_size } else { start };
+                let cells = groups
+                    .entry(FaceGroup {
+                        axis,
+                        positive,
+                        plane,
+                        data,
+                    })
+                    .or_default();
+                let mut expose = |first: V3c<u32>, last: V3c<u32>| {
+                    for v in component(&first, v_axis)..component(&last, v_axis) {
+                        for u in component(&first, u_axis)..component(&last, u_axis) {
+                            cells.insert((u, v));
+                        }
+                    }
+                };
+
+                // Faces on the boundary of the tree are always exposed, others where the
+                // layer of voxels next to the leaf is empty
+                if (positive && plane >= size) || (!positive && 0 == plane) {
+                    expose(position, position + V3c::unit(leaf_size));
+                    continue;
+                }
+                let neighbor_layer = if positive { plane } else { plane - 1 };
+                self.visit_leaves_in_box(
+                    V3c::unit(0),
+                    size,
+                    &with_component(&position, axis, neighbor_layer),
+                    &with_component(&(position + V3c::unit(leaf_size)), axis, neighbor_layer + 1),
+                    &mut |first, last, neighbor| {
+                        if AIR == neighbor {
+                            expose(first, last);
+                        }
+                    },
+                );
+            }
+        });
+        groups.retain(|_, cells| !cells.is_empty());
+
+        let mut groups: Vec<(FaceGroup, HashSet<(u32, u32)>)> = groups.into_iter().collect();
+        groups.sort_by_key(|(group, _)| (group.axis, group.positive, group.plane, group.data));
+
+        let mut quads = Vec::new();
+        for (group, cells) in groups {
+            let rectangles = if merge {
+                greedy_rectangles(&cells)
+            } else {
+                let mut cells: Vec<(u32, u32)> = cells.into_iter().collect();
+                cells.sort_by_key(|(u, v)| (*v, *u));
+                cells.into_iter().map(|(u, v)| (u, v, 1, 1)).collect()
+            };
+            let mut normal = from_plane(group.axis, if group.positive { 1. } else { -1. }, 0., 0.);
+            normal.z = -normal.z;
+            for (u, v, width, height) in rectangles {
+                let corner = |du: u32, dv: u32| {
+                    let position =
+                        from_plane(group.axis, group.plane as f32, (u + du) as f32, (v + dv) as f32);
+                    V3c::new(position.x, position.y, size as f32 - position.z)
+                };
+                let mut corners = [
+                    corner(0, 0),
+                    corner(width, 0),
+                    corner(width, height),
+                    corner(0, height),
+                ];
+                // Winding is decided after mirroring, so the front of the face points outwards
+                let face_normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+                if face_normal.dot(&normal) < 0. {
+                    corners.reverse();
+                }
+                quads.push(SurfaceQuad {
+                    corners,
+                    normal,
+                    data: group.data,
+                });
+            }
+        }
+        Ok(quads)
+    }
+
+    /// Exports the surface of the tree into a Wavefront OBJ file, with coplanar faces merged.
+    /// Materials are written into an .mtl file next to it, one for every distinct voxel color.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn export_obj(&self, path: &str, size: u32) -> Result<(), ContreeError> {
+        let quads = self.surface_quads(size, true)?;
+        let path = std::path::Path::new(path);
+        let material_path = path.with_extension("mtl");
+        let material_file_name = material_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("materials.mtl");
+
+        let mut materials: Vec<VoxelData> = quads.iter().map(|quad| quad.data).collect();
+        materials.sort_unstable();
+        materials.dedup();
+        let mut mtl = std::io::BufWriter::new(std::fs::File::create(&material_path)?);
+        for data in materials.iter() {
+            let color = Albedo::from(*data);
+            writeln!(mtl, "newmtl voxel_{data:08x}")?;
+            writeln!(
+                mtl,
+                "Kd {} {} {}",
+                color.r as f32 / 255.,
+                color.g as f32 / 255.,
+                color.b as f32 / 255.
+            )?;
+            writeln!(mtl, "d {}\n", color.a as f32 / 255.)?;
+        }
+        mtl.flush()?;
+
+        let mut obj = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(obj, "mtllib {material_file_name}")?;
+        for quad in quads.iter() {
+            for corner in quad.corners.iter() {
+                writeln!(obj, "v {} {} {}", corner.x, corner.y, corner.z)?;
+            }
+        }
+        let normals: Vec<V3cf32> = vec![
+            V3c::new(1., 0., 0.),
+            V3c::new(-1., 0., 0.),
+            V3c::new(0., 1., 0.),
+            V3c::new(0., -1., 0.),
+            V3c::new(0., 0., 1.),
+            V3c::new(0., 0., -1.),
+        ];
+        for normal in normals.iter() {
+            writeln!(obj, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+        let mut current_material = None;
+        for (index, quad) in quads.iter().enumerate() {
+            if current_material != Some(quad.data) {
+                writeln!(obj, "usemtl voxel_{:08x}", quad.data)?;
+                current_material = Some(quad.data);
+            }
+            let normal = normals
+                .iter()
+                .position(|normal| *normal == quad.normal)
+                .unwrap_or(0)
+                + 1;
+            let first = index * 4 + 1;
+            writeln!(
+                obj,
+                "f {}//{normal} {}//{normal} {}//{normal} {}//{normal}",
+                first,
+                first + 1,
+                first + 2,
+                first + 3
+            )?;
+        }
+        obj.flush()?;
+        Ok(())
+    }
+
+    /// Exports the surface of the tree into a binary glTF ( .glb ) file, with coplanar faces merged.
+    /// Vertices carry the color of their voxel in the COLOR_0 attribute.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn export_glb(&self, path: &str, size: u32) -> Result<(), ContreeError> {
+        let quads = self.surface_quads(size, true)?;
+        let vertex_count = quads.len() * 4;
+        let mut positions = Vec::with_capacity(vertex_count * 12);
+        let mut normals = Vec::with_capacity(vertex_count * 12);
+        let mut colors = Vec::with_capacity(vertex_count * 16);
+        let mut indices = Vec::with_capacity(quads.len() * 6 * 4);
+        let mut min = V3c::unit(f32::MAX);
+        let mut max = V3c::unit(f32::MIN);
+        for (index, quad) in quads.iter().enumerate() {
+            let color = Albedo::from(quad.data);
+            for corner in quad.corners.iter() {
+                for value in [corner.x, corner.y, corner.z] {
+                    positions.extend_from_slice(&value.to_le_bytes());
+                }
+                for value in [quad.normal.x, quad.normal.y, quad.normal.z] {
+                    normals.extend_from_slice(&value.to_le_bytes());
+                }
+                for channel in [color.r, color.g, color.b, color.a] {
+                    colors.extend_from_slice(&(channel as f32 / 255.).to_le_bytes());
+                }
+                min = V3c::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z));
+                max = V3c::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z));
+            }
+            let first = (index * 4) as u32;
+            for offset in [0, 1, 2, 0, 2, 3] {
+                indices.extend_from_slice(&(first + offset).to_le_bytes());
+            }
+        }
+
+        let mut binary = Vec::with_capacity(positions.len() + normals.len() + colors.len() + indices.len());
+        let views = [&positions, &normals, &colors, &indices].map(|data| {
+            let offset = binary.len();
+            binary.extend_from_slice(data);
+            (offset, data.len())
+        });
+        // Accessors must not be empty, so a tree without surface is exported as an empty scene
+        let json = if quads.is_empty() {
+            r#"{"asset":{"version":"2.0","generator":"voxelhex"},"scene":0,"scenes":[{"nodes":[]}]}"#.to_string()
+        } else {
+            format!(
+                concat!(
+                    r#"{{"asset":{{"version":"2.0","generator":"voxelhex"}},"scene":0,"scenes":[{{"nodes":[0]}}],"#,
+                    r#""nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"COLOR_0":2}},"indices":3,"mode":4}}]}}],"#,
+                    r#""buffers":[{{"byteLength":{}}}],"bufferViews":["#,
+                    r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}},"#,
+                    r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}},"#,
+                    r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34962}},"#,
+                    r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":34963}}],"accessors":["#,
+                    r#"{{"bufferView":0,"componentType":5126,"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}},"#,
+                    r#"{{"bufferView":1,"componentType":5126,"count":{},"type":"VEC3"}},"#,
+                    r#"{{"bufferView":2,"componentType":5126,"count":{},"type":"VEC4"}},"#,
+                    r#"{{"bufferView":3,"componentType":5125,"count":{},"type":"SCALAR"}}]}}"#
+                ),
+                binary.len(),
+                views[0].0,
+                views[0].1,
+                views[1].0,
+                views[1].1,
+                views[2].0,
+                views[2].1,
+                views[3].0,
+                views[3].1,
+                vertex_count,
+                min.x,
+                min.y,
+                min.z,
+                max.x,
+                max.y,
+                max.z,
+                vertex_count,
+                vertex_count,
+                quads.len() * 6,
+            )
+        };
+
+        // Chunks are padded to 4 bytes: JSON with spaces, binary data with zeros
+        let mut json = json.into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        binary.resize(binary.len().next_multiple_of(4), 0);
+        let binary_chunk_length = if binary.is_empty() { 0 } else { 8 + binary.len() };
+        let total_length = 12 + 8 + json.len() + binary_chunk_length;
+
+        let mut glb = std::io::BufWriter::new(std::fs::File::create(path)?);
+        glb.write_all(b"glTF")?;
+        glb.write_all(&2u32.to_le_bytes())?;
+        glb.write_all(&(total_length as u32).to_le_bytes())?;
+        glb.write_all(&(json.len() as u32).to_le_bytes())?;
+        glb.write_all(b"JSON")?;
+        glb.write_all(&json)?;
+        if !binary.is_empty() {
+            glb.write_all(&(binary.len() as u32).to_le_bytes())?;
+            glb.write_all(b"BIN\0")?;
+            glb.write_all(&binary)?;
+        }
+        glb.flush()?;
+        Ok(())
+    }
+
+    /// Exports the surface of the tree into a binary STL file. Faces are not merged,
+    /// so the mesh stays watertight without T-junctions, ready for 3D printing.
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn export_stl(&self, path: &str, size: u32) -> Result<(), ContreeError> {
+        let quads = self.surface_quads(size, false)?;
+        let mut stl = std::io::BufWriter::new(std::fs::File::create(path)?);
+        stl.write_all(&[0u8; 80])?;
+        stl.write_all(&((quads.len() * 2) as u32).to_le_bytes())?;
+        for quad in quads.iter() {
+            for triangle in [[0, 1, 2], [0, 2, 3]] {
+                for value in [quad.normal.x, quad.normal.y, quad.normal.z] {
+                    stl.write_all(&value.to_le_bytes())?;
+                }
+                for corner in triangle {
+                    let corner = quad.corners[corner];
+                    for value in [corner.x, corner.y, corner.z] {
+                        stl.write_all(&value.to_le_bytes())?;
+                    }
+                }
+                stl.write_all(&0u16.to_le_bytes())?;
+            }
+        }
+        stl.flush()?;
+        Ok(())
+    }
+}
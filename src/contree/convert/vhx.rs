@@ -0,0 +1,385 @@
+use crate::contree::{
+    convert::migration::VersionedLoader,
+    detail::LoadBudget,
+    types::{Albedo, Contree, ContreeError, ContreeNode, LoadLimits, VoxelData, AIR},
+};
+use std::{collections::HashMap, io::Write};
+
+/// Every .vhx file starts with these bytes
+pub(crate) const VHX_MAGIC: [u8; 4] = *b"VHX\0";
+
+/// The version of the .vhx format written by this library
+pub const VHX_VERSION: u32 = 1;
+
+const TREE_TAG_LEAF: u8 = 0;
+const TREE_TAG_NODE: u8 = 1;
+
+/// Bitwise CRC-32 ( IEEE ) lookup table
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if 0 != crc & 1 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Calculates the CRC-32 ( IEEE ) checksum of the given bytes
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Continues the CRC-32 checksum of some data with the bytes following it;
+/// `crc32_update(crc32(a), b)` equals the checksum of a and b together
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Passes written data through to the inner writer, keeping the checksum of everything written
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    crc: u32,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(bytes)?;
+        self.crc = crc32_update(self.crc, &bytes[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+//####################################################################################
+//  Writing
+//####################################################################################
+
+/// Encodes the value as a LEB128 varint into the buffer, returning the used part of it
+fn encode_varint(mut value: u32, buffer: &mut [u8; 5]) -> &[u8] {
+    let mut length = 0;
+    while value >= 0x80 {
+        buffer[length] = (value as u8 & 0x7F) | 0x80;
+        value >>= 7;
+        length += 1;
+    }
+    buffer[length] = value as u8;
+    &buffer[..=length]
+}
+
+fn write_varint(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(encode_varint(value, &mut [0; 5]));
+}
+
+fn write_section(bytes: &mut Vec<u8>, payload: &[u8]) {
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes.extend_from_slice(&crc32(payload).to_le_bytes());
+}
+
+/// Collects every distinct voxel data in the tree, in order of appearance
+fn collect_palette(contree: &Contree, palette: &mut Vec<VoxelData>, indices: &mut HashMap<VoxelData, u32>) {
+    match contree {
+        Contree::Leaf(data) => {
+            indices.entry(*data).or_insert_with(|| {
+                palette.push(*data);
+                (palette.len() - 1) as u32
+            });
+        }
+        Contree::Node(node) => {
+            for child in node.children.iter().flatten() {
+                collect_palette(child, palette, indices);
+            }
+        }
+    }
+}
+
+/// The number of bytes the record of the given node takes up, see [Contree::to_vhx_bytes]
+fn node_record_length(node: &ContreeNode, indices: &HashMap<VoxelData, u32>) -> usize {
+    let children: usize = node
+        .children
+        .iter()
+        .map(|child| match child {
+            Some(Contree::Leaf(data)) if *data != AIR => encode_varint(indices[data], &mut [0; 5]).len(),
+            Some(Contree::Node(child_node)) => node_record_length(child_node, indices),
+            _ => 0,
+        })
+        .sum();
+    8 + 8 + 4 + children
+}
+
+fn write_node<W: Write>(writer: &mut W, node: &ContreeNode, indices: &HashMap<VoxelData, u32>) -> std::io::Result<()> {
+    let mut occupancy = 0u64;
+    let mut node_mask = 0u64;
+    for (sectant, child) in node.children.iter().enumerate() {
+        match child {
+            Some(Contree::Leaf(data)) if *data != AIR => occupancy |= 1 << sectant,
+            Some(Contree::Node(_)) => {
+                occupancy |= 1 << sectant;
+                node_mask |= 1 << sectant;
+            }
+            _ => {}
+        }
+    }
+    let mut header = [0u8; 20];
+    header[0..8].copy_from_slice(&occupancy.to_le_bytes());
+    header[8..16].copy_from_slice(&node_mask.to_le_bytes());
+    header[16..20].copy_from_slice(&[node.mip.r, node.mip.g, node.mip.b, node.mip.a]);
+    writer.write_all(&header)?;
+    for child in node.children.iter() {
+        match child {
+            Some(Contree::Leaf(data)) if *data != AIR => writer.write_all(encode_varint(indices[data], &mut [0; 5]))?,
+            Some(Contree::Node(child_node)) => write_node(writer, child_node, indices)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+//####################################################################################
+//  Reading
+//####################################################################################
+
+/// Sequential reader over a byte slice, failing cleanly on truncated data
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(crate) fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], ContreeError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, ContreeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, ContreeError> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, ContreeError> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_varint(&mut self) -> Result<u32, ContreeError> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7F) as u32) << shift;
+            if 0 == byte & 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(ContreeError::InvalidStructure("Varint is longer, than 5 bytes".into()))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    /// Reads a section and verifies its checksum, returning its payload
+    fn read_section(&mut self, section: &'static str) -> Result<&'a [u8], ContreeError> {
+        let length = self.read_u32()? as usize;
+        let payload = self.read_bytes(length)?;
+        if crc32(payload) != self.read_u32()? {
+            return Err(ContreeError::ChecksumMismatch { section });
+        }
+        Ok(payload)
+    }
+}
+
+fn read_leaf(reader: &mut ByteReader, palette: &[VoxelData]) -> Result<Contree, ContreeError> {
+    let index = reader.read_varint()? as usize;
+    palette
+        .get(index)
+        .map(|data| Contree::Leaf(*data))
+        .ok_or_else(|| ContreeError::InvalidStructure("Leaf references a missing palette entry".into()))
+}
+
+fn read_node(
+    reader: &mut ByteReader,
+    palette: &[VoxelData],
+    budget: &mut LoadBudget,
+    depth: u32,
+) -> Result<Contree, ContreeError> {
+    budget.add_node(depth)?;
+    let occupancy = reader.read_u64()?;
+    let node_mask = reader.read_u64()?;
+    if node_mask & !occupancy != 0 {
+        return Err(ContreeError::InvalidStructure(
+            "Node mask marks children which are not present".into(),
+        ));
+    }
+    let mip = reader.read_bytes(4)?;
+    let mip = Albedo {
+        r: mip[0],
+        g: mip[1],
+        b: mip[2],
+        a: mip[3],
+    };
+    let mut children: [Option<Contree>; 64] = std::array::from_fn(|_| None);
+    for (sectant, child) in children.iter_mut().enumerate() {
+        let bit = 1u64 << sectant;
+        if 0 != node_mask & bit {
+            *child = Some(read_node(reader, palette, budget, depth + 1)?);
+        } else if 0 != occupancy & bit {
+            *child = Some(read_leaf(reader, palette)?);
+        }
+    }
+    Ok(Contree::Node(ContreeNode {
+        mip,
+        occupancy,
+        children: Box::new(children),
+    }))
+}
+
+impl Contree {
+    /// Encodes the contree into the .vhx binary format
+    /// Layout of a .vhx file, all numbers are little endian:
+    /// * magic: "VHX\0"
+    /// * version: u32
+    /// * palette section: [ length: u32 ][ count: u32, count x VoxelData: u32 ][ crc32: u32 ]
+    /// * tree section: [ length: u32 ][ root ][ crc32: u32 ]
+    ///
+    /// Inside the tree section, the root is a single tag byte ( 0: leaf, 1: node ) followed by either
+    /// the palette index of the leaf as a varint, or a node record. A node record is:
+    /// * occupancy: u64, with a bit set for every child which is present and not empty
+    /// * node mask: u64, with a bit set for every present child which is a node
+    /// * mip: the r, g, b, a bytes of the color of the node seen from afar
+    /// * the present children in ascending sectant order: either a node record or a varint palette index
+    pub fn to_vhx_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_vhx(&mut bytes).expect("Writing into memory can not fail");
+        bytes
+    }
+
+    /// Writes the contree in the .vhx format into the given writer, see [Contree::to_vhx_bytes].
+    /// Nodes are streamed into the writer, so the encoded tree is never stored in memory as a whole.
+    pub fn write_vhx<W: Write>(&self, mut writer: W) -> Result<(), ContreeError> {
+        let mut palette = Vec::new();
+        let mut indices = HashMap::new();
+        collect_palette(self, &mut palette, &mut indices);
+
+        let mut palette_section = Vec::with_capacity(4 + palette.len() * 4);
+        palette_section.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        for data in palette.iter() {
+            palette_section.extend_from_slice(&data.to_le_bytes());
+        }
+
+        let mut header = Vec::with_capacity(16 + palette_section.len());
+        header.extend_from_slice(&VHX_MAGIC);
+        header.extend_from_slice(&VHX_VERSION.to_le_bytes());
+        write_section(&mut header, &palette_section);
+        writer.write_all(&header)?;
+
+        // The length of the tree section precedes its content, so it is measured before writing
+        let tree_length = 1 + match self {
+            Contree::Leaf(data) => encode_varint(indices[data], &mut [0; 5]).len(),
+            Contree::Node(node) => node_record_length(node, &indices),
+        };
+        let tree_length = u32::try_from(tree_length)
+            .map_err(|_| ContreeError::InvalidStructure("Tree is too large for the .vhx format".into()))?;
+        writer.write_all(&tree_length.to_le_bytes())?;
+        let mut tree_writer = ChecksumWriter {
+            inner: &mut writer,
+            crc: 0,
+        };
+        match self {
+            Contree::Leaf(data) => {
+                tree_writer.write_all(&[TREE_TAG_LEAF])?;
+                tree_writer.write_all(encode_varint(indices[data], &mut [0; 5]))?;
+            }
+            Contree::Node(node) => {
+                tree_writer.write_all(&[TREE_TAG_NODE])?;
+                write_node(&mut tree_writer, node, &indices)?;
+            }
+        }
+        let crc = tree_writer.crc;
+        writer.write_all(&crc.to_le_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Decodes a contree from the .vhx binary format, migrating data written by older versions
+    /// and decompressing data saved with [Contree::save_vhx_compressed].
+    /// The default `LoadLimits` are enforced, see [VersionedLoader::with_limits] to configure them.
+    pub fn from_vhx_bytes(bytes: &[u8]) -> Result<Self, ContreeError> {
+        VersionedLoader::new().load(bytes)
+    }
+
+    /// Decodes a contree from .vhx data written by the current version of the format
+    pub(crate) fn decode_current_vhx(bytes: &[u8], limits: &LoadLimits) -> Result<Self, ContreeError> {
+        let mut budget = LoadBudget::new(limits);
+        let mut reader = ByteReader::new(bytes);
+        if reader.read_bytes(VHX_MAGIC.len())? != VHX_MAGIC {
+            return Err(ContreeError::InvalidHeader);
+        }
+        let version = reader.read_u32()?;
+        if version != VHX_VERSION {
+            return Err(ContreeError::UnsupportedVersion {
+                found: version,
+                supported: VHX_VERSION,
+            });
+        }
+
+        let mut palette_reader = ByteReader::new(reader.read_section("palette")?);
+        let palette_count = palette_reader.read_u32()? as usize;
+        budget.allocate(palette_count.saturating_mul(std::mem::size_of::<VoxelData>()))?;
+        let palette = palette_reader.read_bytes(palette_count.saturating_mul(4))?;
+        let palette: Vec<VoxelData> = palette
+            .chunks_exact(4)
+            .map(|data| u32::from_le_bytes(data.try_into().unwrap()))
+            .collect();
+
+        let mut tree_reader = ByteReader::new(reader.read_section("tree")?);
+        let tree = match tree_reader.read_u8()? {
+            TREE_TAG_LEAF => read_leaf(&mut tree_reader, &palette)?,
+            TREE_TAG_NODE => read_node(&mut tree_reader, &palette, &mut budget, 0)?,
+            _ => return Err(ContreeError::InvalidStructure("Unknown root tag".into())),
+        };
+        if !tree_reader.is_empty() {
+            return Err(ContreeError::InvalidStructure(
+                "Tree section contains trailing data".into(),
+            ));
+        }
+        Ok(tree)
+    }
+
+    /// Saves the contree into a .vhx file
+    pub fn save_vhx(&self, path: &str) -> Result<(), ContreeError> {
+        self.write_vhx(std::io::BufWriter::new(std::fs::File::create(path)?))
+    }
+
+    /// Loads a contree from a .vhx file, which may be compressed
+    pub fn load_vhx(path: &str) -> Result<Self, ContreeError> {
+        VersionedLoader::new().load_file(path)
+    }
+}
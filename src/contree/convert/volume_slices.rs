@@ -0,0 +1,240 @@
+use crate::{
+    contree::{
+        detail::{read_file_within_limits, size_within_limits, LoadBudget},
+        types::{Contree, ContreeError, LoadLimits, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+use std::path::{Path, PathBuf};
+
+/// The encoding of the slice files inside a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceFormat {
+    /// Portable graymap files ( .pgm ), either ASCII ( P2 ) or binary ( P5 )
+    Pgm,
+
+    /// Headerless grayscale samples ( .raw ), row by row
+    Raw {
+        width: u32,
+        height: u32,
+        /// 1 or 2 bytes for every sample
+        bytes_per_sample: u32,
+        little_endian: bool,
+    },
+}
+
+/// A single grayscale slice of a volume
+struct Slice {
+    width: u32,
+    height: u32,
+    densities: Vec<u16>,
+}
+
+fn invalid_slice(message: &str) -> ContreeError {
+    ContreeError::InvalidStructure(message.to_string().into())
+}
+
+/// Splits PGM header tokens, skipping comments starting with '#'
+fn pgm_header_tokens(bytes: &[u8], count: usize) -> Result<(Vec<u32>, usize), ContreeError> {
+    let mut tokens = Vec::with_capacity(count);
+    let mut position = 2; // after the magic number
+    while tokens.len() < count {
+        match bytes.get(position) {
+            None => return Err(ContreeError::UnexpectedEndOfData),
+            Some(b'#') => {
+                while bytes.get(position).is_some_and(|byte| b'\n' != *byte) {
+                    position += 1;
+                }
+            }
+            Some(byte) if byte.is_ascii_whitespace() => position += 1,
+            Some(_) => {
+                let start = position;
+                while bytes.get(position).is_some_and(|byte| byte.is_ascii_digit()) {
+                    position += 1;
+                }
+                let token = std::str::from_utf8(&bytes[start..position])
+                    .ok()
+                    .and_then(|token| token.parse().ok())
+                    .ok_or_else(|| invalid_slice("Invalid number in PGM header"))?;
+                tokens.push(token);
+            }
+        }
+    }
+    // A single whitespace separates the header from binary data
+    Ok((tokens, position + 1))
+}
+
+fn parse_pgm(bytes: &[u8]) -> Result<Slice, ContreeError> {
+    let binary = match bytes.get(0..2) {
+        Some(b"P5") => true,
+        Some(b"P2") => false,
+        _ => return Err(ContreeError::InvalidHeader),
+    };
+    let (header, data_start) = pgm_header_tokens(bytes, 3)?;
+    let (width, height, max_value) = (header[0], header[1], header[2]);
+    let sample_count = width as usize * height as usize;
+    let densities = if binary {
+        let sample_size = if max_value > 255 { 2 } else { 1 };
+        let data = bytes
+            .get(data_start..data_start.saturating_add(sample_count * sample_size))
+            .ok_or(ContreeError::UnexpectedEndOfData)?;
+        if 1 == sample_size {
+            data.iter().map(|sample| *sample as u16).collect()
+        } else {
+            data.chunks_exact(2)
+                .map(|sample| u16::from_be_bytes([sample[0], sample[1]]))
+                .collect()
+        }
+    } else {
+        let text = String::from_utf8_lossy(bytes.get(data_start.min(bytes.len())..).unwrap_or_default());
+        let densities = text
+            .split_ascii_whitespace()
+            .take(sample_count)
+            .map(|sample| sample.parse::<u16>().map_err(|_| invalid_slice("Invalid PGM sample")))
+            .collect::<Result<Vec<u16>, ContreeError>>()?;
+        if densities.len() < sample_count {
+            return Err(ContreeError::UnexpectedEndOfData);
+        }
+        densities
+    };
+    Ok(Slice {
+        width,
+        height,
+        densities,
+    })
+}
+
+fn parse_raw(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_sample: u32,
+    little_endian: bool,
+) -> Result<Slice, ContreeError> {
+    let sample_count = width as usize * height as usize;
+    let data = bytes
+        .get(..sample_count.saturating_mul(bytes_per_sample as usize))
+        .ok_or(ContreeError::UnexpectedEndOfData)?;
+    let densities = match bytes_per_sample {
+        1 => data.iter().map(|sample| *sample as u16).collect(),
+        2 => data
+            .chunks_exact(2)
+            .map(|sample| {
+                if little_endian {
+                    u16::from_le_bytes([sample[0], sample[1]])
+                } else {
+                    u16::from_be_bytes([sample[0], sample[1]])
+                }
+            })
+            .collect(),
+        _ => return Err(invalid_slice("Raw slices must have 1 or 2 bytes per sample")),
+    };
+    Ok(Slice {
+        width,
+        height,
+        densities,
+    })
+}
+
+fn read_slice(path: &Path, format: SliceFormat, limits: &LoadLimits) -> Result<Slice, ContreeError> {
+    let bytes = read_file_within_limits(path, limits)?;
+    match format {
+        SliceFormat::Pgm => parse_pgm(&bytes),
+        SliceFormat::Raw {
+            width,
+            height,
+            bytes_per_sample,
+            little_endian,
+        } => parse_raw(&bytes, width, height, bytes_per_sample, little_endian),
+    }
+}
+
+/// The number inside a file name, used to order slices numerically ( "slice_2" before "slice_10" )
+fn slice_number(path: &Path) -> Option<u64> {
+    let name = path.file_stem()?.to_str()?;
+    let digits: String = name
+        .chars()
+        .rev()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<Vec<char>>()
+        .into_iter()
+        .rev()
+        .collect();
+    digits.parse().ok()
+}
+
+impl Contree {
+    /// Loads a volume from a directory of numbered grayscale slices within the default `LoadLimits`,
+    /// see [Contree::load_image_stack_with_limits]
+    pub fn load_image_stack<F: Fn(u16) -> VoxelData>(
+        directory: &str,
+        format: SliceFormat,
+        transfer: F,
+    ) -> Result<(Self, u32), ContreeError> {
+        Self::load_image_stack_with_limits(directory, format, transfer, &LoadLimits::default())
+    }
+
+    /// Loads a volume from a directory of numbered grayscale slices, converting densities into
+    /// voxel data through the given transfer function. Slices are read and inserted one by one,
+    /// so the dense volume is never stored in memory as a whole.
+    /// Slices are stacked along the Y axis, image rows go along the Z axis.
+    /// Returns the tree and its edge length in voxels.
+    /// * `transfer` - converts a density into voxel data; `AIR` leaves the voxel empty
+    /// * `limits` - bounds the size of every slice file and the loaded tree
+    pub fn load_image_stack_with_limits<F: Fn(u16) -> VoxelData>(
+        directory: &str,
+        format: SliceFormat,
+        transfer: F,
+        limits: &LoadLimits,
+    ) -> Result<(Self, u32), ContreeError> {
+        let extension = match format {
+            SliceFormat::Pgm => "pgm",
+            SliceFormat::Raw { .. } => "raw",
+        };
+        let mut slices: Vec<PathBuf> = std::fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|path_extension| path_extension.eq_ignore_ascii_case(extension))
+            })
+            .collect();
+        slices.sort_by(|a, b| slice_number(a).cmp(&slice_number(b)).then_with(|| a.cmp(b)));
+
+        let Some(first_slice) = slices.first() else {
+            return Ok((Contree::new(), 1));
+        };
+        let first = read_slice(first_slice, format, limits)?;
+        let (width, height) = (first.width, first.height);
+        let extent = width
+            .max(height)
+            .max(u32::try_from(slices.len()).unwrap_or(u32::MAX));
+        let size = size_within_limits(extent, limits)?;
+
+        let mut tree = Contree::new();
+        let mut budget = LoadBudget::new(limits);
+        let mut insert_slice = |y: u32, slice: &Slice| -> Result<(), ContreeError> {
+            for row in 0..height {
+                for column in 0..width {
+                    let density = slice.densities[row as usize * width as usize + column as usize];
+                    let data = transfer(density);
+                    if AIR != data {
+                        tree.insert_within_budget(size, &V3c::new(column, y, row), data, &mut budget, 0)?;
+                    }
+                }
+            }
+            Ok(())
+        };
+        insert_slice(0, &first)?;
+        drop(first);
+        for (y, path) in slices.iter().enumerate().skip(1) {
+            let slice = read_slice(path, format, limits)?;
+            if slice.width != width || slice.height != height {
+                return Err(invalid_slice("Slices have different dimensions"));
+            }
+            insert_slice(y as u32, &slice)?;
+        }
+        Ok((tree, size))
+    }
+}
@@ -1,6 +1,15 @@
-use crate::{contree::types::Albedo};
+use crate::{
+    contree::types::{
+        Albedo, Contree, ContreeError, ContreeNode, LoadLimits, MaterialKind, MaterialProperties,
+        VoxelData, AIR,
+    },
+    spatial::math::vector::V3c,
+};
 use num_traits::Zero;
-use std::ops::{Add, Div};
+use std::{
+    io::Read,
+    ops::{Add, Div},
+};
 
 //####################################################################################
 //     █████████   █████       ███████████  ██████████ ██████████      ███████
@@ -101,3 +110,313 @@ impl Zero for Albedo {
     }
 }
 
+
+impl From<Albedo> for u32 {
+    fn from(color: Albedo) -> Self {
+        ((color.r as u32) << 24) | ((color.g as u32) << 16) | ((color.b as u32) << 8) | color.a as u32
+    }
+}
+
+impl Default for MaterialProperties {
+    fn default() -> Self {
+        Self {
+            kind: MaterialKind::Diffuse,
+            emission: 0.,
+            transparency: 0.,
+            roughness: 1.,
+            metalness: 0.,
+            refraction_index: 1.,
+        }
+    }
+}
+
+impl From<std::io::Error> for ContreeError {
+    fn from(error: std::io::Error) -> Self {
+        ContreeError::Io(error)
+    }
+}
+
+impl Default for LoadLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_TREE_DEPTH,
+            max_nodes: 1 << 24,
+            max_memory: 1 << 30,
+        }
+    }
+}
+
+//####################################################################################
+//  Loading
+//####################################################################################
+
+/// The deepest a tree can be, as its size ( 4^depth ) needs to fit into an u32
+pub(crate) const MAX_TREE_DEPTH: u32 = 15;
+
+/// Memory used by a single node, with its children array
+const NODE_MEMORY: usize = std::mem::size_of::<ContreeNode>() + std::mem::size_of::<[Option<Contree>; 64]>();
+
+/// Keeps track of the resources used while loading, failing when the limits are exceeded
+pub(crate) struct LoadBudget {
+    limits: LoadLimits,
+    nodes: usize,
+    memory: usize,
+}
+
+impl LoadBudget {
+    pub(crate) fn new(limits: &LoadLimits) -> Self {
+        Self {
+            limits: *limits,
+            nodes: 0,
+            memory: 0,
+        }
+    }
+
+    /// Accounts for a node at the given depth below the root
+    pub(crate) fn add_node(&mut self, depth: u32) -> Result<(), ContreeError> {
+        if depth >= self.limits.max_depth.min(MAX_TREE_DEPTH) {
+            return Err(ContreeError::LimitExceeded { limit: "depth" });
+        }
+        self.nodes += 1;
+        if self.nodes > self.limits.max_nodes {
+            return Err(ContreeError::LimitExceeded { limit: "node count" });
+        }
+        self.allocate(NODE_MEMORY)
+    }
+
+    /// Accounts for every node of an already built tree, with its root at the given depth
+    pub(crate) fn add_tree(&mut self, tree: &Contree, depth: u32) -> Result<(), ContreeError> {
+        if let Contree::Node(node) = tree {
+            self.add_node(depth)?;
+            for child in node.children.iter().flatten() {
+                self.add_tree(child, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Accounts for the given number of bytes allocated outside of nodes
+    pub(crate) fn allocate(&mut self, bytes: usize) -> Result<(), ContreeError> {
+        self.memory = self.memory.saturating_add(bytes);
+        if self.memory > self.limits.max_memory {
+            return Err(ContreeError::LimitExceeded { limit: "memory" });
+        }
+        Ok(())
+    }
+}
+
+/// The smallest contree size containing the given extent, within the depth allowed by the limits
+pub(crate) fn size_within_limits(extent: u32, limits: &LoadLimits) -> Result<u32, ContreeError> {
+    let mut size: u32 = 1;
+    let mut depth = 0;
+    while size < extent {
+        depth += 1;
+        if depth > limits.max_depth.min(MAX_TREE_DEPTH) {
+            return Err(ContreeError::LimitExceeded { limit: "depth" });
+        }
+        size *= CONTREE_NODE_DIMENSION;
+    }
+    Ok(size)
+}
+
+/// Reads the data remaining in the reader, failing if it is larger, than `max_size` bytes
+pub(crate) fn read_bounded<R: Read>(reader: R, max_size: usize) -> Result<Vec<u8>, ContreeError> {
+    let mut payload = Vec::new();
+    reader
+        .take((max_size as u64).saturating_add(1))
+        .read_to_end(&mut payload)
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => ContreeError::UnexpectedEndOfData,
+            _ => ContreeError::Io(error),
+        })?;
+    if payload.len() > max_size {
+        return Err(ContreeError::LimitExceeded { limit: "memory" });
+    }
+    Ok(payload)
+}
+
+/// Reads the whole file, failing if it is larger, than the memory allowed by the limits
+pub(crate) fn read_file_within_limits<P: AsRef<std::path::Path>>(
+    path: P,
+    limits: &LoadLimits,
+) -> Result<Vec<u8>, ContreeError> {
+    read_bounded(std::fs::File::open(path)?, limits.max_memory)
+}
+
+//####################################################################################
+//  Spatial helpers
+//####################################################################################
+
+/// The number of children along each axis of a contree node
+pub(crate) const CONTREE_NODE_DIMENSION: u32 = 4;
+
+/// The relative position of the given sectant inside its parent, in units of child size
+/// Sectants are indexed as x + (y * 4) + (z * 16)
+pub(crate) fn sectant_offset(sectant: usize) -> V3c<u32> {
+    V3c::new(
+        sectant as u32 % CONTREE_NODE_DIMENSION,
+        (sectant as u32 / CONTREE_NODE_DIMENSION) % CONTREE_NODE_DIMENSION,
+        sectant as u32 / (CONTREE_NODE_DIMENSION * CONTREE_NODE_DIMENSION),
+    )
+}
+
+/// The index of the sectant containing the given position, relative to its parent
+pub(crate) fn sectant_of(position: &V3c<u32>, child_size: u32) -> usize {
+    let index = V3c::new(
+        position.x / child_size,
+        position.y / child_size,
+        position.z / child_size,
+    );
+    (index.x + (index.y * CONTREE_NODE_DIMENSION) + (index.z * CONTREE_NODE_DIMENSION * CONTREE_NODE_DIMENSION))
+        as usize
+}
+
+/// True if the given size can be the edge length of a contree
+pub(crate) fn is_valid_size(size: u32) -> bool {
+    size.is_power_of_two() && size.trailing_zeros().is_multiple_of(2)
+}
+
+impl Contree {
+    /// Sets the voxel at the given position relative to this node, which covers size^3 voxels
+    pub(crate) fn insert_at(&mut self, size: u32, position: &V3c<u32>, data: VoxelData) {
+        if size <= 1 {
+            *self = Contree::Leaf(data);
+            return;
+        }
+        if let Contree::Leaf(current) = self {
+            if *current == data {
+                return;
+            }
+        }
+        self.subdivide();
+        let child_size = size / CONTREE_NODE_DIMENSION;
+        if let Contree::Node(node) = self {
+            node.children[sectant_of(position, child_size)]
+                .get_or_insert(Contree::Leaf(AIR))
+                .insert_at(child_size, &(*position % child_size), data);
+        }
+        self.recalculate_occupancy_bits();
+    }
+
+    /// Sets the voxel like [Contree::insert_at], charging the budget for every node created on the way,
+    /// so loaders fail before building a tree larger, than their limits allow
+    /// * `depth` - the depth of this node below the root of the tree
+    pub(crate) fn insert_within_budget(
+        &mut self,
+        size: u32,
+        position: &V3c<u32>,
+        data: VoxelData,
+        budget: &mut LoadBudget,
+        depth: u32,
+    ) -> Result<(), ContreeError> {
+        if size <= 1 {
+            *self = Contree::Leaf(data);
+            return Ok(());
+        }
+        if let Contree::Leaf(current) = self {
+            if *current == data {
+                return Ok(());
+            }
+            budget.add_node(depth)?;
+        }
+        self.subdivide();
+        let child_size = size / CONTREE_NODE_DIMENSION;
+        if let Contree::Node(node) = self {
+            node.children[sectant_of(position, child_size)]
+                .get_or_insert(Contree::Leaf(AIR))
+                .insert_within_budget(child_size, &(*position % child_size), data, budget, depth + 1)?;
+        }
+        self.recalculate_occupancy_bits();
+        Ok(())
+    }
+
+    /// Provides the voxel at the given position relative to this node, which covers size^3 voxels
+    pub(crate) fn get_at(&self, size: u32, position: &V3c<u32>) -> VoxelData {
+        match self {
+            Contree::Leaf(data) => *data,
+            Contree::Node(node) => {
+                let child_size = (size / CONTREE_NODE_DIMENSION).max(1);
+                match &node.children[sectant_of(position, child_size)] {
+                    Some(child) => child.get_at(child_size, &(*position % child_size)),
+                    None => AIR,
+                }
+            }
+        }
+    }
+
+    /// Calls the visitor with every leaf of the tree, alongside the position of its
+    /// bottom-left-near corner and its edge length. Missing children are not visited.
+    pub(crate) fn visit_leaves<F: FnMut(V3c<u32>, u32, VoxelData)>(
+        &self,
+        position: V3c<u32>,
+        size: u32,
+        visitor: &mut F,
+    ) {
+        match self {
+            Contree::Leaf(data) => visitor(position, size, *data),
+            Contree::Node(node) => {
+                let child_size = (size / CONTREE_NODE_DIMENSION).max(1);
+                for (sectant, child) in node.children.iter().enumerate() {
+                    if let Some(child) = child {
+                        child.visit_leaves(
+                            position + sectant_offset(sectant) * child_size,
+                            child_size,
+                            visitor,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls the visitor with the part of every leaf inside the box between `min` ( inclusive ) and
+    /// `max` ( exclusive ), as its first and past-the-last voxel, alongside the data of the leaf.
+    /// Missing children are visited as `AIR`.
+    pub(crate) fn visit_leaves_in_box<F: FnMut(V3c<u32>, V3c<u32>, VoxelData)>(
+        &self,
+        position: V3c<u32>,
+        size: u32,
+        min: &V3c<u32>,
+        max: &V3c<u32>,
+        visitor: &mut F,
+    ) {
+        let first = V3c::new(position.x.max(min.x), position.y.max(min.y), position.z.max(min.z));
+        let last = V3c::new(
+            (position.x + size).min(max.x),
+            (position.y + size).min(max.y),
+            (position.z + size).min(max.z),
+        );
+        if first.x >= last.x || first.y >= last.y || first.z >= last.z {
+            return;
+        }
+        match self {
+            Contree::Leaf(data) => visitor(first, last, *data),
+            Contree::Node(node) => {
+                let child_size = (size / CONTREE_NODE_DIMENSION).max(1);
+                for (sectant, child) in node.children.iter().enumerate() {
+                    let child_position = position + sectant_offset(sectant) * child_size;
+                    child
+                        .as_ref()
+                        .unwrap_or(&Contree::Leaf(AIR))
+                        .visit_leaves_in_box(child_position, child_size, min, max, visitor);
+                }
+            }
+        }
+    }
+
+    /// Calls the visitor with every voxel which is not empty, alongside its position
+    pub(crate) fn visit_voxels<F: FnMut(V3c<u32>, VoxelData)>(&self, size: u32, visitor: &mut F) {
+        self.visit_leaves(V3c::unit(0), size, &mut |position, leaf_size, data| {
+            if data == AIR {
+                return;
+            }
+            for z in 0..leaf_size {
+                for y in 0..leaf_size {
+                    for x in 0..leaf_size {
+                        visitor(position + V3c::new(x, y, z), data);
+                    }
+                }
+            }
+        });
+    }
+}
@@ -1,3 +1,60 @@
 pub mod types;
 mod detail;
 pub mod contree_gpu_serialization;
+pub mod contree_gpu_cache;
+pub mod convert;
+
+use crate::{
+    contree::{
+        detail::is_valid_size,
+        types::{Contree, ContreeError, VoxelData, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+impl Default for Contree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Contree {
+    /// Creates an empty tree
+    pub fn new() -> Self {
+        Contree::Leaf(AIR)
+    }
+
+    /// Sets the voxel at the given position inside a tree covering size^3 voxels
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn insert(
+        &mut self,
+        size: u32,
+        position: &V3c<u32>,
+        data: VoxelData,
+    ) -> Result<(), ContreeError> {
+        Self::check_bounds(size, position)?;
+        self.insert_at(size, position, data);
+        Ok(())
+    }
+
+    /// Provides the voxel at the given position inside a tree covering size^3 voxels
+    /// * `size` - The edge length of the tree, must be a power of 4
+    pub fn get(&self, size: u32, position: &V3c<u32>) -> Result<VoxelData, ContreeError> {
+        Self::check_bounds(size, position)?;
+        Ok(self.get_at(size, position))
+    }
+
+    pub(crate) fn check_bounds(size: u32, position: &V3c<u32>) -> Result<(), ContreeError> {
+        if !is_valid_size(size) {
+            return Err(ContreeError::InvalidSize(size));
+        }
+        if position.x >= size || position.y >= size || position.z >= size {
+            return Err(ContreeError::InvalidPosition {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::{error::Error, hash::Hash, u64};
+use std::{collections::HashMap, error::Error, hash::Hash, u64};
 
 #[cfg(feature = "serialization")]
 use serde::{Deserialize, Serialize};
@@ -11,6 +11,44 @@ pub enum ContreeError {
 
     /// Octree query was attempted with an invalid position
     InvalidPosition { x: u32, y: u32, z: u32 },
+
+    /// Octree size is expected to be a power of 4
+    InvalidSize(u32),
+
+    /// Reading or writing the underlying storage failed
+    Io(std::io::Error),
+
+    /// Loaded data does not start with the expected file header
+    InvalidHeader,
+
+    /// Loaded data was written by a newer version of the format, than supported
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    /// Loaded data ended before the stored structure was complete
+    UnexpectedEndOfData,
+
+    /// A section of the loaded data does not match its stored checksum
+    ChecksumMismatch { section: &'static str },
+
+    /// Loaded data would exceed one of the configured `LoadLimits`
+    LimitExceeded { limit: &'static str },
+
+    /// Loaded point or vertex at the given index has a coordinate which is NaN or infinite
+    NonFiniteCoordinate { index: usize },
+}
+
+/// Bounds enforced while loading data from untrusted sources
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadLimits {
+    /// Maximum number of node levels below the root
+    pub max_depth: u32,
+
+    /// Maximum number of nodes in the loaded tree, leaves not included
+    pub max_nodes: usize,
+
+    /// Maximum number of bytes allocated for the loaded tree, also bounding the size of
+    /// the source data after reading and decompressing it
+    pub max_memory: usize,
 }
 
 /// Color properties of a voxel
@@ -23,9 +61,47 @@ pub struct Albedo {
     pub a: u8,
 }
 
-pub(crate) type VoxelData = u32;
+pub type VoxelData = u32;
 pub const AIR: VoxelData = 0;
 
+/// The way light interacts with a material
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaterialKind {
+    #[default]
+    Diffuse,
+    Metal,
+    Glass,
+    Emissive,
+    Blend,
+    Media,
+}
+
+/// Surface properties shared by every voxel with the same `VoxelData`
+#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialProperties {
+    pub kind: MaterialKind,
+
+    /// Emitted light strength, 0.0 for materials not emitting light
+    pub emission: f32,
+
+    /// 0.0 for opaque, 1.0 for fully transparent materials
+    pub transparency: f32,
+
+    /// 0.0 for mirror-like, 1.0 for fully rough surfaces
+    pub roughness: f32,
+
+    /// 0.0 for dielectric, 1.0 for fully metallic surfaces
+    pub metalness: f32,
+
+    /// Index of refraction for transparent materials
+    pub refraction_index: f32,
+}
+
+/// Material properties for the `VoxelData` stored inside a contree
+pub type MaterialTable = HashMap<VoxelData, MaterialProperties>;
+
 /// Sparse 64Tree of Voxels. Branches indefinitely until reaching a homogenous Contree or air.
 #[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
@@ -39,13 +115,39 @@ pub enum Contree {
 pub struct ContreeNode {
     pub(crate) mip: Albedo,
     pub(crate) occupancy: u64,
+    #[cfg_attr(feature = "serialization", serde(with = "serde_children"))]
     pub(crate) children: Box<[Option<Contree>; 64]>,
 }
 
+/// Serde only supports arrays up to 32 elements, so children are stored as a sequence
+#[cfg(feature = "serialization")]
+mod serde_children {
+    use super::Contree;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        children: &[Option<Contree>; 64],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(children.iter())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Box<[Option<Contree>; 64]>, D::Error> {
+        let children = Vec::<Option<Contree>>::deserialize(deserializer)?;
+        let count = children.len();
+        children
+            .into_boxed_slice()
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(count, &"64 children"))
+    }
+}
+
 impl Contree {
     /// Subdivides the leaf into multiple identicial nodes. Does nothing if this is not a leaf.
     #[inline]
-    fn subdivide(&mut self) {
+    pub(crate) fn subdivide(&mut self) {
         let children: [Option<Contree>; 64] = match self {
             Contree::Leaf(_) => {
                 let mut children = [
@@ -79,39 +181,54 @@ impl Contree {
         todo!();
     }
 
+    /// Updates the occupancy bits and the mip of the node from its children,
+    /// collapsing it into a leaf when it is empty or filled with a single material.
+    /// The mip is the average color of the occupied children, taking the mip of child nodes.
     #[inline]
-    fn recalculate_occupancy_bits(&mut self) {
+    pub(crate) fn recalculate_occupancy_bits(&mut self) {
         let mut occupancy = 0;
         let mut homogeneous_material = Some(0);
+        let mut color_sum = [0u32; 4];
         match self {
             Contree::Node(node) => {
-                let mut bit = 1;
-                for node in node.children.iter() {
-                    match node {
+                for (sectant, node) in node.children.iter().enumerate() {
+                    let bit = 1u64 << sectant;
+                    let color = match node {
                         Some(Contree::Leaf(node)) => {
                             let node = *node;
                             if node == AIR {
                                 homogeneous_material = None;
-                                bit *= 2;
                                 continue;
                             } else if homogeneous_material == Some(0) {
                                 homogeneous_material = Some(node);
                             } else if homogeneous_material != Some(node) {
                                 homogeneous_material = None;
                             }
-                            occupancy &= bit;
+                            Albedo::from(node)
                         },
-                        Some(Contree::Node(_)) => {
-                            occupancy &= bit;
+                        Some(Contree::Node(node)) => {
                             homogeneous_material = None;
+                            node.mip
                         },
                         None => {
                             homogeneous_material = None;
+                            continue;
                         }
-                    }
-                    bit *= 2;
+                    };
+                    occupancy |= bit;
+                    color_sum[0] += color.r as u32;
+                    color_sum[1] += color.g as u32;
+                    color_sum[2] += color.b as u32;
+                    color_sum[3] += color.a as u32;
                 }
+                let count = occupancy.count_ones().max(1);
                 node.occupancy = occupancy;
+                node.mip = Albedo {
+                    r: (color_sum[0] / count) as u8,
+                    g: (color_sum[1] / count) as u8,
+                    b: (color_sum[2] / count) as u8,
+                    a: (color_sum[3] / count) as u8,
+                };
             },
             Contree::Leaf(_) => return,
         }
@@ -136,10 +253,11 @@ impl Contree {
         match self {
             Contree::Node(node) => {
                 node.children[i] = Some(Contree::Leaf(voxel));
+                self.recalculate_occupancy_bits();
             },
-            Contree::Leaf(_) => unreachable!(),
+            // Subdividing always results in a node
+            Contree::Leaf(_) => {},
         };
-        self.recalculate_occupancy_bits();
     }
 
     fn set_voxels(&mut self, voxels: [VoxelData; 64]) {
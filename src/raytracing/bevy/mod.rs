@@ -20,7 +20,7 @@ use bevy::{
         extract_component::ExtractComponentPlugin, extract_resource::ExtractResourcePlugin, render_asset::RenderAssetUsages, render_graph::RenderGraph, render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages}, Render, RenderApp, RenderSet
     },
 };
-use pipeline::write_to_gpu;
+use pipeline::{handle_gpu_readback, write_to_gpu};
 use types::{BoxTreeGPUView, RaymarchingViewSet};
 
 impl From<Vec4> for Albedo {
@@ -175,7 +175,7 @@ impl Plugin for RenderBevyPlugin
             (
                 write_to_gpu.in_set(RenderSet::PrepareResources),
                 prepare_bind_groups.in_set(RenderSet::PrepareBindGroups),
-                //handle_gpu_readback.in_set(RenderSet::Cleanup),
+                handle_gpu_readback.in_set(RenderSet::Cleanup),
             ),
         );
         let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
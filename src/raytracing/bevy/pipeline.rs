@@ -1,9 +1,16 @@
-use crate::
+use crate::{
+    contree::{
+        contree_gpu_cache::{GPU_FEEDBACK_FRAMES, GPU_NODE_REQUEST_LIMIT},
+        contree_gpu_serialization::{
+            create_contree_buffer, BakedContree, BakedContreeBuffers, GpuContreeData,
+            GpuContreeRanges, GpuNodeEncoding, GPU_BOUND_PAGES, GPU_NODE_SIZE, GPU_PAGE_NODES,
+        },
+    },
     raytracing::bevy::types::{
         RenderStageData,
-        RaymarchingRenderNode, RaymarchingRenderPipeline,
-    }
-;
+        RaymarchingRenderNode, RaymarchingRenderPipeline, Viewport,
+    },
+};
 use bevy::{prelude::*, render::{render_asset::RenderAssets, render_graph, render_resource::*, renderer::*, texture::GpuImage}};
 use bevy::render::render_resource::encase::StorageBuffer;
 use std::{borrow::Cow, ops::Range};
@@ -13,6 +20,15 @@ use super::types::{BoxTreeGPUView, ContreeRenderDataResources, RaymarchingViewSe
 const RENDER_STAGE_DEPTH_PREPASS: u32 = 0;
 const RENDER_STAGE_MAIN: u32 = 1;
 
+/// Bindings of the node, mip and node offset buffers of every bound page, in the order of the pages
+const NODE_PAGE_BINDINGS: [u32; GPU_BOUND_PAGES] = [3, 10, 11, 12];
+const MIP_PAGE_BINDINGS: [u32; GPU_BOUND_PAGES] = [4, 13, 14, 15];
+const NODE_OFFSET_PAGE_BINDINGS: [u32; GPU_BOUND_PAGES] = [6, 16, 17, 18];
+
+/// Storage buffers the render stage binds in the compute stage: the buffers of every bound page,
+/// the palette and the node request and usage feedback buffers
+const RENDER_STAGE_STORAGE_BUFFERS: u32 = 3 * GPU_BOUND_PAGES as u32 + 3;
+
 impl FromWorld for RaymarchingRenderPipeline {
     //##############################################################################
     // ███████████  █████ ██████   █████ ██████████
@@ -43,66 +59,130 @@ impl FromWorld for RaymarchingRenderPipeline {
 
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
-        let render_stage_bind_group_layout = render_device.create_bind_group_layout(
-            "RenderStageBindGroup",
-            &[
-                BindGroupLayoutEntry {
-                    binding: 0u32, // @group(0) @binding(0) var<uniform> stage_data: RenderStageData;
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: Some(<RenderStageData as ShaderType>::min_size()),
-                    },
-                    count: None,
+        let storage_buffer_limit = render_device.limits().max_storage_buffers_per_shader_stage;
+        if storage_buffer_limit < RENDER_STAGE_STORAGE_BUFFERS {
+            panic!(
+                "The render device supports {storage_buffer_limit} storage buffers per shader stage, \
+                 the contree render stage needs {RENDER_STAGE_STORAGE_BUFFERS}; \
+                 Request the limits of the adapter in `WgpuSettings` instead of the WebGPU defaults"
+            );
+        }
+        let mut layout_entries = vec![
+            BindGroupLayoutEntry {
+                binding: 0u32, // @group(0) @binding(0) var<uniform> stage_data: RenderStageData;
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(<RenderStageData as ShaderType>::min_size()),
                 },
-                BindGroupLayoutEntry { // @group(0) @binding(1) var output_texture: texture_storage_2d<rgba8unorm, read_write>;
-                    binding: 1u32,
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::StorageTexture {
-                        access: StorageTextureAccess::ReadWrite,
-                        format: TextureFormat::Rgba8Unorm,
-                        view_dimension: TextureViewDimension::D2,
-                    },
-                    count: None,
+                count: None,
+            },
+            BindGroupLayoutEntry { // @group(0) @binding(1) var output_texture: texture_storage_2d<rgba8unorm, read_write>;
+                binding: 1u32,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: TextureFormat::Rgba8Unorm,
+                    view_dimension: TextureViewDimension::D2,
                 },
-                BindGroupLayoutEntry { // @group(0) @binding(2) var depth_texture: texture_storage_2d<r32float, read_write>;
-                    binding: 2u32,
-                    visibility: ShaderStages::COMPUTE,
-                    ty: BindingType::StorageTexture {
-                        access: StorageTextureAccess::ReadWrite,
-                        format: TextureFormat::R32Float,
-                        view_dimension: TextureViewDimension::D2,
-                    },
-                    count: None,
+                count: None,
+            },
+            BindGroupLayoutEntry { // @group(0) @binding(2) var depth_texture: texture_storage_2d<r32float, read_write>;
+                binding: 2u32,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::StorageTexture {
+                    access: StorageTextureAccess::ReadWrite,
+                    format: TextureFormat::R32Float,
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            },
+            // @group(0) @binding(5) var<storage, read> palette: array<u32>;
+            storage_buffer_layout_entry(5, true),
+            // @group(0) @binding(7) var<storage, read_write> node_requests: array<atomic<u32>>;
+            storage_buffer_layout_entry(7, false),
+            // @group(0) @binding(8) var<storage, read_write> node_usage: array<atomic<u32>>;
+            storage_buffer_layout_entry(8, false),
+            BindGroupLayoutEntry {
+                binding: 9u32, // @group(0) @binding(9) var<uniform> viewport: Viewport;
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: Some(<Viewport as ShaderType>::min_size()),
                 },
-            ],
+                count: None,
+            },
+        ];
+        // @group(0) @binding(3, 10..=12) var<storage, read> node_page_N: array<u32>; and likewise
+        // mip_page_N at 4, 13..=15 and node_offset_page_N at 6, 16..=18. With the palette and the feedback buffers
+        // this takes `RENDER_STAGE_STORAGE_BUFFERS`, above the WebGPU defaults but within the adapter limits Bevy requests
+        layout_entries.extend(
+            NODE_PAGE_BINDINGS
+                .iter()
+                .chain(MIP_PAGE_BINDINGS.iter())
+                .chain(NODE_OFFSET_PAGE_BINDINGS.iter())
+                .map(|binding| storage_buffer_layout_entry(*binding, true)),
         );
+        let render_stage_bind_group_layout =
+            render_device.create_bind_group_layout("RenderStageBindGroup", &layout_entries);
         let shader = world
             .resource::<AssetServer>()
             .load("shaders/viewport_render.wgsl");
+        let create_empty_buffer = |label| {
+            render_device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: std::mem::size_of::<u32>() as u64,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            })
+        };
         let pipeline_cache = world.resource::<PipelineCache>();
-        let update_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
-            zero_initialize_workgroup_memory: false,
-            label: Some(std::borrow::Cow::Borrowed("MainRenderComputeBindGroup")),
-            layout: vec![
-                render_stage_bind_group_layout.clone(),
-            ],
-            push_constant_ranges: Vec::new(),
-            shader,
-            shader_defs: vec![],
-            entry_point: Cow::from("update"),
-        });
+        let queue_update_pipeline = |shader_defs: Vec<ShaderDefVal>| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                zero_initialize_workgroup_memory: false,
+                label: Some(std::borrow::Cow::Borrowed("MainRenderComputeBindGroup")),
+                layout: vec![
+                    render_stage_bind_group_layout.clone(),
+                ],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs,
+                entry_point: Cow::from("update"),
+            })
+        };
+        let update_pipeline = queue_update_pipeline(vec![]);
+        let compact_update_pipeline = queue_update_pipeline(vec!["COMPACT_NODES".into()]);
+        let streaming_update_pipeline = queue_update_pipeline(vec!["NODE_STREAMING".into()]);
 
         RaymarchingRenderPipeline {
             render_queue: world.resource::<RenderQueue>().clone(),
             update_tree: true,
             render_stage_bind_group_layout,
             update_pipeline,
+            compact_update_pipeline,
+            streaming_update_pipeline,
+            empty_buffer: create_empty_buffer("Unused contree buffer"),
+            empty_feedback_buffer: create_empty_buffer("Unused contree feedback buffer"),
         }
     }
 }
 
+/// Layout of a storage buffer of the contree in the render stage bind groups
+fn storage_buffer_layout_entry(binding: u32, read_only: bool) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::COMPUTE,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
 //##############################################################################
 //  ███████████   █████  █████ ██████   █████
 // ░░███░░░░░███ ░░███  ░░███ ░░██████ ░░███
@@ -134,13 +214,15 @@ impl render_graph::Node for RaymarchingRenderNode {
     ) -> Result<(), render_graph::NodeRunError> {
         if !self.ready { return Ok(()); }
 
-        let pipeline = world.resource::<RaymarchingRenderPipeline>();
         let vhx_view_set = world.resource::<RaymarchingViewSet>();
         //let current_view = vhx_view_set.views[0].lock().unwrap();
         let resolution = vhx_view_set.view.lock().unwrap().resolution;
         
         if let Some(resources) = &vhx_view_set.resources {
             let pipeline_cache = world.resource::<PipelineCache>();
+            let Some(update_pipeline) = pipeline_cache.get_compute_pipeline(resources.update_pipeline) else {
+                return Ok(());
+            };
             let command_encoder = render_context.command_encoder();
             
             {
@@ -148,10 +230,7 @@ impl render_graph::Node for RaymarchingRenderNode {
                     command_encoder.begin_compute_pass(&ComputePassDescriptor::default());
 
                 prepass.set_bind_group(0, &resources.render_stage_prepass_bind_group, &[]);
-                let pipeline = pipeline_cache
-                    .get_compute_pipeline(pipeline.update_pipeline)
-                    .unwrap();
-                prepass.set_pipeline(pipeline);
+                prepass.set_pipeline(update_pipeline);
                 prepass.dispatch_workgroups(
                     (resolution[0] / 2) / WORKGROUP_SIZE,
                     (resolution[1] / 2) / WORKGROUP_SIZE,
@@ -162,10 +241,7 @@ impl render_graph::Node for RaymarchingRenderNode {
             let mut main_pass =
                 command_encoder.begin_compute_pass(&ComputePassDescriptor::default());
             main_pass.set_bind_group(0, &resources.render_stage_main_bind_group, &[]);
-            let pipeline = pipeline_cache
-                .get_compute_pipeline(pipeline.update_pipeline)
-                .unwrap();
-            main_pass.set_pipeline(pipeline);
+            main_pass.set_pipeline(update_pipeline);
             main_pass.dispatch_workgroups(
                 resolution[0] / WORKGROUP_SIZE,
                 resolution[1] / WORKGROUP_SIZE,
@@ -195,18 +271,98 @@ impl render_graph::Node for RaymarchingRenderNode {
 //  ░░█████████  █████   █████ ░░░███████░   ░░████████   █████       ░░█████████
 //   ░░░░░░░░░  ░░░░░   ░░░░░    ░░░░░░░      ░░░░░░░░   ░░░░░         ░░░░░░░░░
 //##############################################################################
-///
+/// The size and the buffers of the given contree to be bound to the render stage bind groups
+fn bound_contree(baked_contree: &BakedContree) -> (u32, Vec<BufferId>) {
+    let buffers = baked_contree.buffers.lock().unwrap();
+    let mut buffer_ids = vec![buffers.palette.id()];
+    buffer_ids.extend(
+        buffers
+            .node_pages
+            .iter()
+            .chain(buffers.mip_pages.iter())
+            .chain(buffers.node_offset_pages.iter())
+            .map(Buffer::id),
+    );
+    buffer_ids.extend(
+        buffers
+            .node_pool
+            .iter()
+            .flat_map(|node_pool| [node_pool.requests.id(), node_pool.usage.id()]),
+    );
+    (baked_contree.host.lock().unwrap().size(), buffer_ids)
+}
+
+/// Encodes the viewport into the layout expected by the shader
+fn viewport_data(viewport: &Viewport) -> Vec<u8> {
+    let mut buffer = StorageBuffer::new(Vec::<u8>::new());
+    buffer.write(viewport).unwrap();
+    buffer.into_inner()
+}
+
+/// Creates the bind groups of the prepass and the main render stage, binding the first `GPU_BOUND_PAGES` pages
+/// of the given contree
 fn create_stage_bind_groups(
     gpu_images: &Res<RenderAssets<GpuImage>>,
     pipeline: &mut RaymarchingRenderPipeline,
     render_device: &Res<RenderDevice>,
     tree_view: &BoxTreeGPUView,
+    baked_contree: &BakedContree,
+    viewport_buffer: &Buffer,
 ) -> (BindGroup, BindGroup) {
+    let contree_size = baked_contree.host.lock().unwrap().size();
+    let contree_buffers = baked_contree.buffers.lock().unwrap();
+    let contree_pages = contree_buffers.mip_pages.len() as u32;
+    let contree_entries = || {
+        let mut entries = Vec::new();
+        for (bindings, pages) in [
+            (&NODE_PAGE_BINDINGS, &contree_buffers.node_pages),
+            (&MIP_PAGE_BINDINGS, &contree_buffers.mip_pages),
+            (&NODE_OFFSET_PAGE_BINDINGS, &contree_buffers.node_offset_pages),
+        ] {
+            // Pages the contree does not have are never read, the empty buffer takes their place
+            entries.extend(bindings.iter().enumerate().map(|(page_index, binding)| BindGroupEntry {
+                binding: *binding,
+                resource: pages
+                    .get(page_index)
+                    .unwrap_or(&pipeline.empty_buffer)
+                    .as_entire_binding(),
+            }));
+        }
+        entries.extend([
+            BindGroupEntry {
+                binding: 5,
+                resource: contree_buffers.palette.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 7,
+                resource: contree_buffers
+                    .node_pool
+                    .as_ref()
+                    .map_or(&pipeline.empty_feedback_buffer, |node_pool| &node_pool.requests)
+                    .as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 8,
+                resource: contree_buffers
+                    .node_pool
+                    .as_ref()
+                    .map_or(&pipeline.empty_feedback_buffer, |node_pool| &node_pool.usage)
+                    .as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 9,
+                resource: viewport_buffer.as_entire_binding(),
+            },
+        ]);
+        entries
+    };
     let mut buffer = StorageBuffer::new(Vec::<u8>::new());
     buffer
         .write(&RenderStageData {
             stage: RENDER_STAGE_DEPTH_PREPASS,
             output_resolution: UVec2::new(tree_view.resolution[0] / 2, tree_view.resolution[1] / 2),
+            contree_size,
+            contree_pages,
         })
         .unwrap();
     let prepass_data_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
@@ -220,6 +376,8 @@ fn create_stage_bind_groups(
         .write(&RenderStageData {
             stage: RENDER_STAGE_MAIN,
             output_resolution: UVec2::new(tree_view.resolution[0], tree_view.resolution[1]),
+            contree_size,
+            contree_pages,
         })
         .unwrap();
     let render_stage_data_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
@@ -233,57 +391,65 @@ fn create_stage_bind_groups(
             "Prepass stage bind group",
             &pipeline.render_stage_bind_group_layout,
             &[
-                bevy::render::render_resource::BindGroupEntry {
-                    binding: 0,
-                    resource: prepass_data_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(
-                        &gpu_images
-                            .get(&tree_view.spyglass.output_texture)
-                            .unwrap()
-                            .texture_view,
-                    ),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::TextureView(
-                        &gpu_images
-                            .get(&tree_view.spyglass.depth_texture)
-                            .unwrap()
-                            .texture_view,
-                    ),
-                },
-            ],
+                &[
+                    bevy::render::render_resource::BindGroupEntry {
+                        binding: 0,
+                        resource: prepass_data_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(
+                            &gpu_images
+                                .get(&tree_view.spyglass.output_texture)
+                                .unwrap()
+                                .texture_view,
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(
+                            &gpu_images
+                                .get(&tree_view.spyglass.depth_texture)
+                                .unwrap()
+                                .texture_view,
+                        ),
+                    },
+                ][..],
+                &contree_entries()[..],
+            ]
+            .concat(),
         ),
         render_device.create_bind_group(
             "Main Render stage main bind group",
             &pipeline.render_stage_bind_group_layout,
             &[
-                bevy::render::render_resource::BindGroupEntry {
-                    binding: 0,
-                    resource: render_stage_data_buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::TextureView(
-                        &gpu_images
-                            .get(&tree_view.spyglass.output_texture)
-                            .unwrap()
-                            .texture_view,
-                    ),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::TextureView(
-                        &gpu_images
-                            .get(&tree_view.spyglass.depth_texture)
-                            .unwrap()
-                            .texture_view,
-                    ),
-                },
-            ],
+                &[
+                    bevy::render::render_resource::BindGroupEntry {
+                        binding: 0,
+                        resource: render_stage_data_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(
+                            &gpu_images
+                                .get(&tree_view.spyglass.output_texture)
+                                .unwrap()
+                                .texture_view,
+                        ),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(
+                            &gpu_images
+                                .get(&tree_view.spyglass.depth_texture)
+                                .unwrap()
+                                .texture_view,
+                        ),
+                    },
+                ][..],
+                &contree_entries()[..],
+            ]
+            .concat(),
         ),
     )
 }
@@ -320,7 +486,12 @@ pub(crate) fn prepare_bind_groups(
     render_device: Res<RenderDevice>,
     mut pipeline: ResMut<RaymarchingRenderPipeline>,
     mut view_set: ResMut<RaymarchingViewSet>,
+    baked_contrees: Query<&BakedContree>,
 ) {
+    // The first baked contree is rendered, the bind groups can not be made without one
+    let Some(baked_contree) = baked_contrees.iter().next() else {
+        return;
+    };
 
     // Rebuild view for texture updates
     let can_rebuild = {
@@ -339,17 +510,14 @@ pub(crate) fn prepare_bind_groups(
     };
 
     if can_rebuild {
-        let (render_stage_prepass_bind_group, render_stage_main_bind_group) =
-            create_stage_bind_groups(
-                &gpu_images,
-                &mut pipeline,
-                &render_device,
-                &view_set.view.lock().unwrap(),
-            );
-
-        let view_resources = view_set.resources.as_mut().unwrap();
-        view_resources.render_stage_prepass_bind_group = render_stage_prepass_bind_group;
-        view_resources.render_stage_main_bind_group = render_stage_main_bind_group;
+        let view_resources = create_view_resources(
+            &mut pipeline,
+            render_device,
+            gpu_images,
+            &view_set.view.lock().unwrap(),
+            baked_contree,
+        );
+        view_set.resources = Some(view_resources);
 
         // Update view to clear temporary objects
         let mut view = view_set.view.lock().unwrap();
@@ -359,8 +527,11 @@ pub(crate) fn prepare_bind_groups(
         return;
     }
 
-    if let Some(_) = &view_set.resources {
-        return;
+    // Buffers are replaced when the contree outgrows them, so the bind groups need to follow
+    if let Some(resources) = &view_set.resources {
+        if resources.bound_contree == bound_contree(baked_contree) {
+            return;
+        }
     }
 
     let view_resources = create_view_resources(
@@ -368,6 +539,7 @@ pub(crate) fn prepare_bind_groups(
         render_device,
         gpu_images,
         &view_set.view.lock().unwrap(),
+        baked_contree,
     );
     view_set.resources = Some(view_resources);
 }
@@ -396,13 +568,36 @@ fn create_view_resources(
     render_device: Res<RenderDevice>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     tree_view: &BoxTreeGPUView,
+    baked_contree: &BakedContree,
 ) -> ContreeRenderDataResources {
-    let (render_stage_prepass_bind_group, render_stage_main_bind_group) =
-        create_stage_bind_groups(&gpu_images, pipeline, &render_device, tree_view);
+    let viewport_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("Vhx Viewport Buffer"),
+        contents: &viewport_data(&tree_view.spyglass.viewport),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let (render_stage_prepass_bind_group, render_stage_main_bind_group) = create_stage_bind_groups(
+        &gpu_images,
+        pipeline,
+        &render_device,
+        tree_view,
+        baked_contree,
+        &viewport_buffer,
+    );
+    let update_pipeline = if baked_contree.cache.is_some() {
+        pipeline.streaming_update_pipeline
+    } else {
+        match baked_contree.host.lock().unwrap().data().encoding {
+            GpuNodeEncoding::Full => pipeline.update_pipeline,
+            GpuNodeEncoding::Compact => pipeline.compact_update_pipeline,
+        }
+    };
 
     ContreeRenderDataResources {
         render_stage_prepass_bind_group,
         render_stage_main_bind_group,
+        update_pipeline,
+        bound_contree: bound_contree(baked_contree),
+        viewport_buffer,
     }
 }
 
@@ -456,37 +651,239 @@ fn write_range_to_buffer<U>(
     }
 }
 
-/// Handles Data Streaming to the GPU based on incoming requests from the view(s)
+/// Writes the changed ranges of the page into its buffer,
+/// or moves the whole page into a new buffer if it outgrew the current one
+fn write_page_to_buffer(
+    page: &[u32],
+    dirty_ranges: &[Range<usize>],
+    buffer: &mut Buffer,
+    capacity: usize,
+    label: &'static str,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) {
+    if std::mem::size_of_val(page) as u64 > buffer.size() {
+        *buffer = create_contree_buffer(render_device, label, page, capacity);
+        return;
+    }
+    for range in dirty_ranges {
+        write_range_to_buffer(page, range.clone(), buffer, render_queue);
+    }
+}
+
+/// Writes the changed ranges of every page into its buffer, adding buffers for new pages
+/// * `dirty_ranges` - Provides the changed ranges inside the page with the given index
+fn write_pages_to_buffers<'a>(
+    pages: impl Iterator<Item = &'a [u32]>,
+    dirty_ranges: impl Fn(usize) -> Vec<Range<usize>>,
+    buffers: &mut Vec<Buffer>,
+    capacity: usize,
+    label: &'static str,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) {
+    for (page_index, page) in pages.enumerate() {
+        match buffers.get_mut(page_index) {
+            Some(buffer) => write_page_to_buffer(
+                page,
+                &dirty_ranges(page_index),
+                buffer,
+                capacity,
+                label,
+                render_device,
+                render_queue,
+            ),
+            None => buffers.push(create_contree_buffer(render_device, label, page, capacity)),
+        }
+    }
+}
+
+/// The parts of the given ranges inside the page with the given index, relative to the start of the page
+fn ranges_inside_page(ranges: &[Range<usize>], page_index: usize, page_size: usize) -> Vec<Range<usize>> {
+    let page_start = page_index * page_size;
+    ranges
+        .iter()
+        .map(|range| range.start.max(page_start)..range.end.min(page_start + page_size))
+        .filter(|range| !range.is_empty())
+        .map(|range| range.start - page_start..range.end - page_start)
+        .collect()
+}
+
+/// Handles Data Streaming to the GPU: writes the viewport if it changed,
+/// and the entries of baked contrees which changed since the last frame
 pub(crate) fn write_to_gpu(
-    //tree_gpu_host: Option<ResMut<ContreeGPUHost<T>>>,
-    //vhx_pipeline: Option<ResMut<VhxRenderPipeline>>,
-    //vhx_view_set: ResMut<VhxViewSet>,
+    render_device: Res<RenderDevice>,
+    pipeline: Option<Res<RaymarchingRenderPipeline>>,
+    view_set: Option<Res<RaymarchingViewSet>>,
+    baked_contrees: Query<&BakedContree>,
 ) {
-    /*
-    // Write out the initial data package
-    write_range_to_buffer(
-        &view.data_handler.render_data.used_bits,
-        0..1,
-        &resources.used_bits_buffer,
-        &pipeline.render_queue,
-    );
-    write_range_to_buffer(
-        &view.data_handler.render_data.node_metadata,
-        0..1,
-        &resources.node_metadata_buffer,
-        &pipeline.render_queue,
-    );
-    write_range_to_buffer(
-        &view.data_handler.render_data.node_children,
-        0..BOX_NODE_CHILDREN_COUNT,
-        &resources.node_children_buffer,
-        &pipeline.render_queue,
-    );
-    write_range_to_buffer(
-        &view.data_handler.render_data.node_ocbits,
-        0..2,
-        &resources.node_ocbits_buffer,
-        &pipeline.render_queue,
+    let Some(pipeline) = pipeline else {
+        return;
+    };
+    if let Some(view_set) = view_set {
+        if let Some(resources) = &view_set.resources {
+            let mut view = view_set.view.lock().unwrap();
+            if view.spyglass.viewport_changed {
+                pipeline.render_queue.write_buffer(
+                    &resources.viewport_buffer,
+                    0,
+                    &viewport_data(&view.spyglass.viewport),
+                );
+                view.spyglass.viewport_changed = false;
+            }
+        }
+    }
+    for baked_contree in baked_contrees.iter() {
+        let mut host = baked_contree.host.lock().unwrap();
+        let mut buffers = baked_contree.buffers.lock().unwrap();
+        let dirty_ranges = host.take_dirty_ranges();
+        let data = host.data();
+        if let Some(cache) = &baked_contree.cache {
+            // Only the nodes loaded into the node pool are on the GPU
+            let mut cache = cache.lock().unwrap();
+            cache.update(data, &dirty_ranges);
+            let pool_ranges = cache.take_dirty_ranges();
+            write_page_to_buffer(
+                cache.nodes(),
+                &pool_ranges.nodes,
+                &mut buffers.node_pages[0],
+                cache.nodes().len(),
+                "Baked Contree node pool",
+                &render_device,
+                &pipeline.render_queue,
+            );
+            write_page_to_buffer(
+                cache.mips(),
+                &pool_ranges.mips,
+                &mut buffers.mip_pages[0],
+                cache.mips().len(),
+                "Baked Contree node pool mips",
+                &render_device,
+                &pipeline.render_queue,
+            );
+        } else {
+            write_baked_nodes_to_gpu(data, &dirty_ranges, &mut buffers, &render_device, &pipeline.render_queue);
+        }
+        write_page_to_buffer(
+            &data.palette,
+            &dirty_ranges.palette,
+            &mut buffers.palette,
+            usize::MAX,
+            "Baked Contree palette",
+            &render_device,
+            &pipeline.render_queue,
+        );
+    }
+}
+
+/// Writes the changed entries of the node and mip arrays of a baked contree into its buffers
+fn write_baked_nodes_to_gpu(
+    data: &GpuContreeData,
+    dirty_ranges: &GpuContreeRanges,
+    buffers: &mut BakedContreeBuffers,
+    render_device: &RenderDevice,
+    render_queue: &RenderQueue,
+) {
+    match data.encoding {
+        GpuNodeEncoding::Full => write_pages_to_buffers(
+            data.node_pages(),
+            |page_index| ranges_inside_page(&dirty_ranges.nodes, page_index, GPU_PAGE_NODES * GPU_NODE_SIZE),
+            &mut buffers.node_pages,
+            GPU_PAGE_NODES * GPU_NODE_SIZE,
+            "Baked Contree nodes",
+            render_device,
+            render_queue,
+        ),
+        GpuNodeEncoding::Compact => {
+            write_pages_to_buffers(
+                data.compact_pages.iter().map(|page| &page[..]),
+                |page_index| dirty_ranges.compact_pages.get(page_index).cloned().unwrap_or_default(),
+                &mut buffers.node_pages,
+                usize::MAX,
+                "Baked Contree nodes",
+                render_device,
+                render_queue,
+            );
+            write_pages_to_buffers(
+                data.node_offset_pages(),
+                |page_index| ranges_inside_page(&dirty_ranges.node_offsets, page_index, GPU_PAGE_NODES),
+                &mut buffers.node_offset_pages,
+                GPU_PAGE_NODES,
+                "Baked Contree node offsets",
+                render_device,
+                render_queue,
+            );
+        }
+    }
+    write_pages_to_buffers(
+        data.mip_pages(),
+        |page_index| ranges_inside_page(&dirty_ranges.mips, page_index, GPU_PAGE_NODES),
+        &mut buffers.mip_pages,
+        GPU_PAGE_NODES,
+        "Baked Contree mips",
+        render_device,
+        render_queue,
     );
-    */
-}
\ No newline at end of file
+}
+
+/// Handles Data Streaming from the GPU: copies the nodes missed by the GPU and the slots it used into a ring
+/// of readback buffers, then loads the nodes missed in earlier frames into the node pools once their copies
+/// are mapped, to be uploaded in the next frame. The CPU never waits for the GPU, reports are processed
+/// up to `GPU_FEEDBACK_FRAMES` frames late.
+pub(crate) fn handle_gpu_readback(
+    render_device: Res<RenderDevice>,
+    pipeline: Option<Res<RaymarchingRenderPipeline>>,
+    baked_contrees: Query<&BakedContree>,
+) {
+    let Some(pipeline) = pipeline else {
+        return;
+    };
+    for baked_contree in baked_contrees.iter() {
+        let Some(cache) = &baked_contree.cache else {
+            continue;
+        };
+        let mut buffers = baked_contree.buffers.lock().unwrap();
+        let Some(node_pool) = &mut buffers.node_pool else {
+            continue;
+        };
+
+        // Process the reports the GPU finished copying, starting from the oldest one
+        for offset in 0..GPU_FEEDBACK_FRAMES {
+            let readback_index = (node_pool.next_readback + offset) % GPU_FEEDBACK_FRAMES;
+            let Some((requests, usage)) = node_pool.readbacks[readback_index].take_mapped() else {
+                continue;
+            };
+            let request_count = (requests[0] as usize).min(GPU_NODE_REQUEST_LIMIT);
+            let host = baked_contree.host.lock().unwrap();
+            let mut cache = cache.lock().unwrap();
+            cache.register_usage(&usage);
+            cache.load_requested(host.data(), &requests[1..=request_count]);
+        }
+
+        // Copy the reports of the last frame, and clear them for the next one.
+        // While every readback is in use, the reports keep accumulating on the GPU
+        let readback = &mut node_pool.readbacks[node_pool.next_readback];
+        if readback.pending {
+            continue;
+        }
+        let mut command_encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Node pool readback"),
+        });
+        command_encoder.copy_buffer_to_buffer(
+            &node_pool.requests,
+            0,
+            &readback.requests,
+            0,
+            node_pool.requests.size(),
+        );
+        command_encoder.copy_buffer_to_buffer(&node_pool.usage, 0, &readback.usage, 0, node_pool.usage.size());
+        command_encoder.clear_buffer(&node_pool.requests, 0, None);
+        command_encoder.clear_buffer(&node_pool.usage, 0, None);
+        pipeline.render_queue.submit([command_encoder.finish()]);
+        readback.map(&render_device);
+        node_pool.next_readback = (node_pool.next_readback + 1) % GPU_FEEDBACK_FRAMES;
+    }
+
+    // Finished mappings are reported in the next frames
+    render_device.poll(Maintain::Poll);
+}
@@ -15,11 +15,12 @@ pub struct Viewport {
 
     /// The volume the viewport reaches to
     /// * `x` - looking glass width
-    /// * `y` - looking glass height
+    /// * `y` - looking glass height, the width of the glass follows the aspect ratio of the output resolution
     /// * `z` - the max depth of the viewport
     pub frustum: V3cf32,
 
     /// Field of View: how scattered will the rays in the viewport are
+    /// It is the distance of the looking glass from the origin, the closer it is, the wider the view
     pub fov: f32,
 }
 
@@ -43,6 +44,12 @@ pub struct BoxTreeSpyGlass {
 pub(crate) struct RenderStageData {
     pub(crate) stage: u32,
     pub(crate) output_resolution: UVec2,
+
+    /// The edge length of the rendered contree in voxels
+    pub(crate) contree_size: u32,
+
+    /// The number of node pages uploaded for the rendered contree, at most `GPU_BOUND_PAGES` of them are bound
+    pub(crate) contree_pages: u32,
 }
 
 #[derive(Resource)]
@@ -50,13 +57,32 @@ pub(crate) struct RaymarchingRenderPipeline {
     pub update_tree: bool,
     pub(crate) render_queue: RenderQueue,
     pub(crate) update_pipeline: CachedComputePipelineId,
+
+    /// Pipeline for contrees baked with `GpuNodeEncoding::Compact`
+    pub(crate) compact_update_pipeline: CachedComputePipelineId,
+
+    /// Pipeline for contrees streamed into a node pool
+    pub(crate) streaming_update_pipeline: CachedComputePipelineId,
     pub(crate) render_stage_bind_group_layout: BindGroupLayout,
+
+    /// Bound in place of the contree buffers not used by the baked contree
+    pub(crate) empty_buffer: Buffer,
+    pub(crate) empty_feedback_buffer: Buffer,
 }
 
 #[derive(Clone)]
 pub(crate) struct ContreeRenderDataResources {
     pub(crate) render_stage_prepass_bind_group: BindGroup,
-    pub(crate) render_stage_main_bind_group: BindGroup
+    pub(crate) render_stage_main_bind_group: BindGroup,
+
+    /// The pipeline matching the layout of the bound contree
+    pub(crate) update_pipeline: CachedComputePipelineId,
+
+    /// The size and the buffers of the bound contree, the bind groups need to be rebuilt if any of them change
+    pub(crate) bound_contree: (u32, Vec<BufferId>),
+
+    /// The viewport rays are cast from, written whenever `BoxTreeSpyGlass::viewport_changed` is set
+    pub(crate) viewport_buffer: Buffer,
 }
 
 #[derive(Resource, Clone)]
@@ -1,6 +1,6 @@
 use std::ops::{Add, AddAssign, Div, Mul, Rem, Sub, SubAssign};
 
-#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, PartialOrd)]
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Hash)]
 #[cfg_attr(
     feature = "serialization",
     derive(serde::Serialize, serde::Deserialize)
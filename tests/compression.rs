@@ -0,0 +1,158 @@
+use voxelhex::{
+    contree::{
+        convert::{
+            compression::{is_compressed, Compression},
+            migration::VersionedLoader,
+        },
+        types::{Contree, ContreeError, LoadLimits},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), 0xFF0000FF).unwrap();
+    tree.insert(SIZE, &V3c::new(1, 0, 0), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), 0xFF0000FF).unwrap();
+    for x in 0..SIZE {
+        tree.insert(SIZE, &V3c::new(x, 15, 15), 0x0000FFFF).unwrap();
+    }
+    tree
+}
+
+/// The codecs enabled in the current build
+fn compressions() -> Vec<Compression> {
+    let mut compressions = vec![Compression::None];
+    if cfg!(feature = "lz4_support") {
+        compressions.push(Compression::Lz4);
+    }
+    if cfg!(feature = "zstd_support") {
+        compressions.push(Compression::Zstd { level: 3 });
+    }
+    compressions
+}
+
+fn compressed(tree: &Contree, compression: Compression) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    tree.write_vhx_compressed(&mut bytes, compression).unwrap();
+    bytes
+}
+
+fn assert_same_content(expected: &Contree, loaded: &Contree) {
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let position = V3c::new(x, y, z);
+                assert_eq!(
+                    expected.get(SIZE, &position).unwrap(),
+                    loaded.get(SIZE, &position).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_streamed_vhx_matches_bytes() {
+    let tree = sample_tree();
+    let mut streamed = Vec::new();
+    tree.write_vhx(&mut streamed).unwrap();
+    assert_eq!(tree.to_vhx_bytes(), streamed);
+
+    let mut leaf = Vec::new();
+    Contree::Leaf(0xFF0000FF).write_vhx(&mut leaf).unwrap();
+    assert_eq!(Contree::Leaf(0xFF0000FF).to_vhx_bytes(), leaf);
+}
+
+#[test]
+fn test_compressed_round_trip() {
+    let tree = sample_tree();
+    for compression in compressions() {
+        let bytes = compressed(&tree, compression);
+        assert!(is_compressed(&bytes), "{compression:?}");
+        assert_same_content(&tree, &Contree::from_vhx_bytes(&bytes).unwrap());
+
+        let path = std::env::temp_dir().join(format!(
+            "voxelhex_{}_{compression:?}.vhx",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+        tree.save_vhx_compressed(path, compression).unwrap();
+        let loaded = Contree::load_vhx(path);
+        std::fs::remove_file(path).unwrap();
+        assert_same_content(&tree, &loaded.unwrap());
+    }
+}
+
+#[cfg(feature = "zstd_support")]
+#[test]
+fn test_zstd_compresses_repetitive_trees() {
+    let mut tree = Contree::new();
+    for x in 0..SIZE {
+        for z in 0..SIZE {
+            tree.insert(SIZE, &V3c::new(x, (x + z) % SIZE, z), 0x808080FF)
+                .unwrap();
+        }
+    }
+    let bytes = compressed(&tree, Compression::Zstd { level: 3 });
+    assert!(bytes.len() < tree.to_vhx_bytes().len());
+    assert_same_content(&tree, &Contree::from_vhx_bytes(&bytes).unwrap());
+}
+
+#[test]
+fn test_compressed_wrong_magic_and_codec() {
+    for compression in compressions() {
+        // A different magic is not recognized as compressed data, and is not valid .vhx either
+        let mut bytes = compressed(&sample_tree(), compression);
+        bytes[3] = b'X';
+        assert!(!is_compressed(&bytes));
+        assert!(matches!(
+            Contree::from_vhx_bytes(&bytes),
+            Err(ContreeError::InvalidHeader)
+        ));
+
+        let mut bytes = compressed(&sample_tree(), compression);
+        bytes[4] = 0xFF;
+        assert!(matches!(
+            Contree::from_vhx_bytes(&bytes),
+            Err(ContreeError::InvalidStructure(_))
+        ));
+    }
+}
+
+#[test]
+fn test_compressed_truncated_frame() {
+    for compression in compressions() {
+        let bytes = compressed(&sample_tree(), compression);
+        // LZ4 frames stay readable without their 4 byte end mark, so content is cut as well
+        for length in [bytes.len() / 2, bytes.len() - 8] {
+            assert!(
+                matches!(
+                    Contree::from_vhx_bytes(&bytes[..length]),
+                    Err(ContreeError::UnexpectedEndOfData)
+                ),
+                "{compression:?} data truncated to {length} bytes was accepted"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_compressed_over_max_memory() {
+    let tree = sample_tree();
+    let uncompressed_length = tree.to_vhx_bytes().len();
+    for compression in compressions() {
+        let bytes = compressed(&tree, compression);
+        let loader = VersionedLoader::new().with_limits(LoadLimits {
+            max_memory: uncompressed_length - 1,
+            ..LoadLimits::default()
+        });
+        assert!(matches!(
+            loader.load(&bytes),
+            Err(ContreeError::LimitExceeded { limit: "memory" })
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+use voxelhex::{
+    contree::types::{Contree, AIR},
+    spatial::math::vector::V3c,
+};
+
+#[test]
+fn test_insert_keeps_occupied_children() {
+    let mut tree = Contree::new();
+    tree.insert(16, &V3c::new(5, 9, 13), 0x00FF00FF).unwrap();
+    tree.insert(16, &V3c::new(15, 0, 0), 0x0000FFFF).unwrap();
+
+    assert!(matches!(tree, Contree::Node(_)));
+    assert_eq!(0x00FF00FF, tree.get(16, &V3c::new(5, 9, 13)).unwrap());
+    assert_eq!(0x0000FFFF, tree.get(16, &V3c::new(15, 0, 0)).unwrap());
+    assert_eq!(AIR, tree.get(16, &V3c::new(5, 9, 12)).unwrap());
+}
+
+#[test]
+fn test_insert_collapses_homogeneous_nodes() {
+    let mut tree = Contree::new();
+    for x in 0..4 {
+        for y in 0..4 {
+            for z in 0..4 {
+                tree.insert(4, &V3c::new(x, y, z), 0x00FF00FF).unwrap();
+            }
+        }
+    }
+    assert_eq!(Contree::Leaf(0x00FF00FF), tree);
+}
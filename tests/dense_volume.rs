@@ -0,0 +1,239 @@
+use voxelhex::{
+    contree::{
+        convert::dense_volume::{AxisOrder, RawSampleType},
+        types::{Contree, ContreeError, LoadLimits, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("voxelhex_{}_{name}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), RED).unwrap();
+    tree.insert(SIZE, &V3c::new(1, 0, 0), GREEN).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), RED).unwrap();
+    tree.insert(SIZE, &V3c::new(15, 15, 15), GREEN).unwrap();
+    tree
+}
+
+/// Checks that every voxel of the expected tree is set in the loaded one, with data converted by `map`
+fn assert_same_shape(expected: &Contree, loaded: &Contree, map: impl Fn(u32) -> u32) {
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let position = V3c::new(x, y, z);
+                assert_eq!(
+                    map(expected.get(SIZE, &position).unwrap()),
+                    loaded.get(SIZE, &position).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_binvox_round_trip() {
+    let tree = sample_tree();
+    for order in [
+        AxisOrder::BINVOX,
+        AxisOrder::LIBRARY,
+        AxisOrder::RIGHT_HANDED_Z_UP,
+    ] {
+        let bytes = tree.to_binvox_bytes(SIZE, &order).unwrap();
+        let (loaded, size) = Contree::from_binvox_bytes(&bytes, &order, RED).unwrap();
+        assert_eq!(SIZE, size);
+        assert_same_shape(&tree, &loaded, |data| if AIR == data { AIR } else { RED });
+    }
+}
+
+#[test]
+fn test_dense_export_of_collapsed_tree() {
+    // A tree filled entirely is a single leaf, every voxel up to the given size is exported
+    let tree = Contree::Leaf(RED);
+    let bytes = tree.to_binvox_bytes(SIZE, &AxisOrder::BINVOX).unwrap();
+    let (loaded, size) = Contree::from_binvox_bytes(&bytes, &AxisOrder::BINVOX, RED).unwrap();
+    assert_eq!(SIZE, size);
+    assert_same_shape(&tree, &loaded, |data| data);
+
+    let path = temp_path("collapsed.raw");
+    tree.export_raw_volume(
+        &path,
+        SIZE,
+        RawSampleType::U32,
+        &AxisOrder::LIBRARY,
+        |data| data,
+    )
+    .unwrap();
+    assert_eq!(
+        4 * (SIZE * SIZE * SIZE) as u64,
+        std::fs::metadata(&path).unwrap().len()
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(
+        tree.to_binvox_bytes(10, &AxisOrder::BINVOX),
+        Err(ContreeError::InvalidSize(10))
+    ));
+}
+
+#[test]
+fn test_binvox_layout() {
+    // 2 x 1 x 1 voxels: "dim depth height width", only the second voxel along X is filled
+    let bytes = b"#binvox 1\ndim 2 1 1\ntranslate 0 0 0\nscale 1\ndata\n\x00\x01\x01\x01";
+    let (tree, size) = Contree::from_binvox_bytes(bytes, &AxisOrder::BINVOX, GREEN).unwrap();
+    assert_eq!(4, size);
+    assert_eq!(AIR, tree.get(size, &V3c::new(0, 0, 0)).unwrap());
+    assert_eq!(GREEN, tree.get(size, &V3c::new(1, 0, 0)).unwrap());
+}
+
+#[test]
+fn test_binvox_rejects_invalid_data() {
+    let bytes = sample_tree()
+        .to_binvox_bytes(SIZE, &AxisOrder::BINVOX)
+        .unwrap();
+    assert!(matches!(
+        Contree::from_binvox_bytes(&bytes[..bytes.len() - 2], &AxisOrder::BINVOX, RED),
+        Err(ContreeError::UnexpectedEndOfData)
+    ));
+    assert!(matches!(
+        Contree::from_binvox_bytes(b"#binvox 2\ndata\n", &AxisOrder::BINVOX, RED),
+        Err(ContreeError::InvalidHeader)
+    ));
+    assert!(matches!(
+        Contree::from_binvox_bytes(b"#binvox 1\ndim 0 4 4\ndata\n", &AxisOrder::BINVOX, RED),
+        Err(ContreeError::InvalidHeader)
+    ));
+    let duplicate_axes = AxisOrder {
+        axes: [0, 0, 1],
+        mirrored: [false; 3],
+    };
+    assert!(matches!(
+        Contree::from_binvox_bytes(&bytes, &duplicate_axes, RED),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+}
+
+#[test]
+fn test_binvox_limits() {
+    // A few bytes describing a volume too large for any tree
+    let huge = b"#binvox 1\ndim 4000000000 1 1\ndata\n\x00\xFF";
+    assert!(matches!(
+        Contree::from_binvox_bytes(huge, &AxisOrder::BINVOX, RED),
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+
+    let bytes = sample_tree()
+        .to_binvox_bytes(SIZE, &AxisOrder::BINVOX)
+        .unwrap();
+    let shallow = LoadLimits {
+        max_depth: 1,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Contree::from_binvox_bytes_with_limits(&bytes, &AxisOrder::BINVOX, RED, &shallow),
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+    let few_nodes = LoadLimits {
+        max_nodes: 2,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Contree::from_binvox_bytes_with_limits(&bytes, &AxisOrder::BINVOX, RED, &few_nodes),
+        Err(ContreeError::LimitExceeded {
+            limit: "node count"
+        })
+    ));
+}
+
+#[test]
+fn test_raw_volume_limits() {
+    let path = temp_path("limited.raw");
+    sample_tree()
+        .export_raw_volume(
+            &path,
+            SIZE,
+            RawSampleType::U8,
+            &AxisOrder::LIBRARY,
+            |data| (AIR != data) as u32,
+        )
+        .unwrap();
+    let limits = LoadLimits {
+        max_memory: (SIZE * SIZE * SIZE) as usize - 1,
+        ..LoadLimits::default()
+    };
+    let result = Contree::load_raw_volume_with_limits(
+        &path,
+        [SIZE; 3],
+        RawSampleType::U8,
+        &AxisOrder::LIBRARY,
+        |sample| sample,
+        &limits,
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        result,
+        Err(ContreeError::LimitExceeded { limit: "memory" })
+    ));
+}
+
+#[test]
+fn test_raw_volume_round_trip() {
+    let tree = sample_tree();
+    let path = temp_path("volume.raw");
+    for order in [
+        AxisOrder::LIBRARY,
+        AxisOrder::RIGHT_HANDED_Y_UP,
+        AxisOrder::BINVOX,
+    ] {
+        tree.export_raw_volume(&path, SIZE, RawSampleType::U32, &order, |data| data)
+            .unwrap();
+        assert_eq!(
+            4 * (SIZE * SIZE * SIZE) as u64,
+            std::fs::metadata(&path).unwrap().len()
+        );
+        let (loaded, size) =
+            Contree::load_raw_volume(&path, [SIZE; 3], RawSampleType::U32, &order, |sample| {
+                sample
+            })
+            .unwrap();
+        assert_eq!(SIZE, size);
+        assert_same_shape(&tree, &loaded, |data| data);
+    }
+
+    // Bytes only keep which voxels are set
+    let to_byte = |data: u32| (AIR != data) as u32;
+    tree.export_raw_volume(&path, SIZE, RawSampleType::U8, &AxisOrder::LIBRARY, to_byte)
+        .unwrap();
+    let (loaded, _) = Contree::load_raw_volume(
+        &path,
+        [SIZE; 3],
+        RawSampleType::U8,
+        &AxisOrder::LIBRARY,
+        |sample| if 0 == sample { AIR } else { GREEN },
+    )
+    .unwrap();
+    assert_same_shape(&tree, &loaded, |data| if AIR == data { AIR } else { GREEN });
+
+    // The file is smaller than the given dimensions
+    let result = Contree::load_raw_volume(
+        &path,
+        [SIZE, SIZE, SIZE + 1],
+        RawSampleType::U8,
+        &AxisOrder::LIBRARY,
+        |sample| sample,
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(ContreeError::UnexpectedEndOfData)));
+}
@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use voxelhex::{
+    contree::{
+        convert::{migration::VersionedLoader, vhx::VHX_VERSION},
+        types::{Contree, ContreeError, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+/// Frozen files written by each version of the .vhx format, all containing the same voxels
+const TREE_V1: &[u8] = include_bytes!("fixtures/tree_v1.vhx");
+
+/// Frozen dump of the same voxels, written through serde as JSON
+#[cfg(feature = "serialization")]
+const TREE_SERDE_JSON: &[u8] = include_bytes!("fixtures/tree_serde.json");
+
+const FIXTURE_SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+fn assert_fixture_content(tree: &Contree) {
+    for x in 0..FIXTURE_SIZE {
+        for y in 0..FIXTURE_SIZE {
+            for z in 0..FIXTURE_SIZE {
+                let expected = match (x, y, z) {
+                    (0, 0, 0) | (5, 9, 13) => RED,
+                    (1, 0, 0) => GREEN,
+                    _ => AIR,
+                };
+                assert_eq!(
+                    expected,
+                    tree.get(FIXTURE_SIZE, &V3c::new(x, y, z)).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+fn with_version(bytes: &[u8], version: u32) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    bytes[4..8].copy_from_slice(&version.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn test_load_every_fixture_version() {
+    assert_fixture_content(&Contree::from_vhx_bytes(TREE_V1).unwrap());
+}
+
+#[test]
+fn test_migrate_to_current() {
+    // Version 0 only exists in this test, its step upgrades the data to the first version
+    let loader = VersionedLoader::new().with_migration(0, |bytes| Ok(with_version(bytes, 1)));
+    let version_0 = with_version(TREE_V1, 0);
+    let migrated = loader.migrate(&version_0).unwrap();
+    assert_eq!(Some(VHX_VERSION), VersionedLoader::vhx_version(&migrated));
+    assert_eq!(TREE_V1, &migrated[..]);
+    assert!(matches!(loader.migrate(TREE_V1).unwrap(), Cow::Borrowed(_)));
+}
+
+#[test]
+fn test_current_version_is_not_migrated() {
+    let saved = Contree::from_vhx_bytes(TREE_V1).unwrap().to_vhx_bytes();
+    assert_eq!(Some(VHX_VERSION), VersionedLoader::vhx_version(&saved));
+    assert_fixture_content(&Contree::from_vhx_bytes(&saved).unwrap());
+}
+
+#[test]
+fn test_newer_version_is_rejected() {
+    let result = Contree::from_vhx_bytes(&with_version(TREE_V1, VHX_VERSION + 1));
+    assert!(matches!(
+        result,
+        Err(ContreeError::UnsupportedVersion { found, supported })
+            if found == VHX_VERSION + 1 && supported == VHX_VERSION
+    ));
+}
+
+#[test]
+fn test_missing_migration_step_is_rejected() {
+    let result = Contree::from_vhx_bytes(&with_version(TREE_V1, 0));
+    assert!(matches!(
+        result,
+        Err(ContreeError::UnsupportedVersion { found: 0, .. })
+    ));
+}
+
+#[test]
+fn test_registered_migration_step() {
+    let loader = VersionedLoader::new().with_migration(0, |bytes| Ok(with_version(bytes, 1)));
+    assert_fixture_content(&loader.load(&with_version(TREE_V1, 0)).unwrap());
+}
+
+#[test]
+fn test_migration_step_must_upgrade_version() {
+    let loader = VersionedLoader::new().with_migration(0, |bytes| Ok(bytes.to_vec()));
+    assert!(matches!(
+        loader.load(&with_version(TREE_V1, 0)),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+}
+
+#[test]
+fn test_registered_legacy_encoding() {
+    let loader = VersionedLoader::new().with_legacy_encoding(
+        |bytes| bytes.starts_with(b"LEGACY"),
+        |_| {
+            let mut tree = Contree::new();
+            tree.insert(FIXTURE_SIZE, &V3c::new(0, 0, 0), RED)?;
+            tree.insert(FIXTURE_SIZE, &V3c::new(1, 0, 0), GREEN)?;
+            tree.insert(FIXTURE_SIZE, &V3c::new(5, 9, 13), RED)?;
+            Ok(tree)
+        },
+    );
+    assert_fixture_content(&loader.load(b"LEGACY").unwrap());
+    assert!(matches!(
+        loader.load(b"UNKNOWN"),
+        Err(ContreeError::InvalidHeader)
+    ));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn test_load_serde_json_fixture() {
+    let loader = VersionedLoader::new();
+    assert_fixture_content(&loader.load(TREE_SERDE_JSON).unwrap());
+
+    let mut indented = b"  \n{ ".to_vec();
+    indented.extend_from_slice(&TREE_SERDE_JSON[1..]);
+    assert_fixture_content(&loader.load(&indented).unwrap());
+
+    // Serde dumps are checked against the limits of the loader too
+    let shallow = VersionedLoader::new().with_limits(voxelhex::contree::types::LoadLimits {
+        max_depth: 1,
+        ..Default::default()
+    });
+    assert!(matches!(
+        shallow.load(TREE_SERDE_JSON),
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+    assert!(matches!(
+        loader.load(&TREE_SERDE_JSON[..TREE_SERDE_JSON.len() / 2]),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+}
+
+#[cfg(all(feature = "serialization", feature = "bytecode"))]
+#[test]
+fn test_load_serde_bencode() {
+    let tree = Contree::from_vhx_bytes(TREE_V1).unwrap();
+    let bytes = bendy::serde::to_bytes(&tree).unwrap();
+    assert_fixture_content(&VersionedLoader::new().load(&bytes).unwrap());
+}
@@ -0,0 +1,138 @@
+use voxelhex::{
+    contree::{
+        contree_gpu_cache::{ContreeGPUCache, GPU_NODE_MISSING},
+        contree_gpu_serialization::{
+            ContreeGPUHost, GpuNodeEncoding, GPU_NODE_FLAG, GPU_NODE_SIZE,
+        },
+        types::{Contree, ContreeError, MaterialTable},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+
+/// Sectants of the root children containing nodes 1, 2 and 3
+const SECTANTS: [usize; 3] = [0, 57, 63];
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(1, 0, 0), 0x0000FFFF).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(15, 15, 15), 0x7FFFFFFF)
+        .unwrap();
+    tree
+}
+
+fn slot_entries(cache: &ContreeGPUCache, slot: usize) -> &[u32] {
+    &cache.nodes()[slot * GPU_NODE_SIZE..(slot + 1) * GPU_NODE_SIZE]
+}
+
+#[test]
+fn test_gpu_cache_loads_requested_nodes() {
+    let data = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let mut cache = ContreeGPUCache::new(&data, 4).unwrap();
+    for sectant in SECTANTS {
+        assert_eq!(GPU_NODE_MISSING, slot_entries(&cache, 0)[2 + sectant]);
+    }
+
+    // Requests for leaves and loaded nodes are ignored
+    assert_eq!(
+        1,
+        cache.load_requested(&data, &[SECTANTS[1] as u32, 1, SECTANTS[1] as u32])
+    );
+    let slot = cache.slot_of(2).unwrap();
+    assert_eq!(
+        slot as u32 | GPU_NODE_FLAG,
+        slot_entries(&cache, 0)[2 + SECTANTS[1]]
+    );
+    assert_eq!(
+        &data.nodes[2 * GPU_NODE_SIZE..3 * GPU_NODE_SIZE],
+        slot_entries(&cache, slot)
+    );
+    assert_eq!(data.mips[2], cache.mips()[slot]);
+}
+
+#[test]
+fn test_gpu_cache_evicts_least_recently_used() {
+    let data = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let mut cache = ContreeGPUCache::new(&data, 3).unwrap();
+    cache.load_requested(&data, &[SECTANTS[0] as u32, SECTANTS[1] as u32]);
+    let first_slot = cache.slot_of(1).unwrap();
+    let second_slot = cache.slot_of(2).unwrap();
+
+    // Nodes used in the last frame are kept
+    cache.register_usage(&[0b111]);
+    assert_eq!(0, cache.load_requested(&data, &[SECTANTS[2] as u32]));
+
+    // Otherwise the node used least recently is replaced
+    cache.register_usage(&[1 | (1 << second_slot)]);
+    cache.register_usage(&[1]);
+    assert_eq!(1, cache.load_requested(&data, &[SECTANTS[2] as u32]));
+    assert_eq!(None, cache.slot_of(1));
+    assert_eq!(Some(first_slot), cache.slot_of(3));
+    assert_eq!(GPU_NODE_MISSING, slot_entries(&cache, 0)[2 + SECTANTS[0]]);
+    assert_eq!(
+        first_slot as u32 | GPU_NODE_FLAG,
+        slot_entries(&cache, 0)[2 + SECTANTS[2]]
+    );
+}
+
+#[test]
+fn test_gpu_cache_keeps_nodes_within_feedback_latency() {
+    let data = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let mut cache = ContreeGPUCache::new(&data, 2).unwrap();
+    cache.set_feedback_latency(2);
+    cache.load_requested(&data, &[SECTANTS[0] as u32]);
+
+    // Reports made before the GPU received the loaded node do not mention it
+    cache.register_usage(&[1]);
+    cache.register_usage(&[1]);
+    assert_eq!(0, cache.load_requested(&data, &[SECTANTS[1] as u32]));
+    assert!(cache.slot_of(1).is_some());
+
+    cache.register_usage(&[1]);
+    assert_eq!(1, cache.load_requested(&data, &[SECTANTS[1] as u32]));
+    assert_eq!(None, cache.slot_of(1));
+    assert!(cache.slot_of(2).is_some());
+}
+
+#[test]
+fn test_gpu_cache_follows_host_updates() {
+    let mut tree = sample_tree();
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Full).unwrap();
+    host.take_dirty_ranges();
+    let mut cache = ContreeGPUCache::new(host.data(), 2).unwrap();
+    cache.load_requested(host.data(), &[SECTANTS[0] as u32]);
+    cache.take_dirty_ranges();
+
+    host.insert(&mut tree, SIZE, &V3c::new(2, 0, 0), 0x0000FFFF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    let changes = host.take_dirty_ranges();
+    cache.update(host.data(), &changes);
+    let slot = cache.slot_of(1).unwrap();
+    assert_eq!(
+        &host.data().nodes[GPU_NODE_SIZE..2 * GPU_NODE_SIZE],
+        slot_entries(&cache, slot)
+    );
+    assert!(!cache.take_dirty_ranges().nodes.is_empty());
+}
+
+#[test]
+fn test_gpu_cache_rejects_invalid_capacity() {
+    let data = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    assert!(matches!(
+        ContreeGPUCache::new(&data, 0),
+        Err(ContreeError::LimitExceeded { .. })
+    ));
+}
@@ -0,0 +1,414 @@
+use voxelhex::{
+    contree::{
+        contree_gpu_serialization::{
+            ContreeGPUHost, GpuContreeData, GpuNodeEncoding, GPU_NODE_FLAG, GPU_NODE_SIZE,
+            GPU_PAGE_NODES, GPU_PALETTE_ENTRY_SIZE,
+        },
+        types::{Contree, ContreeError, MaterialKind, MaterialProperties, MaterialTable, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(1, 0, 0), 0x0000FFFF).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(15, 15, 15), 0x7FFFFFFF)
+        .unwrap();
+    tree
+}
+
+fn assert_same_voxels(expected: &Contree, actual: &Contree) {
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let position = V3c::new(x, y, z);
+                assert_eq!(
+                    expected.get(SIZE, &position).unwrap(),
+                    actual.get(SIZE, &position).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+fn assert_host_matches(tree: &Contree, host: &ContreeGPUHost) {
+    assert_same_voxels(tree, &Contree::from_gpu_serialized(host.data()).unwrap());
+}
+
+#[test]
+fn test_gpu_layout_round_trip() {
+    let tree = sample_tree();
+    let serialized = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let decoded = Contree::from_gpu_serialized(&serialized).unwrap();
+    assert_same_voxels(&tree, &decoded);
+    assert_eq!(
+        serialized,
+        decoded
+            .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_gpu_layout_node_pointers() {
+    let serialized = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+
+    // Root and the 3 nodes containing voxels
+    assert_eq!(4 * GPU_NODE_SIZE, serialized.nodes.len());
+    assert_eq!(4, serialized.mips.len());
+    let root_children = &serialized.nodes[2..GPU_NODE_SIZE];
+    let pointers: Vec<u32> = root_children
+        .iter()
+        .filter(|entry| 0 != *entry & GPU_NODE_FLAG)
+        .map(|entry| entry & !GPU_NODE_FLAG)
+        .collect();
+    assert_eq!(vec![1, 2, 3], pointers);
+}
+
+#[test]
+fn test_gpu_layout_mips() {
+    let serialized = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+
+    // The first child node holds a green and a blue voxel, the root averages its 3 child nodes
+    assert_eq!(u32::from_le_bytes([0x00, 0x7F, 0x7F, 0xFF]), serialized.mips[1]);
+    assert_eq!(u32::from_le_bytes([0x2A, 0xD4, 0x7F, 0xFF]), serialized.mips[0]);
+    assert!(serialized.mips.iter().all(|mip| 0 != *mip));
+}
+
+#[test]
+fn test_gpu_layout_leaf_root() {
+    let serialized = Contree::new()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    assert_eq!(GPU_NODE_SIZE, serialized.nodes.len());
+    assert!(serialized.nodes.iter().all(|entry| AIR == *entry));
+}
+
+#[test]
+fn test_gpu_layout_palette() {
+    let mut materials = MaterialTable::new();
+    materials.insert(
+        0x0000FFFF,
+        MaterialProperties {
+            kind: MaterialKind::Glass,
+            transparency: 0.5,
+            ..Default::default()
+        },
+    );
+    let serialized = sample_tree()
+        .serialize_gpu(SIZE, &materials, GpuNodeEncoding::Full)
+        .unwrap();
+
+    // Air, then the 3 distinct voxel data in the order they are reached
+    assert_eq!(4 * GPU_PALETTE_ENTRY_SIZE, serialized.palette.len());
+    let entry = |data: u32| {
+        serialized
+            .palette
+            .chunks_exact(GPU_PALETTE_ENTRY_SIZE)
+            .find(|entry| data == entry[GPU_PALETTE_ENTRY_SIZE - 1])
+            .unwrap()
+    };
+    assert_eq!(
+        u32::from_le_bytes([0x00, 0x00, 0xFF, 0xFF]),
+        entry(0x0000FFFF)[0]
+    );
+    assert_eq!(2, entry(0x0000FFFF)[1]);
+    assert_eq!(0.5, f32::from_bits(entry(0x0000FFFF)[3]));
+    assert_eq!(0, entry(0x00FF00FF)[1]);
+}
+
+#[test]
+fn test_gpu_layout_keeps_flagged_voxel_data() {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), GPU_NODE_FLAG | 1)
+        .unwrap();
+    let serialized = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    assert_same_voxels(&tree, &Contree::from_gpu_serialized(&serialized).unwrap());
+}
+
+#[test]
+fn test_gpu_decoder_rejects_corrupted_data() {
+    let mut serialized = sample_tree()
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let truncated = GpuContreeData {
+        nodes: serialized.nodes[..GPU_NODE_SIZE + 1].to_vec(),
+        ..serialized.clone()
+    };
+    assert!(matches!(
+        Contree::from_gpu_serialized(&truncated),
+        Err(ContreeError::UnexpectedEndOfData)
+    ));
+
+    // A child pointing back to the root would be a cycle
+    let first_pointer = serialized.nodes[2..GPU_NODE_SIZE]
+        .iter()
+        .position(|entry| 0 != entry & GPU_NODE_FLAG)
+        .unwrap();
+    serialized.nodes[2 + first_pointer] = GPU_NODE_FLAG;
+    assert!(matches!(
+        Contree::from_gpu_serialized(&serialized),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+}
+
+/// A root node in the first page with a single child node in the second page, holding a red voxel at the origin
+fn two_page_data(encoding: GpuNodeEncoding) -> GpuContreeData {
+    let child_index = GPU_PAGE_NODES;
+    let mut palette = vec![AIR; 2 * GPU_PALETTE_ENTRY_SIZE];
+    palette[2 * GPU_PALETTE_ENTRY_SIZE - 1] = 0xFF0000FF;
+    let mut data = GpuContreeData {
+        encoding,
+        mips: vec![0; child_index + 1],
+        palette,
+        ..Default::default()
+    };
+    match encoding {
+        GpuNodeEncoding::Full => {
+            data.nodes = vec![AIR; (child_index + 1) * GPU_NODE_SIZE];
+            data.nodes[..3].copy_from_slice(&[1, 0, GPU_NODE_FLAG | child_index as u32]);
+            let child_start = child_index * GPU_NODE_SIZE;
+            data.nodes[child_start..child_start + 3].copy_from_slice(&[1, 0, 1]);
+        }
+        GpuNodeEncoding::Compact => {
+            // Node offsets are relative to the compact page of the node
+            data.node_offsets = vec![0; child_index + 1];
+            data.compact_pages = vec![
+                vec![1, 0, GPU_NODE_FLAG | child_index as u32],
+                vec![1, 0, 1],
+            ];
+        }
+    }
+    data
+}
+
+#[test]
+fn test_gpu_layout_multiple_pages() {
+    let full = two_page_data(GpuNodeEncoding::Full);
+    assert_eq!(2, full.node_pages().count());
+    assert_eq!(2, full.mip_pages().count());
+    assert_eq!(GPU_NODE_SIZE, full.node_pages().last().unwrap().len());
+    assert_eq!(1, full.mip_pages().last().unwrap().len());
+
+    let compact = two_page_data(GpuNodeEncoding::Compact);
+    assert_eq!(2, compact.node_offset_pages().count());
+    for data in [full, compact] {
+        let decoded = Contree::from_gpu_serialized(&data).unwrap();
+        assert_eq!(0xFF0000FF, decoded.get(SIZE, &V3c::new(0, 0, 0)).unwrap());
+        assert_eq!(AIR, decoded.get(SIZE, &V3c::new(1, 0, 0)).unwrap());
+        assert_eq!(AIR, decoded.get(SIZE, &V3c::new(4, 0, 0)).unwrap());
+    }
+}
+
+#[test]
+fn test_gpu_host_single_voxel_update() {
+    let mut tree = sample_tree();
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Full).unwrap();
+    host.take_dirty_ranges();
+
+    host.insert(&mut tree, SIZE, &V3c::new(1, 0, 0), 0x00FF00FF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    let dirty_ranges = host.take_dirty_ranges();
+    let dirty_entries: usize = dirty_ranges.nodes.iter().map(|range| range.len()).sum();
+    assert!(0 < dirty_entries && dirty_entries <= 2, "{dirty_ranges:?}");
+    assert!(dirty_ranges.palette.is_empty());
+    assert_eq!(4 * GPU_NODE_SIZE, host.data().nodes.len());
+    assert_host_matches(&tree, &host);
+
+    // New voxel data extends the palette
+    host.insert(&mut tree, SIZE, &V3c::new(1, 0, 0), 0x123456FF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    let dirty_ranges = host.take_dirty_ranges();
+    assert_eq!(
+        vec![4 * GPU_PALETTE_ENTRY_SIZE..5 * GPU_PALETTE_ENTRY_SIZE],
+        dirty_ranges.palette
+    );
+    assert_host_matches(&tree, &host);
+}
+
+#[test]
+fn test_gpu_host_reuses_freed_nodes() {
+    let mut tree = sample_tree();
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Full).unwrap();
+
+    // Filling the node containing ( 5, 9, 13 ) turns it into a leaf, freeing its slot
+    for x in 4..8 {
+        for y in 8..12 {
+            for z in 12..16 {
+                host.insert(&mut tree, SIZE, &V3c::new(x, y, z), 0x00FF00FF)
+                    .unwrap();
+            }
+        }
+    }
+    host.update(&tree, SIZE).unwrap();
+    assert_host_matches(&tree, &host);
+
+    // A new node takes the place of the freed one instead of growing the structure
+    host.insert(&mut tree, SIZE, &V3c::new(0, 15, 0), 0x0000FFFF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    assert_eq!(4 * GPU_NODE_SIZE, host.data().nodes.len());
+    assert_host_matches(&tree, &host);
+
+    // Without free slots the structure grows
+    host.insert(&mut tree, SIZE, &V3c::new(15, 0, 0), 0x0000FFFF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    assert_eq!(5 * GPU_NODE_SIZE, host.data().nodes.len());
+    assert_eq!(5, host.data().mips.len());
+    assert_host_matches(&tree, &host);
+}
+
+#[test]
+fn test_gpu_host_update_from_empty_tree() {
+    let mut tree = Contree::new();
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Full).unwrap();
+    let expected = sample_tree();
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let data = expected.get(SIZE, &V3c::new(x, y, z)).unwrap();
+                if AIR != data {
+                    host.insert(&mut tree, SIZE, &V3c::new(x, y, z), data)
+                        .unwrap();
+                }
+            }
+        }
+    }
+    host.update(&tree, SIZE).unwrap();
+    assert_host_matches(&expected, &host);
+}
+
+#[test]
+fn test_gpu_compact_layout_round_trip() {
+    let tree = sample_tree();
+    let compact = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Compact)
+        .unwrap();
+    assert_same_voxels(&tree, &Contree::from_gpu_serialized(&compact).unwrap());
+
+    // Only occupied children are stored
+    let full = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Full)
+        .unwrap();
+    let compact_size: usize = compact.compact_pages.iter().map(|page| page.len()).sum();
+    assert!(compact_size < full.nodes.len());
+    assert_eq!(full.mips, compact.mips);
+    assert_eq!(full.palette, compact.palette);
+}
+
+#[test]
+fn test_gpu_compact_layout_uniform_node() {
+    let mut tree = Contree::new();
+    for x in 0..4 {
+        for y in 0..4 {
+            tree.insert(SIZE, &V3c::new(x, y, 0), 0x00FF00FF).unwrap();
+        }
+    }
+    let compact = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Compact)
+        .unwrap();
+    assert_same_voxels(&tree, &Contree::from_gpu_serialized(&compact).unwrap());
+
+    // The node containing the voxels stores its 16 identical children as a single run
+    let start = collapsed_node_start(&compact);
+    let page = &compact.compact_pages[0];
+    assert_eq!(0xFFFF, page[start]);
+    assert_eq!(0, page[start + 1]);
+    assert_eq!([1, 0], page[start + 2..start + 4]);
+    assert_eq!(5, page.len() - start);
+}
+
+/// The start of the only compact node storing runs of identical leaves
+fn collapsed_node_start(compact: &GpuContreeData) -> usize {
+    let collapsed_offsets: Vec<u32> = compact
+        .node_offsets
+        .iter()
+        .filter(|offset| 0 != *offset & GPU_NODE_FLAG)
+        .map(|offset| offset & !GPU_NODE_FLAG)
+        .collect();
+    assert_eq!(1, collapsed_offsets.len());
+    collapsed_offsets[0] as usize
+}
+
+#[test]
+fn test_gpu_compact_layout_leaf_runs() {
+    const GREEN: u32 = 0x00FF00FF;
+    const BLUE: u32 = 0x0000FFFF;
+    let mut tree = Contree::new();
+    for x in 0..4 {
+        for (y, data) in [GREEN, BLUE, GREEN, GREEN].into_iter().enumerate() {
+            tree.insert(SIZE, &V3c::new(x, y as u32, 0), data).unwrap();
+        }
+    }
+    let compact = tree
+        .serialize_gpu(SIZE, &MaterialTable::new(), GpuNodeEncoding::Compact)
+        .unwrap();
+    assert_same_voxels(&tree, &Contree::from_gpu_serialized(&compact).unwrap());
+
+    // Runs start at sectants 0, 4 and 8, each of them stored once
+    let start = collapsed_node_start(&compact);
+    let page = &compact.compact_pages[0];
+    assert_eq!([0x111, 0], page[start + 2..start + 4]);
+    assert_eq!(page[start + 4], page[start + 6]);
+    assert_ne!(page[start + 4], page[start + 5]);
+    assert_eq!(7, page.len() - start);
+
+    // Leaves differing from their neighbours start runs of their own
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Compact).unwrap();
+    for x in 0..4 {
+        host.insert(&mut tree, SIZE, &V3c::new(x, 2, 0), 0x123456FF + x)
+            .unwrap();
+    }
+    host.update(&tree, SIZE).unwrap();
+    assert_host_matches(&tree, &host);
+    let start = collapsed_node_start(host.data());
+    let page = &host.data().compact_pages[0];
+    assert_eq!([0x1F11, 0], page[start + 2..start + 4]);
+    assert_eq!(page[start + 4], page[start + 10]);
+}
+
+#[test]
+fn test_gpu_compact_host_update() {
+    let mut tree = sample_tree();
+    let mut host =
+        ContreeGPUHost::new(&tree, SIZE, &MaterialTable::new(), GpuNodeEncoding::Compact).unwrap();
+    host.take_dirty_ranges();
+
+    host.insert(&mut tree, SIZE, &V3c::new(2, 0, 0), 0x0000FFFF)
+        .unwrap();
+    host.insert(&mut tree, SIZE, &V3c::new(15, 0, 0), 0x0000FFFF)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    let dirty_ranges = host.take_dirty_ranges();
+    assert!(!dirty_ranges
+        .compact_pages
+        .iter()
+        .all(|ranges| ranges.is_empty()));
+    assert_host_matches(&tree, &host);
+
+    // Removing the only voxel of a node frees it
+    host.insert(&mut tree, SIZE, &V3c::new(15, 0, 0), AIR)
+        .unwrap();
+    host.update(&tree, SIZE).unwrap();
+    assert_host_matches(&tree, &host);
+}
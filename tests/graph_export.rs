@@ -0,0 +1,92 @@
+use voxelhex::{
+    contree::{convert::graph_export::GraphExportOptions, types::Contree},
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+/// A root node with two child nodes, holding one leaf each
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), RED).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), GREEN).unwrap();
+    tree
+}
+
+#[test]
+fn test_dot_export() {
+    let dot = sample_tree().to_dot(&GraphExportOptions::default());
+    assert!(dot.starts_with("digraph contree {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert_eq!(3, dot.matches("shape=box").count());
+    assert_eq!(2, dot.matches("shape=ellipse").count());
+    assert_eq!(4, dot.matches(" -> ").count());
+    assert!(dot.contains("occupancy 0x0200000000000001\\n2 / 64 occupied"));
+    assert!(dot.contains("label=\"0xff0000ff\", style=filled, fillcolor=\"#ff0000\""));
+    assert!(dot.contains("label=\"0x00ff00ff\", style=filled, fillcolor=\"#00ff00\""));
+
+    // Nodes without a visible mip color are not filled black
+    assert!(!dot.contains("fillcolor=\"#000000\""));
+
+    // ( 5, 9, 13 ) is inside sectant 1 + 2 * 4 + 3 * 16 of the root, then 1 + 1 * 4 + 1 * 16
+    assert!(dot.contains("n0 -> n3 [label=\"57\"]"));
+    assert!(dot.contains("n3 -> n4 [label=\"21\"]"));
+    assert!(!dot.contains("color=red"));
+}
+
+#[test]
+fn test_dot_export_options() {
+    let options = GraphExportOptions {
+        max_depth: Some(0),
+        ..GraphExportOptions::default()
+    };
+    let dot = sample_tree().to_dot(&options);
+    assert_eq!(1, dot.matches("shape=box").count());
+    assert_eq!(
+        2,
+        dot.matches("shape=plaintext, label=\"... 1 nodes\"")
+            .count()
+    );
+    assert!(!dot.contains("shape=ellipse"));
+
+    let options = GraphExportOptions {
+        max_depth: None,
+        occupancy_labels: false,
+        mip_fill: false,
+        leaf_materials: false,
+    };
+    let dot = sample_tree().to_dot(&options);
+    assert_eq!(3, dot.matches("shape=box").count());
+    assert_eq!(2, dot.matches(" -> ").count());
+    assert!(!dot.contains("occupancy"));
+    assert!(!dot.contains("filled"));
+    assert!(dot.contains("shape=box, label=\"node, depth 1\""));
+}
+
+#[test]
+fn test_graphml_export() {
+    let graphml = sample_tree().to_graphml(&GraphExportOptions::default());
+    assert!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"));
+    assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+    assert_eq!(5, graphml.matches("<node id=").count());
+    assert_eq!(4, graphml.matches("<edge id=").count());
+    assert_eq!(3, graphml.matches("<data key=\"kind\">node</data>").count());
+    assert_eq!(2, graphml.matches("<data key=\"kind\">leaf</data>").count());
+    assert_eq!(
+        5,
+        graphml
+            .matches("<data key=\"collapsible\">false</data>")
+            .count()
+    );
+    assert!(graphml.contains(
+        "<edge id=\"e3\" source=\"n0\" target=\"n3\">\n      <data key=\"sectant\">57</data>"
+    ));
+
+    // A single leaf is exported as a graph with a single vertex
+    let graphml = Contree::Leaf(RED).to_graphml(&GraphExportOptions::default());
+    assert_eq!(1, graphml.matches("<node id=").count());
+    assert!(!graphml.contains("<edge"));
+    assert!(graphml.contains("<data key=\"fill\">#ff0000</data>"));
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use voxelhex::{
+    contree::types::{Contree, ContreeError},
+    spatial::math::vector::V3c,
+};
+
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+/// A ball of the given radius around the given center, split into a red and a green half along X
+fn ball(tree: &mut Contree, size: u32, center: V3c<u32>, radius: u32) {
+    for x in center.x - radius..=center.x + radius {
+        for y in center.y - radius..=center.y + radius {
+            for z in center.z - radius..=center.z + radius {
+                let offset = V3c::new(x as f32, y as f32, z as f32)
+                    - V3c::new(center.x as f32, center.y as f32, center.z as f32);
+                if offset.length() <= radius as f32 {
+                    let data = if x < center.x { RED } else { GREEN };
+                    tree.insert(size, &V3c::new(x, y, z), data).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Counts the triangles around every edge, with vertices at the same position treated as one
+fn edge_usage(
+    positions: &[V3c<f32>],
+    triangles: &[[u32; 3]],
+) -> HashMap<[(u32, u32, u32); 2], usize> {
+    let key = |index: u32| {
+        let position = positions[index as usize];
+        (
+            position.x.to_bits(),
+            position.y.to_bits(),
+            position.z.to_bits(),
+        )
+    };
+    let mut edges = HashMap::new();
+    for triangle in triangles {
+        for (start, end) in [(0, 1), (1, 2), (2, 0)] {
+            let mut edge = [key(triangle[start]), key(triangle[end])];
+            edge.sort();
+            *edges.entry(edge).or_insert(0) += 1;
+        }
+    }
+    edges
+}
+
+#[test]
+fn test_isosurface_of_single_chunk() {
+    let mut tree = Contree::new();
+    ball(&mut tree, 16, V3c::new(8, 8, 8), 4);
+    let mesh = tree.extract_isosurface(16, None, 0.5).unwrap();
+    assert_eq!(
+        mesh,
+        tree.extract_isosurface_chunk(16, V3c::unit(0), 16, None, 0.5)
+            .unwrap()
+    );
+    assert!(!mesh.triangles.is_empty());
+    assert_eq!(mesh.triangles.len(), mesh.materials.len());
+    assert!(mesh
+        .materials
+        .iter()
+        .all(|data| RED == *data || GREEN == *data));
+    assert!(edge_usage(&mesh.positions, &mesh.triangles)
+        .values()
+        .all(|count| 2 == *count));
+}
+
+#[test]
+fn test_isosurface_across_chunks_is_closed() {
+    // The ball crosses the boundaries of the chunks the tree is processed in
+    let size = 256;
+    let mut tree = Contree::new();
+    ball(&mut tree, size, V3c::new(64, 64, 64), 5);
+    tree.insert(size, &V3c::new(255, 255, 255), RED).unwrap();
+
+    let mesh = tree.extract_isosurface(size, None, 0.5).unwrap();
+    assert!(!mesh.triangles.is_empty());
+    assert!(edge_usage(&mesh.positions, &mesh.triangles)
+        .values()
+        .all(|count| 2 == *count));
+
+    // Every triangle is inside the ball or around the lone voxel
+    assert!(mesh.positions.iter().all(|position| {
+        (*position - V3c::new(64.5, 64.5, 64.5)).length() < 8.
+            || (*position - V3c::new(255.5, 255.5, 255.5)).length() < 2.
+    }));
+}
+
+#[test]
+fn test_isosurface_of_collapsed_tree() {
+    // A tree filled entirely is a single leaf, the surface wraps the boundary of the given size
+    let tree = Contree::Leaf(RED);
+    let mesh = tree.extract_isosurface(16, None, 0.5).unwrap();
+    assert!(!mesh.triangles.is_empty());
+    assert!(edge_usage(&mesh.positions, &mesh.triangles)
+        .values()
+        .all(|count| 2 == *count));
+    assert!(mesh.positions.iter().any(|position| position.x > 15.));
+    assert!(mesh
+        .positions
+        .iter()
+        .all(|position| position.x < 17. && position.y < 17. && position.z < 17.));
+
+    assert!(matches!(
+        tree.extract_isosurface(10, None, 0.5),
+        Err(ContreeError::InvalidSize(10))
+    ));
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "dot_vox_support")]
+
+use dot_vox::{Color, Dict, DotVoxData, Material, Model, Size, Voxel};
+use voxelhex::{
+    contree::types::{Contree, ContreeError, MaterialKind, AIR},
+    spatial::math::vector::V3c,
+};
+
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("voxelhex_{}_{name}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_vox_export_round_trip() {
+    let mut tree = Contree::new();
+    tree.insert(16, &V3c::new(0, 0, 0), RED).unwrap();
+    tree.insert(16, &V3c::new(1, 0, 0), GREEN).unwrap();
+    tree.insert(16, &V3c::new(5, 9, 13), RED).unwrap();
+
+    let path = temp_path("round_trip.vox");
+    tree.export_vox(&path, 16).unwrap();
+    let (loaded, size) = Contree::load_vox_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(16, size);
+
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let position = V3c::new(x, y, z);
+                assert_eq!(
+                    tree.get(16, &position).unwrap(),
+                    loaded.get(16, &position).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+    assert_eq!(AIR, loaded.get(16, &V3c::new(2, 0, 0)).unwrap());
+}
+
+#[test]
+fn test_vox_export_collapsed_subtree() {
+    // The solid block collapses into a single leaf, one level above the voxels;
+    // The voxel at the origin keeps the loaded model at the same position
+    let mut tree = Contree::new();
+    tree.insert(16, &V3c::new(0, 0, 0), GREEN).unwrap();
+    for x in 4..8 {
+        for y in 0..4 {
+            for z in 8..12 {
+                tree.insert(16, &V3c::new(x, y, z), RED).unwrap();
+            }
+        }
+    }
+    assert!(matches!(tree, Contree::Node(_)));
+
+    let path = temp_path("collapsed.vox");
+    tree.export_vox(&path, 16).unwrap();
+    let (loaded, size) = Contree::load_vox_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(16, size);
+
+    let mut voxel_count = 0;
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                let position = V3c::new(x, y, z);
+                let expected = tree.get(16, &position).unwrap();
+                assert_eq!(expected, loaded.get(16, &position).unwrap(), "Mismatch at {x},{y},{z}");
+                if expected != AIR {
+                    voxel_count += 1;
+                }
+            }
+        }
+    }
+    assert_eq!(65, voxel_count);
+}
+
+#[test]
+fn test_vox_export_rejects_invalid_size() {
+    let path = temp_path("invalid_size.vox");
+    assert!(matches!(
+        Contree::new().export_vox(&path, 10),
+        Err(ContreeError::InvalidSize(10))
+    ));
+}
+
+#[test]
+fn test_vox_export_rejects_palette_overflow() {
+    let mut tree = Contree::new();
+    for index in 0..256u32 {
+        let position = V3c::new(index % 16, index / 16, 0);
+        tree.insert(16, &position, 0x000000FF | (index + 1) << 8).unwrap();
+    }
+    let path = temp_path("palette_overflow.vox");
+    assert!(matches!(
+        tree.export_vox(&path, 16),
+        Err(ContreeError::LimitExceeded { limit: "palette" })
+    ));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_vox_materials_follow_palette_indices() {
+    // MATL ids are the palette indices of the file, which are one larger than `Voxel::i`
+    let mut palette = vec![Color { r: 0, g: 0, b: 0, a: 255 }; 256];
+    palette[0] = Color { r: 255, g: 0, b: 0, a: 255 };
+    palette[1] = Color { r: 0, g: 255, b: 0, a: 255 };
+    let material = |id, properties: &[(&str, &str)]| Material {
+        id,
+        properties: properties
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<Dict>(),
+    };
+    let vox_data = DotVoxData {
+        version: 150,
+        index_map: (0..=255).collect(),
+        models: vec![Model {
+            size: Size { x: 2, y: 1, z: 1 },
+            voxels: vec![
+                Voxel { x: 0, y: 0, z: 0, i: 0 },
+                Voxel { x: 1, y: 0, z: 0, i: 1 },
+            ],
+        }],
+        palette,
+        materials: vec![
+            material(1, &[("_type", "_metal"), ("_metal", "0.8")]),
+            material(2, &[("_type", "_glass"), ("_trans", "0.5")]),
+            material(3, &[("_type", "_emit"), ("_emit", "1.0")]),
+        ],
+        scenes: Vec::new(),
+        layers: Vec::new(),
+    };
+    let path = temp_path("materials.vox");
+    vox_data
+        .write_vox(&mut std::fs::File::create(&path).unwrap())
+        .unwrap();
+    let (tree, size, materials) = Contree::load_vox_file_with_materials(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(4, size);
+    assert_eq!(RED, tree.get(size, &V3c::new(0, 0, 0)).unwrap());
+    assert_eq!(GREEN, tree.get(size, &V3c::new(1, 0, 0)).unwrap());
+    assert_eq!(2, materials.len());
+    assert_eq!(MaterialKind::Metal, materials[&RED].kind);
+    assert_eq!(0.8, materials[&RED].metalness);
+    assert_eq!(MaterialKind::Glass, materials[&GREEN].kind);
+    assert_eq!(0.5, materials[&GREEN].transparency);
+}
@@ -0,0 +1,155 @@
+use std::io::Cursor;
+use voxelhex::{
+    contree::{
+        convert::mapped::{replace_subtree, MappedChild, MappedContree},
+        types::{Contree, ContreeError, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 64;
+const SUBTREE_SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+const BLUE: u32 = 0x0000FFFF;
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), RED).unwrap();
+    tree.insert(SIZE, &V3c::new(3, 2, 1), GREEN).unwrap();
+    tree.insert(SIZE, &V3c::new(40, 50, 60), RED).unwrap();
+    tree
+}
+
+/// A subtree covering 16^3 voxels with the given voxels set
+fn subtree(voxels: &[(V3c<u32>, u32)]) -> Contree {
+    let mut tree = Contree::new();
+    for (position, data) in voxels {
+        tree.insert(SUBTREE_SIZE, position, *data).unwrap();
+    }
+    tree
+}
+
+/// Sets the voxels of the given subtree inside the expected tree, with the subtree placed at the given offset
+fn place(expected: &mut Contree, offset: V3c<u32>, subtree: &Contree) {
+    for x in 0..SUBTREE_SIZE {
+        for y in 0..SUBTREE_SIZE {
+            for z in 0..SUBTREE_SIZE {
+                let position = V3c::new(x, y, z);
+                let data = subtree.get(SUBTREE_SIZE, &position).unwrap();
+                expected.insert(SIZE, &(offset + position), data).unwrap();
+            }
+        }
+    }
+}
+
+fn assert_content(bytes: &[u8], expected: &Contree) {
+    let mapped = MappedContree::new(bytes).unwrap();
+    let loaded = mapped.load().unwrap();
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let position = V3c::new(x, y, z);
+                let expected = expected.get(SIZE, &position).unwrap();
+                assert_eq!(expected, mapped.get(SIZE, &position).unwrap(), "Mismatch at {x},{y},{z}");
+                assert_eq!(expected, loaded.get(SIZE, &position).unwrap(), "Mismatch at {x},{y},{z}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_mapped_round_trip() {
+    let tree = sample_tree();
+    assert_content(&tree.to_mapped_bytes(), &tree);
+}
+
+#[test]
+fn test_replace_subtree_with_larger_one() {
+    let mut expected = sample_tree();
+    let mut file = Cursor::new(expected.to_mapped_bytes());
+    let replacement = subtree(&[
+        (V3c::new(0, 0, 0), BLUE),
+        (V3c::new(15, 15, 15), GREEN),
+        (V3c::new(7, 8, 9), BLUE),
+        (V3c::new(1, 0, 0), RED),
+    ]);
+    replace_subtree(&mut file, &[0], &replacement).unwrap();
+
+    // The previous content of the subtree is replaced entirely
+    place(&mut expected, V3c::new(0, 0, 0), &Contree::Leaf(AIR));
+    place(&mut expected, V3c::new(0, 0, 0), &replacement);
+    assert_content(file.get_ref(), &expected);
+}
+
+#[test]
+fn test_replace_subtree_with_smaller_one() {
+    let mut expected = sample_tree();
+    let mut file = Cursor::new(expected.to_mapped_bytes());
+
+    // A node replaced by a leaf
+    replace_subtree(&mut file, &[0], &Contree::Leaf(BLUE)).unwrap();
+    place(&mut expected, V3c::new(0, 0, 0), &Contree::Leaf(BLUE));
+    assert_content(file.get_ref(), &expected);
+
+    // A node removed entirely, which rewrites its parent
+    replace_subtree(&mut file, &[62], &Contree::Leaf(AIR)).unwrap();
+    place(&mut expected, V3c::new(32, 48, 48), &Contree::Leaf(AIR));
+    assert_content(file.get_ref(), &expected);
+}
+
+#[test]
+fn test_replace_subtree_repoints_ancestors() {
+    let mut expected = sample_tree();
+    let mut file = Cursor::new(expected.to_mapped_bytes());
+
+    // The slot is empty in the parent, so the parent is copied and the root is repointed
+    let replacement = subtree(&[(V3c::new(2, 3, 4), GREEN)]);
+    replace_subtree(&mut file, &[21], &replacement).unwrap();
+    place(&mut expected, V3c::new(16, 16, 16), &replacement);
+    assert_content(file.get_ref(), &expected);
+
+    // Deeper levels follow the copied parent
+    replace_subtree(&mut file, &[21, 0], &Contree::Leaf(RED)).unwrap();
+    for x in 16..20 {
+        for y in 16..20 {
+            for z in 16..20 {
+                expected.insert(SIZE, &V3c::new(x, y, z), RED).unwrap();
+            }
+        }
+    }
+    replace_subtree(&mut file, &[21, 0, 1], &Contree::Leaf(BLUE)).unwrap_err();
+    assert_content(file.get_ref(), &expected);
+}
+
+#[test]
+fn test_replace_root() {
+    let mut file = Cursor::new(sample_tree().to_mapped_bytes());
+    replace_subtree(&mut file, &[], &Contree::Leaf(GREEN)).unwrap();
+    assert_eq!(
+        MappedChild::Leaf(GREEN),
+        MappedContree::new(file.get_ref()).unwrap().root()
+    );
+
+    let tree = sample_tree();
+    replace_subtree(&mut file, &[], &tree).unwrap();
+    assert_content(file.get_ref(), &tree);
+}
+
+#[test]
+fn test_replace_subtree_rejects_paths_through_leaves() {
+    let mut file = Cursor::new(sample_tree().to_mapped_bytes());
+    assert!(matches!(
+        replace_subtree(&mut file, &[21, 5], &Contree::Leaf(RED)),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+    replace_subtree(&mut file, &[0], &Contree::Leaf(BLUE)).unwrap();
+    assert!(matches!(
+        replace_subtree(&mut file, &[0, 0], &Contree::Leaf(RED)),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+    assert!(matches!(
+        replace_subtree(&mut file, &[64], &Contree::Leaf(RED)),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+}
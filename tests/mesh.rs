@@ -0,0 +1,199 @@
+use voxelhex::{
+    contree::{
+        convert::mesh::{TriangleSoup, VoxelColoring, VoxelizationMode},
+        types::{Contree, ContreeError, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+const BLUE: u32 = 0x0000FFFF;
+
+/// A unit cube with quad faces, vertices colored red when `colored` is set
+fn cube_obj(colored: bool) -> String {
+    let color = if colored { " 1.0 0.0 0.0" } else { "" };
+    let mut source = String::from("# unit cube\n");
+    for (x, y, z) in [
+        (0, 0, 0),
+        (1, 0, 0),
+        (1, 1, 0),
+        (0, 1, 0),
+        (0, 0, 1),
+        (1, 0, 1),
+        (1, 1, 1),
+        (0, 1, 1),
+    ] {
+        source.push_str(&format!("v {x} {y} {z}{color}\n"));
+    }
+    source.push_str("f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 4 8 7 3\nf 1 5 8 4\nf 2/1 3/2 7/3 6/4\n");
+    source
+}
+
+fn count_voxels(tree: &Contree, data: u32) -> usize {
+    let mut count = 0;
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                if data == tree.get(SIZE, &V3c::new(x, y, z)).unwrap() {
+                    count += 1;
+                }
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn test_obj_parsing() {
+    let mesh = TriangleSoup::from_obj(&cube_obj(false)).unwrap();
+    assert_eq!(12, mesh.triangles.len());
+    assert!(mesh
+        .triangles
+        .iter()
+        .all(|triangle| triangle.colors.is_none()));
+
+    let mesh = TriangleSoup::from_obj(&cube_obj(true)).unwrap();
+    assert!(mesh
+        .triangles
+        .iter()
+        .all(|triangle| triangle.colors.is_some()));
+
+    assert!(TriangleSoup::from_obj("v 0 0 0\nf 1 2 3\n").is_err());
+}
+
+#[test]
+fn test_voxelize_surface_and_solid() {
+    let mesh = TriangleSoup::from_obj(&cube_obj(false)).unwrap();
+
+    let surface = Contree::voxelize(
+        &mesh,
+        SIZE,
+        VoxelizationMode::Surface,
+        VoxelColoring::Constant(GREEN),
+    )
+    .unwrap();
+    assert_eq!(GREEN, surface.get(SIZE, &V3c::new(0, 8, 8)).unwrap());
+    assert_eq!(GREEN, surface.get(SIZE, &V3c::new(8, 15, 8)).unwrap());
+    assert_eq!(AIR, surface.get(SIZE, &V3c::new(8, 8, 8)).unwrap());
+    assert_eq!(
+        (SIZE * SIZE * SIZE - (SIZE - 2) * (SIZE - 2) * (SIZE - 2)) as usize,
+        count_voxels(&surface, GREEN)
+    );
+
+    let solid = Contree::voxelize(
+        &mesh,
+        SIZE,
+        VoxelizationMode::Solid,
+        VoxelColoring::Constant(GREEN),
+    )
+    .unwrap();
+    assert_eq!((SIZE * SIZE * SIZE) as usize, count_voxels(&solid, GREEN));
+}
+
+#[test]
+fn test_voxelize_vertex_colors() {
+    let mesh = TriangleSoup::from_obj(&cube_obj(true)).unwrap();
+    let tree = Contree::voxelize(
+        &mesh,
+        SIZE,
+        VoxelizationMode::Surface,
+        VoxelColoring::VertexColors { fallback: GREEN },
+    )
+    .unwrap();
+    assert_eq!(RED, tree.get(SIZE, &V3c::new(0, 8, 8)).unwrap());
+    assert_eq!(0, count_voxels(&tree, GREEN));
+
+    let mesh = TriangleSoup::from_obj(&cube_obj(false)).unwrap();
+    let tree = Contree::voxelize(
+        &mesh,
+        SIZE,
+        VoxelizationMode::Surface,
+        VoxelColoring::VertexColors { fallback: GREEN },
+    )
+    .unwrap();
+    assert_eq!(GREEN, tree.get(SIZE, &V3c::new(0, 8, 8)).unwrap());
+}
+
+#[test]
+fn test_voxelize_vertex_colors_of_closest_triangle() {
+    // Both triangles cross the bottom layer of voxels, the blue one is closer to their centers
+    let mesh = TriangleSoup::from_obj(
+        "v 0 0.8 0 0 0 1\nv 16 0.8 0 0 0 1\nv 0 0.8 16 0 0 1\n\
+         v 0 0.1 0 1 0 0\nv 16 0.1 0 1 0 0\nv 0 0.1 16 1 0 0\n\
+         f 1 2 3\nf 4 5 6\n",
+    )
+    .unwrap();
+    let tree = Contree::voxelize(
+        &mesh,
+        SIZE,
+        VoxelizationMode::Surface,
+        VoxelColoring::VertexColors { fallback: GREEN },
+    )
+    .unwrap();
+    assert_eq!(BLUE, tree.get(SIZE, &V3c::new(4, 0, 8)).unwrap());
+}
+
+#[test]
+fn test_mesh_rejects_non_finite_vertices() {
+    assert!(matches!(
+        TriangleSoup::from_obj("v 0 0 0\nv 1 NaN 0\nv 0 1 0\nf 1 2 3\n"),
+        Err(ContreeError::NonFiniteCoordinate { index: 1 })
+    ));
+    assert!(matches!(
+        TriangleSoup::from_stl(
+            b"solid test\nfacet normal 0 0 0\nouter loop\n\
+              vertex 0 0 0\nvertex 1 0 0\nvertex 0 inf 0\nendloop\nendfacet\nendsolid test\n"
+        ),
+        Err(ContreeError::NonFiniteCoordinate { index: 2 })
+    ));
+
+    let mut binary = vec![0u8; 80];
+    binary.extend_from_slice(&1u32.to_le_bytes());
+    binary.extend_from_slice(&[0u8; 12]);
+    for component in [0., 0., 0., 1., 0., 0., 0., 0., f32::NEG_INFINITY] {
+        binary.extend_from_slice(&f32::to_le_bytes(component));
+    }
+    binary.extend_from_slice(&[0u8; 2]);
+    assert!(matches!(
+        TriangleSoup::from_stl(&binary),
+        Err(ContreeError::NonFiniteCoordinate { index: 2 })
+    ));
+}
+
+#[test]
+fn test_stl_binary_and_ascii_agree() {
+    let triangles = [
+        [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]],
+        [[0., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+    ];
+
+    let mut binary = vec![0u8; 80];
+    binary.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+    let mut ascii = String::from("solid test\n");
+    for triangle in triangles.iter() {
+        binary.extend_from_slice(&[0u8; 12]);
+        ascii.push_str("facet normal 0 0 0\nouter loop\n");
+        for vertex in triangle.iter() {
+            for component in vertex.iter() {
+                binary.extend_from_slice(&(*component as f32).to_le_bytes());
+            }
+            ascii.push_str(&format!(
+                "vertex {} {} {}\n",
+                vertex[0], vertex[1], vertex[2]
+            ));
+        }
+        binary.extend_from_slice(&[0u8; 2]);
+        ascii.push_str("endloop\nendfacet\n");
+    }
+    ascii.push_str("endsolid test\n");
+
+    let from_binary = TriangleSoup::from_stl(&binary).unwrap();
+    let from_ascii = TriangleSoup::from_stl(ascii.as_bytes()).unwrap();
+    assert_eq!(2, from_binary.triangles.len());
+    assert_eq!(from_binary.triangles, from_ascii.triangles);
+    assert_eq!(V3c::new(0., 0., 1.), from_binary.triangles[1].vertices[2]);
+
+    assert!(TriangleSoup::from_stl(b"not a mesh").is_err());
+}
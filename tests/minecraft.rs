@@ -0,0 +1,651 @@
+#![cfg(feature = "minecraft_support")]
+
+use std::io::Write;
+use voxelhex::{
+    contree::{
+        convert::minecraft::BlockMapping,
+        types::{Contree, ContreeError, LoadLimits, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+const BLUE: u32 = 0x0000FFFF;
+const GRAY: u32 = 0x808080FF;
+
+/// A minimal NBT writer to build test documents with
+enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    String(&'static str),
+    ByteArray(Vec<u8>),
+    LongArray(Vec<i64>),
+    List(Vec<Nbt>),
+    Compound(Vec<(&'static str, Nbt)>),
+}
+
+impl Nbt {
+    fn tag_type(&self) -> u8 {
+        match self {
+            Nbt::Byte(_) => 1,
+            Nbt::Short(_) => 2,
+            Nbt::Int(_) => 3,
+            Nbt::Long(_) => 4,
+            Nbt::String(_) => 8,
+            Nbt::ByteArray(_) => 7,
+            Nbt::LongArray(_) => 12,
+            Nbt::List(_) => 9,
+            Nbt::Compound(_) => 10,
+        }
+    }
+
+    fn write_payload(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Nbt::Byte(value) => bytes.push(*value as u8),
+            Nbt::Short(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+            Nbt::Int(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+            Nbt::Long(value) => bytes.extend_from_slice(&value.to_be_bytes()),
+            Nbt::String(value) => {
+                bytes.extend_from_slice(&(value.len() as u16).to_be_bytes());
+                bytes.extend_from_slice(value.as_bytes());
+            }
+            Nbt::ByteArray(values) => {
+                bytes.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                bytes.extend_from_slice(values);
+            }
+            Nbt::LongArray(values) => {
+                bytes.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                for value in values {
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            Nbt::List(values) => {
+                bytes.push(values.first().map_or(0, Nbt::tag_type));
+                bytes.extend_from_slice(&(values.len() as i32).to_be_bytes());
+                for value in values {
+                    value.write_payload(bytes);
+                }
+            }
+            Nbt::Compound(children) => {
+                for (name, child) in children {
+                    bytes.push(child.tag_type());
+                    Nbt::String(name).write_payload(bytes);
+                    child.write_payload(bytes);
+                }
+                bytes.push(0);
+            }
+        }
+    }
+
+    /// Encodes the value as the root compound of an NBT document
+    fn document(&self) -> Vec<u8> {
+        let mut bytes = vec![self.tag_type()];
+        Nbt::String("").write_payload(&mut bytes);
+        self.write_payload(&mut bytes);
+        bytes
+    }
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("voxelhex_{}_{name}", std::process::id()))
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn varints(values: &[u32]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for value in values {
+        let mut value = *value;
+        while value >= 0x80 {
+            bytes.push((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+    }
+    bytes
+}
+
+fn mapping() -> BlockMapping {
+    BlockMapping::new(GRAY)
+        .with_block("minecraft:stone", RED)
+        .with_block("minecraft:oak_log[axis=y]", GREEN)
+        .with_block("minecraft:oak_log", BLUE)
+}
+
+//####################################################################################
+//  Schematic
+//####################################################################################
+
+const SCHEMATIC_WIDTH: i16 = 3;
+const SCHEMATIC_HEIGHT: i16 = 2;
+const SCHEMATIC_LENGTH: i16 = 2;
+
+/// Palette indices of the test schematic in YZX order
+const SCHEMATIC_BLOCKS: [u32; 12] = [
+    1, 0, 2, // y: 0, z: 0
+    0, 3, 0, // y: 0, z: 1
+    0, 0, 0, // y: 1, z: 0
+    4, 0, 130, // y: 1, z: 1
+];
+
+fn schematic_palette() -> Nbt {
+    Nbt::Compound(vec![
+        ("minecraft:air", Nbt::Int(0)),
+        ("minecraft:stone", Nbt::Int(1)),
+        ("minecraft:oak_log[axis=y]", Nbt::Int(2)),
+        ("minecraft:oak_log[axis=x]", Nbt::Int(3)),
+        ("minecraft:dirt", Nbt::Int(4)),
+        ("minecraft:glass", Nbt::Int(130)),
+    ])
+}
+
+fn schematic_dimensions() -> Vec<(&'static str, Nbt)> {
+    vec![
+        ("Width", Nbt::Short(SCHEMATIC_WIDTH)),
+        ("Height", Nbt::Short(SCHEMATIC_HEIGHT)),
+        ("Length", Nbt::Short(SCHEMATIC_LENGTH)),
+    ]
+}
+
+fn schematic_v2() -> Vec<u8> {
+    let mut children = schematic_dimensions();
+    children.push(("Version", Nbt::Int(2)));
+    children.push(("Palette", schematic_palette()));
+    children.push(("BlockData", Nbt::ByteArray(varints(&SCHEMATIC_BLOCKS))));
+    Nbt::Compound(children).document()
+}
+
+fn schematic_v3() -> Vec<u8> {
+    let mut children = schematic_dimensions();
+    children.push(("Version", Nbt::Int(3)));
+    children.push((
+        "Blocks",
+        Nbt::Compound(vec![
+            ("Palette", schematic_palette()),
+            ("Data", Nbt::ByteArray(varints(&SCHEMATIC_BLOCKS))),
+        ]),
+    ));
+    Nbt::Compound(vec![("Schematic", Nbt::Compound(children))]).document()
+}
+
+fn assert_schematic_content(tree: &Contree) {
+    // Z is mirrored into the left handed coordinate system of the library
+    let expected = [
+        ((0, 0, 1), RED),
+        ((2, 0, 1), GREEN),
+        ((1, 0, 0), BLUE),
+        ((0, 1, 0), GRAY),
+        ((2, 1, 0), GRAY),
+    ];
+    for x in 0..4 {
+        for y in 0..4 {
+            for z in 0..4 {
+                let data = expected
+                    .iter()
+                    .find(|(position, _)| *position == (x, y, z))
+                    .map_or(AIR, |(_, data)| *data);
+                assert_eq!(
+                    data,
+                    tree.get(4, &V3c::new(x, y, z)).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_schematic_versions() {
+    assert_schematic_content(&Contree::from_schematic_bytes(&schematic_v2(), &mapping()).unwrap());
+    assert_schematic_content(&Contree::from_schematic_bytes(&schematic_v3(), &mapping()).unwrap());
+}
+
+#[test]
+fn test_schematic_compressed() {
+    let tree = Contree::from_schematic_bytes(&gzip(&schematic_v3()), &mapping()).unwrap();
+    assert_schematic_content(&tree);
+}
+
+#[test]
+fn test_schematic_missing_palette_entry() {
+    let mut children = schematic_dimensions();
+    children.push(("Palette", schematic_palette()));
+    children.push(("BlockData", Nbt::ByteArray(varints(&[5; 12]))));
+    let result = Contree::from_schematic_bytes(&Nbt::Compound(children).document(), &mapping());
+    assert!(matches!(result, Err(ContreeError::InvalidStructure(_))));
+}
+
+#[test]
+fn test_schematic_truncated_block_data() {
+    let mut children = schematic_dimensions();
+    children.push(("Palette", schematic_palette()));
+    children.push((
+        "BlockData",
+        Nbt::ByteArray(varints(&SCHEMATIC_BLOCKS[..11])),
+    ));
+    let result = Contree::from_schematic_bytes(&Nbt::Compound(children).document(), &mapping());
+    assert!(matches!(result, Err(ContreeError::UnexpectedEndOfData)));
+}
+
+#[test]
+fn test_schematic_nested_too_deep() {
+    let mut nested = Nbt::List(Vec::new());
+    for _ in 0..1000 {
+        nested = Nbt::List(vec![nested]);
+    }
+    let mut children = schematic_dimensions();
+    children.push(("Metadata", nested));
+    let result = Contree::from_schematic_bytes(&Nbt::Compound(children).document(), &mapping());
+    assert!(matches!(
+        result,
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+}
+
+#[test]
+fn test_schematic_limits() {
+    let limits = LoadLimits {
+        max_depth: 2,
+        ..LoadLimits::default()
+    };
+    let tree = Contree::from_schematic_bytes_with_limits(&schematic_v2(), &mapping(), &limits);
+    assert_schematic_content(&tree.unwrap());
+
+    // 65535 blocks wide, which needs a tree of depth 8
+    let mut children = schematic_dimensions();
+    children[0] = ("Width", Nbt::Short(-1));
+    children.push(("Palette", schematic_palette()));
+    children.push(("BlockData", Nbt::ByteArray(Vec::new())));
+    let oversized = Nbt::Compound(children).document();
+    assert!(matches!(
+        Contree::from_schematic_bytes_with_limits(&oversized, &mapping(), &limits),
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+
+    let limits = LoadLimits {
+        max_nodes: 0,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Contree::from_schematic_bytes_with_limits(&schematic_v2(), &mapping(), &limits),
+        Err(ContreeError::LimitExceeded {
+            limit: "node count"
+        })
+    ));
+}
+
+#[test]
+fn test_schematic_decompression_bomb() {
+    let limits = LoadLimits {
+        max_memory: 1 << 20,
+        ..LoadLimits::default()
+    };
+    let bomb = gzip(&vec![0u8; 16 << 20]);
+    assert!(bomb.len() < limits.max_memory);
+    assert!(matches!(
+        Contree::from_schematic_bytes_with_limits(&bomb, &mapping(), &limits),
+        Err(ContreeError::LimitExceeded { limit: "memory" })
+    ));
+
+    // Parsed tags take up more memory, than their encoding
+    let mut children = schematic_dimensions();
+    children.push((
+        "Metadata",
+        Nbt::List((0..1 << 18).map(|_| Nbt::Byte(0)).collect()),
+    ));
+    let document = Nbt::Compound(children).document();
+    assert!(document.len() < limits.max_memory);
+    assert!(matches!(
+        Contree::from_schematic_bytes_with_limits(&document, &mapping(), &limits),
+        Err(ContreeError::LimitExceeded { limit: "memory" })
+    ));
+}
+
+//####################################################################################
+//  Anvil
+//####################################################################################
+
+/// Block names of the test section palette, the first one is air
+const SECTION_BLOCKS: [&str; 17] = [
+    "minecraft:air",
+    "minecraft:block_1",
+    "minecraft:block_2",
+    "minecraft:block_3",
+    "minecraft:block_4",
+    "minecraft:block_5",
+    "minecraft:block_6",
+    "minecraft:block_7",
+    "minecraft:block_8",
+    "minecraft:block_9",
+    "minecraft:block_10",
+    "minecraft:block_11",
+    "minecraft:block_12",
+    "minecraft:block_13",
+    "minecraft:block_14",
+    "minecraft:block_15",
+    "minecraft:oak_log",
+];
+
+/// Data versions of the supported chunk layouts
+const DATA_VERSION_1_15: i32 = 2230;
+/// Snapshot 20w17a, the first version with block states aligned to longs
+const DATA_VERSION_20W17A: i32 = 2529;
+const DATA_VERSION_1_17: i32 = 2730;
+const DATA_VERSION_1_20: i32 = 3465;
+
+/// The palette index of every block in the test section, in YZX order
+fn section_block(index: usize) -> usize {
+    (index * 7 + index / 256) % SECTION_BLOCKS.len()
+}
+
+fn section_mapping() -> BlockMapping {
+    (1..SECTION_BLOCKS.len()).fold(BlockMapping::new(GRAY), |mapping, block| {
+        let name = match SECTION_BLOCKS[block] {
+            // Properties of the palette entry are part of the block state
+            "minecraft:oak_log" => "minecraft:oak_log[axis=y]",
+            name => name,
+        };
+        mapping.with_block(name, block as u32)
+    })
+}
+
+fn section_palette() -> Nbt {
+    Nbt::List(
+        SECTION_BLOCKS
+            .iter()
+            .map(|name| match *name {
+                "minecraft:oak_log" => Nbt::Compound(vec![
+                    ("Name", Nbt::String(name)),
+                    (
+                        "Properties",
+                        Nbt::Compound(vec![("axis", Nbt::String("y"))]),
+                    ),
+                ]),
+                _ => Nbt::Compound(vec![("Name", Nbt::String(name))]),
+            })
+            .collect(),
+    )
+}
+
+/// Packs the block states of the test section, either aligned to longs or spanning across them
+fn section_block_states(aligned: bool) -> Nbt {
+    let bits = 5;
+    let entries_per_long = 64 / bits;
+    let mut longs = vec![
+        0u64;
+        if aligned {
+            4096usize.div_ceil(entries_per_long)
+        } else {
+            4096 * bits / 64
+        }
+    ];
+    for index in 0..4096 {
+        let value = section_block(index) as u64;
+        let first_bit = if aligned {
+            (index / entries_per_long) * 64 + (index % entries_per_long) * bits
+        } else {
+            index * bits
+        };
+        longs[first_bit / 64] |= value << (first_bit % 64);
+        if first_bit % 64 + bits > 64 {
+            longs[first_bit / 64 + 1] |= value >> (64 - first_bit % 64);
+        }
+    }
+    Nbt::LongArray(longs.into_iter().map(|long| long as i64).collect())
+}
+
+/// A chunk with a single section at Y = 1, in the layout used by the given data version
+fn chunk(data_version: i32) -> Nbt {
+    chunk_at(data_version, Nbt::Int(0))
+}
+
+/// A chunk like [chunk], with the given chunk X position
+fn chunk_at(data_version: i32, x_position: Nbt) -> Nbt {
+    if data_version >= DATA_VERSION_1_20 {
+        return Nbt::Compound(vec![
+            ("DataVersion", Nbt::Int(data_version)),
+            ("xPos", x_position),
+            ("zPos", Nbt::Int(0)),
+            (
+                "sections",
+                Nbt::List(vec![Nbt::Compound(vec![
+                    ("Y", Nbt::Byte(1)),
+                    (
+                        "block_states",
+                        Nbt::Compound(vec![
+                            ("palette", section_palette()),
+                            ("data", section_block_states(true)),
+                        ]),
+                    ),
+                ])]),
+            ),
+        ]);
+    }
+    Nbt::Compound(vec![
+        ("DataVersion", Nbt::Int(data_version)),
+        (
+            "Level",
+            Nbt::Compound(vec![
+                ("xPos", x_position),
+                ("zPos", Nbt::Int(0)),
+                (
+                    "Sections",
+                    Nbt::List(vec![Nbt::Compound(vec![
+                        ("Y", Nbt::Byte(1)),
+                        ("Palette", section_palette()),
+                        (
+                            "BlockStates",
+                            section_block_states(data_version >= DATA_VERSION_20W17A),
+                        ),
+                    ])]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+fn zlib(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// A region file with the given chunk at its first position
+/// * `compression` - 1: gzip, 2: zlib, 3: none
+fn region(chunk: &Nbt, compression: u8) -> Vec<u8> {
+    let document = chunk.document();
+    let payload = match compression {
+        1 => gzip(&document),
+        2 => zlib(&document),
+        _ => document,
+    };
+    region_with_payload(&payload, compression)
+}
+
+/// A region file with the given, already compressed chunk data at its first position
+fn region_with_payload(payload: &[u8], compression: u8) -> Vec<u8> {
+    let sectors = (5 + payload.len()).div_ceil(4096);
+    let mut bytes = vec![0u8; 8192];
+    bytes[0..4].copy_from_slice(&[0, 0, 2, sectors as u8]);
+    bytes.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+    bytes.push(compression);
+    bytes.extend_from_slice(payload);
+    bytes.resize(8192 + sectors * 4096, 0);
+    bytes
+}
+
+fn assert_section_content(tree: &Contree) {
+    for index in 0..4096 {
+        let (x, y, z) = (index % 16, index / 256, (index / 16) % 16);
+        let expected = match section_block(index as usize) {
+            0 => AIR,
+            block => block as u32,
+        };
+        let position = V3c::new(x, y, 15 - z);
+        assert_eq!(
+            expected,
+            tree.get(16, &position).unwrap(),
+            "Mismatch at {x},{y},{z}"
+        );
+    }
+}
+
+fn load_region_sections_with_limits(
+    name: &str,
+    region: &[u8],
+    limits: &LoadLimits,
+) -> Result<Vec<voxelhex::contree::convert::minecraft::anvil::AnvilSection>, ContreeError> {
+    let path = temp_path(name);
+    std::fs::write(&path, region).unwrap();
+    let sections = Contree::load_anvil_sections_with_limits(
+        path.to_str().unwrap(),
+        &section_mapping(),
+        limits,
+    );
+    std::fs::remove_file(&path).unwrap();
+    sections
+}
+
+fn load_region_sections(
+    name: &str,
+    region: &[u8],
+) -> Vec<voxelhex::contree::convert::minecraft::anvil::AnvilSection> {
+    load_region_sections_with_limits(name, region, &LoadLimits::default()).unwrap()
+}
+
+#[test]
+fn test_anvil_layout_before_1_16() {
+    let sections = load_region_sections("r_1_15.mca", &region(&chunk(DATA_VERSION_1_15), 2));
+    assert_eq!(1, sections.len());
+    assert_eq!(V3c::new(0, 1, 0), sections[0].position);
+    assert_section_content(&sections[0].tree);
+}
+
+#[test]
+fn test_anvil_layout_aligned_since_20w17a() {
+    for data_version in [DATA_VERSION_20W17A - 1, DATA_VERSION_20W17A] {
+        let sections = load_region_sections(
+            &format!("r_{data_version}.mca"),
+            &region(&chunk(data_version), 2),
+        );
+        assert_eq!(1, sections.len());
+        assert_section_content(&sections[0].tree);
+    }
+}
+
+#[test]
+fn test_anvil_layout_before_1_18() {
+    let sections = load_region_sections("r_1_17.mca", &region(&chunk(DATA_VERSION_1_17), 1));
+    assert_eq!(1, sections.len());
+    assert_section_content(&sections[0].tree);
+}
+
+#[test]
+fn test_anvil_layout_since_1_18() {
+    let sections = load_region_sections("r_1_20.mca", &region(&chunk(DATA_VERSION_1_20), 3));
+    assert_eq!(1, sections.len());
+    assert_eq!(V3c::new(0, 1, 0), sections[0].position);
+    assert_section_content(&sections[0].tree);
+}
+
+#[test]
+fn test_anvil_region_volume() {
+    let directory = temp_path("region");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("r.0.0.mca"),
+        region(&chunk(DATA_VERSION_1_20), 2),
+    )
+    .unwrap();
+    let tree = Contree::load_anvil_region(
+        directory.to_str().unwrap(),
+        V3c::new(0, 16, 0),
+        16,
+        &section_mapping(),
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_section_content(&tree.unwrap());
+}
+
+#[test]
+fn test_anvil_rejects_out_of_range_positions() {
+    // Chunk positions are truncated by neither the section nor the region loader
+    let region_file = region(&chunk_at(DATA_VERSION_1_20, Nbt::Long(1 << 32)), 2);
+    assert!(matches!(
+        load_region_sections_with_limits("r_long.mca", &region_file, &LoadLimits::default()),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+
+    let directory = temp_path("far_region");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("r.0.0.mca"),
+        region(&chunk_at(DATA_VERSION_1_20, Nbt::Int(i32::MAX)), 2),
+    )
+    .unwrap();
+    let far_chunk = Contree::load_anvil_region(
+        directory.to_str().unwrap(),
+        V3c::new(0, 16, 0),
+        16,
+        &section_mapping(),
+    );
+    let far_volume = Contree::load_anvil_region(
+        directory.to_str().unwrap(),
+        V3c::new(i32::MAX - 8, 16, 0),
+        16,
+        &section_mapping(),
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(matches!(far_chunk, Err(ContreeError::InvalidStructure(_))));
+    assert!(matches!(far_volume, Err(ContreeError::InvalidStructure(_))));
+}
+
+#[test]
+fn test_anvil_limits() {
+    let region = region(&chunk(DATA_VERSION_1_20), 2);
+    let limits = LoadLimits {
+        max_nodes: 1,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        load_region_sections_with_limits("r_nodes.mca", &region, &limits),
+        Err(ContreeError::LimitExceeded {
+            limit: "node count"
+        })
+    ));
+
+    let limits = LoadLimits {
+        max_memory: region.len() - 1,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        load_region_sections_with_limits("r_large.mca", &region, &limits),
+        Err(ContreeError::LimitExceeded { limit: "memory" })
+    ));
+}
+
+#[test]
+fn test_anvil_decompression_bomb() {
+    let limits = LoadLimits {
+        max_memory: 1 << 20,
+        ..LoadLimits::default()
+    };
+    for (compression, payload) in [
+        (1, gzip(&vec![0u8; 16 << 20])),
+        (2, zlib(&vec![0u8; 16 << 20])),
+    ] {
+        let region = region_with_payload(&payload, compression);
+        assert!(region.len() < limits.max_memory);
+        assert!(matches!(
+            load_region_sections_with_limits("r_bomb.mca", &region, &limits),
+            Err(ContreeError::LimitExceeded { limit: "memory" })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use voxelhex::{
+    contree::{
+        convert::point_cloud::PointCloud,
+        types::{Albedo, Contree, ContreeError, LoadLimits, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+fn gray() -> Albedo {
+    Albedo::default()
+        .with_red(128)
+        .with_green(128)
+        .with_blue(128)
+        .with_alpha(255)
+}
+
+#[test]
+fn test_xyz_colors_use_one_scale_per_file() {
+    // The second point would be read as a float color on its own
+    let cloud = PointCloud::from_xyz("0 0 0 255 128 0\n1 0 0 1 0 1\n").unwrap();
+    let first = cloud.points[0].color.unwrap();
+    let second = cloud.points[1].color.unwrap();
+    assert_eq!((255, 128, 0), (first.r, first.g, first.b));
+    assert_eq!((1, 0, 1), (second.r, second.g, second.b));
+
+    let cloud = PointCloud::from_xyz("# floats\n0,0,0,1.0,0.5,0\n1,0,0,0,0,1\n").unwrap();
+    let first = cloud.points[0].color.unwrap();
+    assert_eq!((255, 128, 0), (first.r, first.g, first.b));
+}
+
+#[test]
+fn test_point_cloud_binning() {
+    let cloud = PointCloud::from_xyz(
+        "0.1 0.1 0.1 255 0 0\n0.2 0.3 0.1 255 0 0\n3.5 0.5 0.5\n2.5 1.5 0.1 0 0 255\n",
+    )
+    .unwrap();
+    let voxels = Contree::from_point_cloud(&cloud, 1., 1, gray()).unwrap();
+    assert_eq!(4, voxels.size);
+
+    // Z is mirrored, the largest cell along Z is 0
+    assert_eq!(2, voxels.point_counts[&V3c::new(0, 0, 0)]);
+    let red = Albedo::default().with_red(255).with_alpha(255);
+    assert_eq!(
+        u32::from(red),
+        voxels.tree.get(4, &V3c::new(0, 0, 0)).unwrap()
+    );
+    assert_eq!(
+        u32::from(gray()),
+        voxels.tree.get(4, &V3c::new(3, 0, 0)).unwrap()
+    );
+    assert_eq!(AIR, voxels.tree.get(4, &V3c::new(1, 0, 0)).unwrap());
+
+    let dense_only = Contree::from_point_cloud(&cloud, 1., 2, gray()).unwrap();
+    assert_eq!(AIR, dense_only.tree.get(4, &V3c::new(3, 0, 0)).unwrap());
+    assert_eq!(
+        u32::from(red),
+        dense_only.tree.get(4, &V3c::new(0, 0, 0)).unwrap()
+    );
+}
+
+#[test]
+fn test_point_cloud_rejects_non_finite_coordinates() {
+    for line in ["NaN 0 0", "0 inf 0", "0 0 -inf"] {
+        let cloud = PointCloud::from_xyz(&format!("0 0 0\n{line}\n")).unwrap();
+        assert!(matches!(
+            Contree::from_point_cloud(&cloud, 1., 1, gray()),
+            Err(ContreeError::NonFiniteCoordinate { index: 1 })
+        ));
+    }
+}
+
+#[test]
+fn test_point_cloud_rejects_huge_extents() {
+    let cloud = PointCloud::from_xyz("0 0 0\n1e30 0 0\n").unwrap();
+    assert!(matches!(
+        Contree::from_point_cloud(&cloud, 1., 1, gray()),
+        Err(ContreeError::LimitExceeded { .. })
+    ));
+
+    let cloud = PointCloud::from_xyz("-3e38 0 0\n3e38 0 0\n").unwrap();
+    assert!(matches!(
+        Contree::from_point_cloud(&cloud, 1e-30, 1, gray()),
+        Err(ContreeError::LimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn test_point_cloud_limits() {
+    let cloud = PointCloud::from_xyz("0 0 0\n20 0 0\n").unwrap();
+    assert_eq!(
+        64,
+        Contree::from_point_cloud(&cloud, 1., 1, gray())
+            .unwrap()
+            .size
+    );
+    let shallow = LoadLimits {
+        max_depth: 2,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Contree::from_point_cloud_with_limits(&cloud, 1., 1, gray(), &shallow),
+        Err(ContreeError::LimitExceeded { limit: "depth" })
+    ));
+    let few_nodes = LoadLimits {
+        max_nodes: 2,
+        ..LoadLimits::default()
+    };
+    assert!(matches!(
+        Contree::from_point_cloud_with_limits(&cloud, 1., 1, gray(), &few_nodes),
+        Err(ContreeError::LimitExceeded {
+            limit: "node count"
+        })
+    ));
+}
+
+#[test]
+fn test_ply_ascii_and_binary() {
+    let header = |format: &str| {
+        format!(
+            "ply\nformat {format} 1.0\nelement vertex 2\nproperty float x\nproperty float y\n\
+             property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 0\nproperty list uchar int vertex_indices\nend_header\n"
+        )
+    };
+    let ascii = header("ascii") + "0 0 0 255 0 0\n1 2 3 0 255 0\n";
+    let mut binary = header("binary_little_endian").into_bytes();
+    for (position, color) in [([0f32, 0., 0.], [255u8, 0, 0]), ([1., 2., 3.], [0, 255, 0])] {
+        for component in position {
+            binary.extend_from_slice(&component.to_le_bytes());
+        }
+        binary.extend_from_slice(&color);
+    }
+
+    let from_ascii = PointCloud::from_ply(ascii.as_bytes()).unwrap();
+    let from_binary = PointCloud::from_ply(&binary).unwrap();
+    assert_eq!(2, from_ascii.points.len());
+    assert_eq!(from_ascii.points, from_binary.points);
+    assert_eq!(V3c::new(1., 2., 3.), from_ascii.points[1].position);
+    assert_eq!(255, from_ascii.points[1].color.unwrap().g);
+
+    assert!(matches!(
+        PointCloud::from_ply(b"plx\nend_header\n"),
+        Err(ContreeError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn test_ply_rejects_impossible_element_counts() {
+    // Elements without properties would take no time to read per instance, no matter their count
+    assert!(matches!(
+        PointCloud::from_ply(
+            b"ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\nend_header\n"
+        ),
+        Err(ContreeError::InvalidStructure(_))
+    ));
+    assert!(matches!(
+        PointCloud::from_ply(
+            b"ply\nformat ascii 1.0\nelement vertex 4000000000\nproperty float x\n\
+              property float y\nproperty float z\nend_header\n0 0 0\n"
+        ),
+        Err(ContreeError::UnexpectedEndOfData)
+    ));
+}
@@ -0,0 +1,167 @@
+use voxelhex::{
+    contree::{
+        convert::surface_mesh::SurfaceQuad,
+        types::{Contree, ContreeError, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+const RED: u32 = 0xFF0000FF;
+const GREEN: u32 = 0x00FF00FF;
+
+fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("voxelhex_{}_{name}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// A 4^3 solid block, a few loose voxels and a block with a voxel carved out of it
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    for x in 0..4 {
+        for y in 0..4 {
+            for z in 0..4 {
+                tree.insert(SIZE, &V3c::new(x, y, z), RED).unwrap();
+                tree.insert(SIZE, &V3c::new(x + 8, y + 4, z + 12), GREEN)
+                    .unwrap();
+            }
+        }
+    }
+    tree.insert(SIZE, &V3c::new(4, 0, 0), GREEN).unwrap();
+    tree.insert(SIZE, &V3c::new(15, 15, 15), RED).unwrap();
+    tree.insert(SIZE, &V3c::new(9, 7, 13), AIR).unwrap();
+    tree
+}
+
+/// Counts the faces between solid voxels and air by checking the neighbors of every voxel
+fn count_exposed_faces(tree: &Contree) -> usize {
+    let is_solid = |x: i32, y: i32, z: i32| {
+        (0..SIZE as i32).contains(&x)
+            && (0..SIZE as i32).contains(&y)
+            && (0..SIZE as i32).contains(&z)
+            && AIR
+                != tree
+                    .get(SIZE, &V3c::new(x as u32, y as u32, z as u32))
+                    .unwrap()
+    };
+    let mut count = 0;
+    for x in 0..SIZE as i32 {
+        for y in 0..SIZE as i32 {
+            for z in 0..SIZE as i32 {
+                if !is_solid(x, y, z) {
+                    continue;
+                }
+                for (dx, dy, dz) in [
+                    (1, 0, 0),
+                    (-1, 0, 0),
+                    (0, 1, 0),
+                    (0, -1, 0),
+                    (0, 0, 1),
+                    (0, 0, -1),
+                ] {
+                    if !is_solid(x + dx, y + dy, z + dz) {
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+    count
+}
+
+#[test]
+fn test_surface_quads_match_exposed_faces() {
+    let tree = sample_tree();
+    let quads = tree.surface_quads(SIZE, false).unwrap();
+    assert_eq!(count_exposed_faces(&tree), quads.len());
+
+    // The face between the red block and the green voxel next to it is hidden
+    let red_faces_at_x4 = quads
+        .iter()
+        .filter(|quad| {
+            RED == quad.data
+                && V3c::new(1., 0., 0.) == quad.normal
+                && quad.corners.iter().all(|corner| 4. == corner.x)
+        })
+        .count();
+    assert_eq!(15, red_faces_at_x4);
+}
+
+#[test]
+fn test_surface_quads_merge_uniform_faces() {
+    let mut tree = Contree::new();
+    for x in 0..4 {
+        for y in 0..4 {
+            for z in 0..4 {
+                tree.insert(SIZE, &V3c::new(x + 4, y + 4, z + 4), RED)
+                    .unwrap();
+            }
+        }
+    }
+    let count_red = |quads: Vec<SurfaceQuad>| quads.iter().filter(|quad| RED == quad.data).count();
+    assert_eq!(6 * 16, count_red(tree.surface_quads(SIZE, false).unwrap()));
+    assert_eq!(6, count_red(tree.surface_quads(SIZE, true).unwrap()));
+
+    // A tree filled entirely only has faces on its boundary
+    let full = Contree::Leaf(GREEN);
+    assert_eq!(6, full.surface_quads(SIZE, true).unwrap().len());
+    assert_eq!(
+        6 * SIZE as usize * SIZE as usize,
+        full.surface_quads(SIZE, false).unwrap().len()
+    );
+    assert_eq!(6, full.surface_quads(1, false).unwrap().len());
+    assert!(Contree::new().surface_quads(SIZE, true).unwrap().is_empty());
+}
+
+#[test]
+fn test_surface_quads_of_collapsed_tree() {
+    // The whole tree is a single leaf, its size can only come from the caller
+    let mut tree = Contree::new();
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                tree.insert(SIZE, &V3c::new(x, y, z), RED).unwrap();
+            }
+        }
+    }
+    assert!(matches!(tree, Contree::Leaf(RED)));
+    let quads = tree.surface_quads(SIZE, true).unwrap();
+    assert_eq!(6, quads.len());
+    assert!(quads
+        .iter()
+        .flat_map(|quad| quad.corners.iter())
+        .all(|corner| [corner.x, corner.y, corner.z]
+            .iter()
+            .all(|value| 0. == *value || SIZE as f32 == *value)));
+
+    assert!(matches!(
+        tree.surface_quads(10, true),
+        Err(ContreeError::InvalidSize(10))
+    ));
+}
+
+#[test]
+fn test_export_glb() {
+    let path = temp_path("surface.glb");
+    sample_tree().export_glb(&path, SIZE).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(b"glTF", &bytes[0..4]);
+    assert_eq!(
+        bytes.len() as u32,
+        u32::from_le_bytes(bytes[8..12].try_into().unwrap())
+    );
+    assert!(String::from_utf8_lossy(&bytes).contains("\"accessors\""));
+
+    // An empty tree has no mesh, as glTF accessors must not be empty
+    Contree::new().export_glb(&path, SIZE).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let json_length = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+    assert_eq!(12 + 8 + json_length, bytes.len());
+    let json = String::from_utf8_lossy(&bytes[20..]);
+    assert!(!json.contains("accessors"));
+    assert!(!json.contains("meshes"));
+}
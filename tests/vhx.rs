@@ -0,0 +1,86 @@
+use voxelhex::{
+    contree::types::{Contree, ContreeError},
+    spatial::math::vector::V3c,
+};
+
+const SIZE: u32 = 16;
+
+fn sample_tree() -> Contree {
+    let mut tree = Contree::new();
+    tree.insert(SIZE, &V3c::new(0, 0, 0), 0xFF0000FF).unwrap();
+    tree.insert(SIZE, &V3c::new(1, 0, 0), 0x00FF00FF).unwrap();
+    tree.insert(SIZE, &V3c::new(5, 9, 13), 0xFF0000FF).unwrap();
+    tree
+}
+
+/// The offset of the payload of the palette and the tree section inside the given .vhx data
+fn section_payloads(bytes: &[u8]) -> (usize, usize) {
+    let palette_length = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+    (12, 12 + palette_length + 4 + 4)
+}
+
+#[test]
+fn test_vhx_round_trip() {
+    let tree = sample_tree();
+    let loaded = Contree::from_vhx_bytes(&tree.to_vhx_bytes()).unwrap();
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                let position = V3c::new(x, y, z);
+                assert_eq!(
+                    tree.get(SIZE, &position).unwrap(),
+                    loaded.get(SIZE, &position).unwrap(),
+                    "Mismatch at {x},{y},{z}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_vhx_truncated_data_is_rejected() {
+    let bytes = sample_tree().to_vhx_bytes();
+    for length in 8..bytes.len() {
+        assert!(
+            matches!(
+                Contree::from_vhx_bytes(&bytes[..length]),
+                Err(ContreeError::UnexpectedEndOfData)
+            ),
+            "Data truncated to {length} bytes was accepted"
+        );
+    }
+}
+
+#[test]
+fn test_vhx_flipped_bits_are_detected() {
+    let bytes = sample_tree().to_vhx_bytes();
+    let (palette_payload, tree_payload) = section_payloads(&bytes);
+
+    let mut corrupted = bytes.clone();
+    corrupted[palette_payload + 4] ^= 0x10;
+    assert!(matches!(
+        Contree::from_vhx_bytes(&corrupted),
+        Err(ContreeError::ChecksumMismatch { section: "palette" })
+    ));
+
+    let mut corrupted = bytes.clone();
+    corrupted[tree_payload + 1] ^= 0x01;
+    assert!(matches!(
+        Contree::from_vhx_bytes(&corrupted),
+        Err(ContreeError::ChecksumMismatch { section: "tree" })
+    ));
+}
+
+#[test]
+fn test_vhx_invalid_header_is_rejected() {
+    let mut bytes = sample_tree().to_vhx_bytes();
+    bytes[0] = b'X';
+    assert!(matches!(
+        Contree::from_vhx_bytes(&bytes),
+        Err(ContreeError::InvalidHeader)
+    ));
+    assert!(matches!(
+        Contree::from_vhx_bytes(b"VH"),
+        Err(ContreeError::InvalidHeader) | Err(ContreeError::UnexpectedEndOfData)
+    ));
+}
@@ -0,0 +1,83 @@
+use voxelhex::{
+    contree::{
+        convert::volume_slices::SliceFormat,
+        types::{Contree, AIR},
+    },
+    spatial::math::vector::V3c,
+};
+
+const DENSE: u32 = 0xFFFFFFFF;
+
+fn temp_directory(name: &str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("voxelhex_{}_{name}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Voxels with a density above 100 are set
+fn threshold(density: u16) -> u32 {
+    if density > 100 {
+        DENSE - density as u32
+    } else {
+        AIR
+    }
+}
+
+#[test]
+fn test_pgm_stack() {
+    let directory = temp_directory("pgm_stack");
+    // Slices are ordered numerically, ASCII and binary PGM can be mixed
+    std::fs::write(
+        directory.join("slice_2.pgm"),
+        b"P5\n3 2\n255\n\x00\x00\xC8\x00\x00\x00",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("slice_10.pgm"),
+        b"P5 3 2 255 \x00\x00\x00\x00\x00\xFF",
+    )
+    .unwrap();
+    std::fs::write(
+        directory.join("slice_1.pgm"),
+        b"P2\n# comment\n3 2\n255\n150 0 0\n0 0 0\n",
+    )
+    .unwrap();
+
+    let (tree, size) =
+        Contree::load_image_stack(directory.to_str().unwrap(), SliceFormat::Pgm, threshold)
+            .unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(4, size);
+    assert_eq!(DENSE - 150, tree.get(size, &V3c::new(0, 0, 0)).unwrap());
+    assert_eq!(DENSE - 200, tree.get(size, &V3c::new(2, 1, 0)).unwrap());
+    assert_eq!(DENSE - 255, tree.get(size, &V3c::new(2, 2, 1)).unwrap());
+    assert_eq!(AIR, tree.get(size, &V3c::new(1, 0, 0)).unwrap());
+    assert_eq!(AIR, tree.get(size, &V3c::new(2, 0, 1)).unwrap());
+}
+
+#[test]
+fn test_raw_stack() {
+    let directory = temp_directory("raw_stack");
+    let format = SliceFormat::Raw {
+        width: 5,
+        height: 2,
+        bytes_per_sample: 2,
+        little_endian: true,
+    };
+    let mut slice = vec![0u8; 20];
+    slice[18..20].copy_from_slice(&1000u16.to_le_bytes());
+    std::fs::write(directory.join("0.raw"), &slice).unwrap();
+
+    let (tree, size) =
+        Contree::load_image_stack(directory.to_str().unwrap(), format, threshold).unwrap();
+    assert_eq!(16, size);
+    assert_eq!(DENSE - 1000, tree.get(size, &V3c::new(4, 0, 1)).unwrap());
+    assert_eq!(AIR, tree.get(size, &V3c::new(0, 0, 0)).unwrap());
+
+    // Slices not matching the given dimensions are rejected
+    std::fs::write(directory.join("1.raw"), &slice[..10]).unwrap();
+    let result = Contree::load_image_stack(directory.to_str().unwrap(), format, threshold);
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(result.is_err());
+}